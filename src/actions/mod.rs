@@ -1,14 +1,10 @@
-use bevy::math::Vec3Swizzles;
 use bevy::prelude::*;
 
 use crate::actions::game_control::{get_movement, GameControl};
-use crate::player::Player;
-use crate::GameState;
+use crate::PlayState;
 
 mod game_control;
 
-pub const FOLLOW_EPSILON: f32 = 5.;
-
 pub struct ActionsPlugin;
 
 // This plugin listens for keyboard input and converts the input into Actions.
@@ -17,43 +13,30 @@ impl Plugin for ActionsPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<Actions>().add_systems(
             Update,
-            set_movement_actions.run_if(in_state(GameState::Playing)),
+            set_movement_actions.run_if(in_state(PlayState::Running)),
         );
     }
 }
 
 #[derive(Default, Resource)]
 pub struct Actions {
-    pub player_movement: Option<Vec2>,
+    pub camera_movement: Option<Vec2>,
 }
 
 pub fn set_movement_actions(
     mut actions: ResMut<Actions>,
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    touch_input: Res<Touches>,
-    player: Query<&Transform, With<Player>>,
-    camera: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
 ) {
-    let mut player_movement = Vec2::new(
+    let camera_movement = Vec2::new(
         get_movement(GameControl::Right, &keyboard_input)
             - get_movement(GameControl::Left, &keyboard_input),
         get_movement(GameControl::Up, &keyboard_input)
             - get_movement(GameControl::Down, &keyboard_input),
     );
 
-    if let Some(touch_position) = touch_input.first_pressed_position() {
-        let (camera, camera_transform) = camera.single();
-        if let Ok(touch_position) = camera.viewport_to_world_2d(camera_transform, touch_position) {
-            let diff = touch_position - player.single().translation.xy();
-            if diff.length() > FOLLOW_EPSILON {
-                player_movement = diff.normalize();
-            }
-        }
-    }
-
-    if player_movement != Vec2::ZERO {
-        actions.player_movement = Some(player_movement.normalize());
+    if camera_movement != Vec2::ZERO {
+        actions.camera_movement = Some(camera_movement.normalize());
     } else {
-        actions.player_movement = None;
+        actions.camera_movement = None;
     }
 }
@@ -1,5 +1,6 @@
-use crate::actions::{set_movement_actions, Actions};
+use crate::build::TileChanged;
 use crate::loading::AudioAssets;
+use crate::pause::GameSettings;
 use crate::GameState;
 use bevy::prelude::*;
 use bevy_kira_audio::prelude::*;
@@ -9,48 +10,24 @@ pub struct InternalAudioPlugin;
 // This plugin is responsible to control the game audio
 impl Plugin for InternalAudioPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(AudioPlugin)
-            .add_systems(OnEnter(GameState::Playing), start_audio)
-            .add_systems(
-                Update,
-                control_flying_sound
-                    .after(set_movement_actions)
-                    .run_if(in_state(GameState::Playing)),
-            );
+        app.add_plugins(AudioPlugin).add_systems(
+            Update,
+            play_build_sound.run_if(in_state(GameState::Playing)),
+        );
     }
 }
 
-#[derive(Resource)]
-struct FlyingAudio(Handle<AudioInstance>);
-
-fn start_audio(mut commands: Commands, audio_assets: Res<AudioAssets>, audio: Res<Audio>) {
-    audio.pause();
-    let handle = audio
-        .play(audio_assets.flying.clone())
-        .looped()
-        .with_volume(0.3)
-        .handle();
-    commands.insert_resource(FlyingAudio(handle));
-}
-
-fn control_flying_sound(
-    actions: Res<Actions>,
-    audio: Res<FlyingAudio>,
-    mut audio_instances: ResMut<Assets<AudioInstance>>,
+/// Plays a sound whenever the player built on or bulldozed tiles this frame, unless sound is off
+fn play_build_sound(
+    mut changed_events: EventReader<TileChanged>,
+    settings: Res<GameSettings>,
+    audio_assets: Res<AudioAssets>,
+    audio: Res<Audio>,
 ) {
-    if let Some(instance) = audio_instances.get_mut(&audio.0) {
-        match instance.state() {
-            PlaybackState::Paused { .. } => {
-                if actions.player_movement.is_some() {
-                    instance.resume(AudioTween::default());
-                }
-            }
-            PlaybackState::Playing { .. } => {
-                if actions.player_movement.is_none() {
-                    instance.pause(AudioTween::default());
-                }
-            }
-            _ => {}
-        }
+    if changed_events.read().last().is_none() || !settings.sound {
+        return;
     }
+    audio
+        .play(audio_assets.build.clone())
+        .with_volume(0.3);
 }
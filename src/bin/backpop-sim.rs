@@ -0,0 +1,118 @@
+use std::path::{Path, PathBuf};
+
+use backpop::{
+    CityMap, HeadlessPlugin, Layout, NewCitySettings, Scenario, SimSeed, StatsHistory, MAX_MAP_SIDE,
+};
+use bevy_ecs_tilemap::map::TilemapSize;
+use bevy::prelude::*;
+
+/// Runs the city simulation without a window for a number of simulated days
+/// and prints the city aggregates after every day.
+///
+/// Usage: `backpop-sim [DAYS] [--seed SEED] [--map-size WIDTHxHEIGHT] [--layout grid|organic|scattered]
+/// [--density DENSITY] [--zones RESIDENTIAL,COMMERCIAL,INDUSTRIAL] [--map FILE] [--scenario FILE]
+/// [--export DIRECTORY] [--sample-ticks TICKS] [--history-length SAMPLES]`
+/// (defaults to 30 days, a random seed and a 32x32 grid city). With `--export`, the statistics sampled every
+/// `TICKS` ticks are written to `DIRECTORY` as CSV and JSON once the run ends.
+/// `--map` builds the city from a `.map.txt` or `.map.png` file instead of generating it.
+/// `--scenario` plays a `.scenario.ron` file, whose map is looked up in `assets/`, and stops once it is decided.
+fn main() -> AppExit {
+    let mut days = 30;
+    let mut export = None;
+    let mut history = StatsHistory::default();
+    let mut settings = NewCitySettings::default();
+    let mut scenario_seed = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            // Read by `SimSeed::from_args`
+            "--seed" => {
+                args.next();
+            }
+            "--map-size" => {
+                let size = args.next().expect("--map-size requires a size");
+                settings.map_size = parse_map_size(&size);
+            }
+            "--layout" => {
+                let layout = args.next().expect("--layout requires a layout");
+                settings.layout = Layout::ALL
+                    .into_iter()
+                    .find(|candidate| candidate.label() == layout)
+                    .expect("the layout must be grid, organic or scattered");
+            }
+            "--density" => {
+                let density = args.next().expect("--density requires a value");
+                settings.params.density = density.parse().expect("DENSITY must be a number from 0 to 1");
+            }
+            "--zones" => {
+                let zones = args.next().expect("--zones requires three shares");
+                let shares: Vec<f32> = zones
+                    .split(',')
+                    .map(|share| share.parse().expect("zone shares must be numbers"))
+                    .collect();
+                let [residential, commercial, industrial] = shares[..] else {
+                    panic!("--zones takes three shares separated by commas");
+                };
+                settings.params.residential = residential;
+                settings.params.commercial = commercial;
+                settings.params.industrial = industrial;
+            }
+            "--map" => {
+                let path = args.next().expect("--map requires a file");
+                match CityMap::read(Path::new(&path)) {
+                    Ok(map) => settings.map = Some(map),
+                    Err(error) => panic!("Failed to read the map {path}: {error}"),
+                }
+            }
+            "--scenario" => {
+                let path = args.next().expect("--scenario requires a file");
+                let scenario = Scenario::read(Path::new(&path))
+                    .unwrap_or_else(|error| panic!("Failed to read the scenario {path}: {error}"));
+                let map = scenario.map.as_ref().map(|map| {
+                    CityMap::read(&Path::new("assets").join(map))
+                        .unwrap_or_else(|error| panic!("Failed to read the map {map}: {error}"))
+                });
+                let mut seed = SimSeed::default();
+                scenario.configure(&mut settings, &mut seed, map);
+                scenario_seed = scenario.seed.map(|_| seed);
+            }
+            "--export" => {
+                export = Some(PathBuf::from(args.next().expect("--export requires a directory")));
+            }
+            "--sample-ticks" => {
+                let ticks = args.next().expect("--sample-ticks requires a value");
+                let ticks = ticks.parse().expect("TICKS must be a positive number");
+                history = StatsHistory::new(ticks, history.capacity());
+            }
+            "--history-length" => {
+                let length = args.next().expect("--history-length requires a value");
+                let length = length.parse().expect("SAMPLES must be a positive number");
+                history = StatsHistory::new(history.interval_ticks(), length);
+            }
+            _ => days = arg.parse().expect("DAYS must be a positive number"),
+        }
+    }
+
+    let mut app = App::new();
+    if let Some(seed) = SimSeed::from_args().or(scenario_seed) {
+        app.insert_resource(seed);
+    }
+    app.insert_resource(history)
+        .insert_resource(settings)
+        .add_plugins((MinimalPlugins, HeadlessPlugin { days, export }))
+        .run()
+}
+
+/// Parses `WIDTHxHEIGHT`, or a single number for a square map
+fn parse_map_size(size: &str) -> TilemapSize {
+    let parse_side = |side: &str| -> u32 {
+        match side.parse() {
+            Ok(side @ 1..=MAX_MAP_SIDE) => side,
+            _ => panic!("map sides must be between 1 and {MAX_MAP_SIDE}"),
+        }
+    };
+    match size.split_once('x') {
+        Some((x, y)) => TilemapSize { x: parse_side(x), y: parse_side(y) },
+        None => TilemapSize { x: parse_side(size), y: parse_side(size) },
+    }
+}
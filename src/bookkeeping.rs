@@ -0,0 +1,247 @@
+use bevy::prelude::*;
+
+use crate::constants::{FIRING_ABSENCE_DAYS, RETIREMENT_AGE};
+use crate::tilemap::{GameClock, House, Job, Pop, Workplace};
+
+/// Gives the pop a job at `workplace` and adds it to the workplace's employees.
+/// The pop must not have a job already, see [`leave_job`].
+pub(crate) fn hire(
+    pop_entity: Entity,
+    pop: &mut Pop,
+    workplace_entity: Entity,
+    workplace: &mut Workplace,
+    day: u64,
+) {
+    debug_assert!(pop.job.is_none(), "{pop_entity} was hired while still employed");
+    pop.job = Some(Job {
+        workplace: workplace_entity,
+        salary: workplace.wage,
+        position: workplace.position,
+        // Nobody gets fired before their first shift
+        last_worked_day: day,
+    });
+    workplace.employees.push(pop_entity);
+}
+
+/// Ends the pop's job, whether it quit, got fired or retired, and drops it from the employees.
+/// The workplace may already be gone, e.g. when it was bulldozed.
+pub(crate) fn leave_job(
+    pop_entity: Entity,
+    pop: &mut Pop,
+    workplace_query: &mut Query<&mut Workplace>,
+) {
+    let Some(job) = pop.job.take() else {
+        return;
+    };
+    pop.shift_ticks = 0;
+    if let Ok(mut workplace) = workplace_query.get_mut(job.workplace) {
+        workplace.employees.retain(|employee| *employee != pop_entity);
+    }
+}
+
+/// Makes `house` the pop's home and adds the pop to its residents.
+/// The pop must be homeless, see [`move_out`].
+pub(crate) fn move_in(pop_entity: Entity, pop: &mut Pop, house_entity: Entity, house: &mut House) {
+    debug_assert!(pop.home.is_none(), "{pop_entity} moved in while still having a home");
+    pop.home = Some(house_entity);
+    pop.unpaid_rent_days = 0;
+    house.residents.push(pop_entity);
+}
+
+/// Leaves the pop homeless and drops it from the residents of its former home
+pub(crate) fn move_out(pop_entity: Entity, pop: &mut Pop, house_query: &mut Query<&mut House>) {
+    let Some(home) = pop.home.take() else {
+        return;
+    };
+    if let Ok(mut house) = house_query.get_mut(home) {
+        house.residents.retain(|resident| *resident != pop_entity);
+    }
+}
+
+/// Once a day pops who haven't shown up at work for more than `FIRING_ABSENCE_DAYS` days are
+/// fired, and pops who reached `RETIREMENT_AGE` retire
+pub(crate) fn review_employment(
+    game_clock: Res<GameClock>,
+    mut pop_query: Query<(Entity, &mut Pop)>,
+    mut workplace_query: Query<&mut Workplace>,
+) {
+    if !game_clock.is_day_start() {
+        return;
+    }
+
+    let today = game_clock.day();
+    for (pop_entity, mut pop) in pop_query.iter_mut() {
+        let Some(job) = &pop.job else {
+            continue;
+        };
+        let absent = today.saturating_sub(job.last_worked_day) > FIRING_ABSENCE_DAYS;
+        let retiring = pop.age >= RETIREMENT_AGE;
+        if absent || retiring {
+            leave_job(pop_entity, &mut pop, &mut workplace_query);
+        }
+    }
+}
+
+/// Panics with a report of every mismatch if the two sides of a link disagree:
+/// `Pop::job` and `Workplace::employees`, `Pop::home` and `House::residents`, or two partners.
+/// Only scheduled in debug builds, where it runs after every tick.
+#[cfg(debug_assertions)]
+pub(crate) fn check_links(
+    pop_query: Query<(Entity, &Pop)>,
+    workplace_query: Query<(Entity, &Workplace)>,
+    house_query: Query<(Entity, &House)>,
+) {
+    let mut problems = Vec::new();
+
+    for (entity, pop) in pop_query.iter() {
+        if let Some(job) = &pop.job {
+            match workplace_query.get(job.workplace) {
+                Ok((_, workplace)) if workplace.employees.contains(&entity) => {}
+                Ok(_) => problems.push(format!(
+                    "pop {entity} works at {} but is not one of its employees",
+                    job.workplace
+                )),
+                Err(_) => problems.push(format!(
+                    "pop {entity} works at {}, which is not a workplace",
+                    job.workplace
+                )),
+            }
+        }
+        if let Some(home) = pop.home {
+            match house_query.get(home) {
+                Ok((_, house)) if house.residents.contains(&entity) => {}
+                Ok(_) => problems.push(format!(
+                    "pop {entity} lives in {home} but is not one of its residents"
+                )),
+                Err(_) => problems.push(format!("pop {entity} lives in {home}, which is not a house")),
+            }
+        }
+        if let Some(partner) = pop.partner {
+            let partners_partner = pop_query.get(partner).ok().and_then(|(_, partner)| partner.partner);
+            if partners_partner != Some(entity) {
+                problems.push(format!(
+                    "pop {entity} is partnered with {partner}, whose partner is {partners_partner:?}"
+                ));
+            }
+        }
+    }
+
+    for (entity, workplace) in workplace_query.iter() {
+        if workplace.employees.len() > workplace.capacity as usize {
+            problems.push(format!(
+                "workplace {entity} has {} employees but only room for {}",
+                workplace.employees.len(),
+                workplace.capacity
+            ));
+        }
+        for (index, employee) in workplace.employees.iter().enumerate() {
+            if workplace.employees[..index].contains(employee) {
+                problems.push(format!("workplace {entity} lists employee {employee} twice"));
+            }
+            match pop_query.get(*employee) {
+                Ok((_, pop)) if pop.job.as_ref().is_some_and(|job| job.workplace == entity) => {}
+                Ok(_) => problems.push(format!(
+                    "workplace {entity} lists employee {employee}, who works elsewhere"
+                )),
+                Err(_) => problems.push(format!(
+                    "workplace {entity} lists employee {employee}, who is not a pop"
+                )),
+            }
+        }
+    }
+
+    for (entity, house) in house_query.iter() {
+        if house.residents.len() > house.capacity as usize {
+            problems.push(format!(
+                "house {entity} has {} residents but only room for {}",
+                house.residents.len(),
+                house.capacity
+            ));
+        }
+        for (index, resident) in house.residents.iter().enumerate() {
+            if house.residents[..index].contains(resident) {
+                problems.push(format!("house {entity} lists resident {resident} twice"));
+            }
+            match pop_query.get(*resident) {
+                Ok((_, pop)) if pop.home == Some(entity) => {}
+                Ok(_) => problems.push(format!(
+                    "house {entity} lists resident {resident}, who lives elsewhere"
+                )),
+                Err(_) => problems.push(format!(
+                    "house {entity} lists resident {resident}, who is not a pop"
+                )),
+            }
+        }
+    }
+
+    if !problems.is_empty() {
+        panic!("Pop and building links diverged:\n  {}", problems.join("\n  "));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::*;
+    use bevy_ecs_tilemap::prelude::*;
+
+    use crate::bookkeeping::{check_links, hire, review_employment};
+    use crate::constants::{BASE_WAGE, FIRING_ABSENCE_DAYS, FIRM_STARTING_CAPITAL};
+    use crate::firms::Product;
+    use crate::tilemap::{GameClock, Pop, Workplace};
+
+    fn workplace() -> Workplace {
+        Workplace {
+            capacity: 2,
+            employees: Vec::new(),
+            position: TilePos { x: 0, y: 0 },
+            wage: BASE_WAGE,
+            treasury: FIRM_STARTING_CAPITAL,
+            product: Product::Goods,
+            inventory: 0.0,
+            sales: 0,
+            daily_sales: 0,
+        }
+    }
+
+    #[test]
+    fn test_absent_pops_are_fired() {
+        let mut app = App::new();
+        let mut game_clock = GameClock::default();
+        game_clock.current_tick = game_clock.ticks_per_day() * (FIRING_ABSENCE_DAYS + 1);
+        let today = game_clock.day();
+        app.insert_resource(game_clock);
+
+        let world = app.world_mut();
+        let workplace_entity = world.spawn_empty().id();
+        let mut workplace = workplace();
+        let mut pops = Vec::new();
+        for last_worked_day in [today - FIRING_ABSENCE_DAYS, today - FIRING_ABSENCE_DAYS - 1] {
+            let entity = world.spawn_empty().id();
+            let mut pop = Pop { age: 30, ..Default::default() };
+            hire(entity, &mut pop, workplace_entity, &mut workplace, last_worked_day);
+            world.entity_mut(entity).insert(pop);
+            pops.push(entity);
+        }
+        world.entity_mut(workplace_entity).insert(workplace);
+
+        world.run_system_once(review_employment).unwrap();
+        world.run_system_once(check_links).unwrap();
+
+        assert!(world.get::<Pop>(pops[0]).unwrap().job.is_some());
+        assert!(world.get::<Pop>(pops[1]).unwrap().job.is_none());
+        assert_eq!(world.get::<Workplace>(workplace_entity).unwrap().employees, vec![pops[0]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "lists employee")]
+    fn test_diverged_links_panic() {
+        let mut world = World::new();
+        let pop = world.spawn(Pop::default()).id();
+        let mut workplace = workplace();
+        workplace.employees.push(pop);
+        world.spawn(workplace);
+
+        world.run_system_once(check_links).unwrap();
+    }
+}
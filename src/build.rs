@@ -0,0 +1,387 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::bookkeeping::{leave_job, move_out};
+use crate::coords::{MapGeometry, WorldPos};
+use crate::pathfinding::{NavGrid, GROUND_COST, ROAD_COST};
+use crate::tilemap::{
+    spawn_empty_tile, spawn_farm, spawn_house, spawn_restaurant, spawn_road, spawn_workplace, Building,
+    House, HousingMarket, JobMarket, Pop, PopState, Road, Tilemap, Workplace,
+};
+use crate::ui::PointerOverUi;
+use crate::{GameState, PlayState};
+
+pub struct BuildPlugin;
+
+/// This plugin lets the player change the city: pick a tool from the toolbar,
+/// then click or tap tiles to place buildings and roads or bulldoze them.
+/// Building only happens on empty tiles, everything else has to be bulldozed first.
+impl Plugin for BuildPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SelectedTool>()
+            .add_event::<BuildTile>()
+            .add_event::<TileChanged>()
+            .add_systems(OnEnter(GameState::Playing), spawn_toolbar)
+            .add_systems(
+                Update,
+                (
+                    handle_tool_hotkeys,
+                    click_tool_buttons,
+                    color_tool_buttons,
+                    click_tiles,
+                    apply_builds,
+                )
+                    .chain()
+                    .run_if(in_state(PlayState::Running)),
+            )
+            .add_systems(OnExit(GameState::Playing), despawn_toolbar);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum BuildTool {
+    House,
+    Workplace,
+    Farm,
+    Restaurant,
+    Road,
+    Bulldoze,
+}
+
+impl BuildTool {
+    const ALL: [BuildTool; 6] = [
+        BuildTool::House,
+        BuildTool::Workplace,
+        BuildTool::Farm,
+        BuildTool::Restaurant,
+        BuildTool::Road,
+        BuildTool::Bulldoze,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            BuildTool::House => "1 House",
+            BuildTool::Workplace => "2 Work",
+            BuildTool::Farm => "3 Farm",
+            BuildTool::Restaurant => "4 Food",
+            BuildTool::Road => "5 Road",
+            BuildTool::Bulldoze => "6 Bulldoze",
+        }
+    }
+
+    fn hotkey(self) -> KeyCode {
+        match self {
+            BuildTool::House => KeyCode::Digit1,
+            BuildTool::Workplace => KeyCode::Digit2,
+            BuildTool::Farm => KeyCode::Digit3,
+            BuildTool::Restaurant => KeyCode::Digit4,
+            BuildTool::Road => KeyCode::Digit5,
+            BuildTool::Bulldoze => KeyCode::Digit6,
+        }
+    }
+
+    /// Cost of walking onto a tile once this tool was used on it
+    fn path_cost(self) -> Option<u32> {
+        match self {
+            BuildTool::House | BuildTool::Workplace | BuildTool::Farm | BuildTool::Restaurant => None,
+            BuildTool::Road => Some(ROAD_COST),
+            BuildTool::Bulldoze => Some(GROUND_COST),
+        }
+    }
+}
+
+/// The tool clicks on the map use, if any
+#[derive(Resource, Default)]
+pub(crate) struct SelectedTool(pub Option<BuildTool>);
+
+/// Uses `tool` on `tile`
+#[derive(Event, Clone, Copy, Debug)]
+pub(crate) struct BuildTile {
+    pub tile: TilePos,
+    pub tool: BuildTool,
+}
+
+/// Sent after a tile was built on or bulldozed
+#[derive(Event, Clone, Copy, Debug)]
+pub(crate) struct TileChanged(pub TilePos);
+
+#[derive(Component)]
+struct Toolbar;
+
+#[derive(Component)]
+struct ToolButton(BuildTool);
+
+const TOOL_NORMAL: Color = Color::linear_rgb(0.15, 0.15, 0.15);
+const TOOL_HOVERED: Color = Color::linear_rgb(0.25, 0.25, 0.25);
+const TOOL_SELECTED: Color = Color::linear_rgb(0.2, 0.4, 0.6);
+
+fn spawn_toolbar(mut commands: Commands) {
+    commands
+        .spawn((
+            Node {
+                flex_direction: FlexDirection::Row,
+                justify_content: JustifyContent::Center,
+                column_gap: Val::Px(5.0),
+                bottom: Val::Px(10.0),
+                width: Val::Percent(100.0),
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+            Toolbar,
+        ))
+        .with_children(|children| {
+            for tool in BuildTool::ALL {
+                children
+                    .spawn((
+                        Button,
+                        Node {
+                            width: Val::Px(100.0),
+                            height: Val::Px(40.0),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        BackgroundColor(TOOL_NORMAL),
+                        ToolButton(tool),
+                    ))
+                    .with_child((
+                        Text::new(tool.label()),
+                        TextFont {
+                            font_size: 18.0,
+                            ..default()
+                        },
+                        TextColor(Color::linear_rgb(0.9, 0.9, 0.9)),
+                    ));
+            }
+        });
+}
+
+fn despawn_toolbar(mut commands: Commands, toolbar_query: Query<Entity, With<Toolbar>>) {
+    for entity in toolbar_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Picking the selected tool again puts it away
+fn toggle_tool(selected: &mut SelectedTool, tool: BuildTool) {
+    selected.0 = if selected.0 == Some(tool) {
+        None
+    } else {
+        Some(tool)
+    };
+}
+
+fn handle_tool_hotkeys(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    mut selected: ResMut<SelectedTool>,
+) {
+    for tool in BuildTool::ALL {
+        if keyboard_input.just_pressed(tool.hotkey()) {
+            toggle_tool(&mut selected, tool);
+        }
+    }
+    if mouse_input.just_pressed(MouseButton::Right) {
+        selected.0 = None;
+    }
+}
+
+fn click_tool_buttons(
+    interaction_query: Query<(&Interaction, &ToolButton), Changed<Interaction>>,
+    mut selected: ResMut<SelectedTool>,
+) {
+    for (interaction, button) in interaction_query.iter() {
+        if *interaction == Interaction::Pressed {
+            toggle_tool(&mut selected, button.0);
+        }
+    }
+}
+
+fn color_tool_buttons(
+    mut button_query: Query<(&Interaction, &ToolButton, &mut BackgroundColor)>,
+    selected: Res<SelectedTool>,
+) {
+    for (interaction, button, mut color) in button_query.iter_mut() {
+        *color = if selected.0 == Some(button.0) {
+            TOOL_SELECTED.into()
+        } else if *interaction == Interaction::Hovered {
+            TOOL_HOVERED.into()
+        } else {
+            TOOL_NORMAL.into()
+        };
+    }
+}
+
+/// Uses the selected tool on the tile under the cursor or finger.
+/// Holding the button down paints, each tile is only sent once per stroke.
+fn click_tiles(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    touch_input: Res<Touches>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    pointer_over_ui: PointerOverUi,
+    selected: Res<SelectedTool>,
+    geometry: Option<Res<MapGeometry>>,
+    mut last_tile: Local<Option<TilePos>>,
+    mut build_events: EventWriter<BuildTile>,
+) {
+    let (Some(tool), Some(geometry)) = (selected.0, geometry) else {
+        return;
+    };
+
+    let screen_position = if mouse_input.pressed(MouseButton::Left) {
+        window_query.get_single().ok().and_then(Window::cursor_position)
+    } else {
+        touch_input.first_pressed_position()
+    };
+    let Some(screen_position) = screen_position else {
+        *last_tile = None;
+        return;
+    };
+    // Clicks on the toolbar or any other panel are not meant for the map
+    if pointer_over_ui.at(screen_position) {
+        return;
+    }
+
+    let Ok((camera, camera_transform)) = camera_query.get_single() else {
+        return;
+    };
+    let Ok(world_position) = camera.viewport_to_world_2d(camera_transform, screen_position) else {
+        return;
+    };
+    let Some(tile) = geometry.tile_at(WorldPos(world_position)) else {
+        return;
+    };
+    if *last_tile != Some(tile) {
+        *last_tile = Some(tile);
+        build_events.send(BuildTile { tile, tool });
+    }
+}
+
+/// Carries out [`BuildTile`] requests: swaps the tile entity in the `TileStorage`,
+/// takes demolished buildings off the markets and unlinks the pops that lived, worked or ate there
+fn apply_builds(
+    mut commands: Commands,
+    mut build_events: EventReader<BuildTile>,
+    mut changed_events: EventWriter<TileChanged>,
+    mut tilemap_query: Query<(Entity, &mut TileStorage), With<Tilemap>>,
+    occupied_query: Query<(), Or<(With<Building>, With<Road>)>>,
+    mut pop_query: Query<(Entity, &mut Pop)>,
+    mut workplace_query: Query<&mut Workplace>,
+    mut house_query: Query<&mut House>,
+    mut job_market: ResMut<JobMarket>,
+    mut housing_market: ResMut<HousingMarket>,
+    mut nav_grid: ResMut<NavGrid>,
+) {
+    let Ok((tilemap_entity, mut tile_storage)) = tilemap_query.get_single_mut() else {
+        return;
+    };
+
+    for BuildTile { tile, tool } in build_events.read().copied() {
+        if !tile.within_map_bounds(&tile_storage.size) {
+            continue;
+        }
+        let Some(old_entity) = tile_storage.get(&tile) else {
+            continue;
+        };
+        let occupied = occupied_query.contains(old_entity);
+        if occupied != (tool == BuildTool::Bulldoze) {
+            continue;
+        }
+
+        if occupied {
+            job_market.available_jobs.retain(|(workplace, ..)| *workplace != old_entity);
+            housing_market.available_houses.retain(|(house, ..)| *house != old_entity);
+            // Drop the pops' links to the demolished building and stop whatever they were doing there
+            for (pop_entity, mut pop) in pop_query.iter_mut() {
+                let pop = &mut *pop;
+                if pop.home == Some(old_entity) {
+                    move_out(pop_entity, pop, &mut house_query);
+                    if pop.state == PopState::Sleeping {
+                        pop.state = PopState::Idle;
+                        pop.destination = None;
+                    }
+                }
+                if pop.job.as_ref().is_some_and(|job| job.workplace == old_entity) {
+                    leave_job(pop_entity, pop, &mut workplace_query);
+                    if pop.state == PopState::Working {
+                        pop.state = PopState::Idle;
+                        pop.destination = None;
+                    }
+                }
+                if pop.restaurant == Some(old_entity) {
+                    pop.restaurant = None;
+                    pop.state = PopState::Idle;
+                    pop.destination = None;
+                }
+            }
+        }
+        commands.entity(old_entity).despawn_recursive();
+
+        let new_entity = match tool {
+            BuildTool::House => spawn_house(&mut commands, tile, tilemap_entity),
+            BuildTool::Workplace => spawn_workplace(&mut commands, tile, tilemap_entity),
+            BuildTool::Farm => spawn_farm(&mut commands, tile, tilemap_entity),
+            BuildTool::Restaurant => spawn_restaurant(&mut commands, tile, tilemap_entity),
+            BuildTool::Road => spawn_road(&mut commands, tile, tilemap_entity),
+            BuildTool::Bulldoze => spawn_empty_tile(&mut commands, tile, tilemap_entity),
+        };
+        tile_storage.set(&tile, new_entity);
+        nav_grid.set_cost(tile, tool.path_cost());
+        changed_events.send(TileChanged(tile));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::schedule::ScheduleLabel;
+    use bevy::prelude::*;
+    use bevy_ecs_tilemap::prelude::*;
+
+    use crate::build::{apply_builds, BuildTile, BuildTool, TileChanged};
+    use crate::rng::SimSeed;
+    use crate::tilemap::{spawn_tilemap, House, Pop, Road, SimulationPlugin, SimulationSet};
+
+    #[test]
+    fn test_bulldozing_a_house_evicts_its_residents() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(SimSeed(3))
+            .add_plugins(SimulationPlugin { schedule: Update.intern() })
+            .add_event::<BuildTile>()
+            .add_event::<TileChanged>()
+            .add_systems(Startup, spawn_tilemap)
+            .add_systems(Update, apply_builds.after(SimulationSet));
+        for _ in 0..10 {
+            app.update();
+        }
+
+        let world = app.world_mut();
+        let (house, tile, residents) = world
+            .query::<(Entity, &House)>()
+            .iter(world)
+            .find(|(_, house)| !house.residents.is_empty())
+            .map(|(entity, house)| (entity, house.position, house.residents.clone()))
+            .expect("Someone should have moved in");
+
+        // Houses can only be replaced after bulldozing them
+        world.send_event(BuildTile { tile, tool: BuildTool::Road });
+        app.update();
+        assert!(app.world().get::<House>(house).is_some());
+
+        app.world_mut().send_event(BuildTile { tile, tool: BuildTool::Bulldoze });
+        app.update();
+        let world = app.world_mut();
+        assert!(world.get::<House>(house).is_none());
+        for resident in residents {
+            assert_ne!(world.get::<Pop>(resident).unwrap().home, Some(house));
+        }
+
+        world.send_event(BuildTile { tile, tool: BuildTool::Road });
+        app.update();
+        let world = app.world_mut();
+        let tile_entity = world.query::<&TileStorage>().single(world).get(&tile).unwrap();
+        assert!(world.get::<Road>(tile_entity).is_some());
+    }
+}
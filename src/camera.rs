@@ -0,0 +1,276 @@
+use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+use crate::actions::{set_movement_actions, Actions};
+use crate::build::SelectedTool;
+use crate::coords::{MapGeometry, WorldPos};
+use crate::inspector::Inspected;
+use crate::pause::GameSettings;
+use crate::tilemap::Pop;
+use crate::ui::PointerOverUi;
+use crate::{GameState, PlayState};
+
+pub struct CameraPlugin;
+
+/// This plugin spawns the camera and moves it over the city:
+/// WASD or the arrow keys, dragging with the right or middle mouse button (or one finger while no
+/// build tool is selected), pushing the cursor against the window edge, and following a pop.
+/// The scroll wheel and pinching zoom in and out around the cursor or fingers.
+/// Camera movement is only active during the State `PlayState::Running`
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraFollow>()
+            .add_systems(Startup, spawn_camera)
+            .add_systems(
+                Update,
+                (
+                    pan_camera.after(set_movement_actions),
+                    drag_camera,
+                    zoom_camera,
+                    scroll_at_edges,
+                    toggle_follow,
+                    follow_pop,
+                    keep_camera_over_map,
+                )
+                    .chain()
+                    .run_if(in_state(PlayState::Running)),
+            )
+            .add_systems(OnExit(GameState::Playing), stop_following);
+    }
+}
+
+/// Logical pixels per second the camera moves at when zoomed all the way in to scale 1
+const PAN_SPEED: f32 = 300.0;
+/// Smallest projection scale, the closest the camera gets
+const MIN_ZOOM: f32 = 0.25;
+/// Largest projection scale, the farthest the camera gets
+const MAX_ZOOM: f32 = 8.0;
+/// Zoom factor of one line on the scroll wheel
+const ZOOM_STEP: f32 = 1.1;
+/// Pixel scrolling, as on touchpads, zooms by one step per this many pixels
+const PIXELS_PER_ZOOM_STEP: f32 = 50.0;
+/// Distance in logical pixels from the window edge at which the camera starts to scroll
+const EDGE_SCROLL_MARGIN: f32 = 4.0;
+
+/// The pop the camera keeps centered, until the player moves the camera
+#[derive(Resource, Default)]
+pub(crate) struct CameraFollow(pub Option<Entity>);
+
+fn spawn_camera(mut commands: Commands) {
+    commands.spawn((Camera2d, Msaa::Off));
+}
+
+fn pan_camera(
+    time: Res<Time>,
+    actions: Res<Actions>,
+    mut follow: ResMut<CameraFollow>,
+    mut camera_query: Query<(&mut Transform, &OrthographicProjection), With<Camera2d>>,
+) {
+    let Some(movement) = actions.camera_movement else {
+        return;
+    };
+    follow.0 = None;
+    for (mut camera_transform, projection) in &mut camera_query {
+        let distance = PAN_SPEED * projection.scale * time.delta_secs();
+        camera_transform.translation += (movement * distance).extend(0.);
+    }
+}
+
+/// Moves the map along with the dragging cursor or finger
+fn drag_camera(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    touches: Res<Touches>,
+    selected: Res<SelectedTool>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    pointer_over_ui: PointerOverUi,
+    mut last_cursor: Local<Option<Vec2>>,
+    mut follow: ResMut<CameraFollow>,
+    mut camera_query: Query<(&mut Transform, &OrthographicProjection), With<Camera2d>>,
+) {
+    let cursor = window_query.get_single().ok().and_then(Window::cursor_position);
+    let dragging = [MouseButton::Right, MouseButton::Middle]
+        .into_iter()
+        .any(|button| mouse_input.pressed(button));
+    let mut delta = match (dragging, cursor, *last_cursor) {
+        (true, Some(cursor), Some(last)) => cursor - last,
+        _ => Vec2::ZERO,
+    };
+    *last_cursor = cursor.filter(|_| dragging);
+
+    // With a tool selected a single finger paints instead
+    let mut fingers = touches.iter();
+    let mut pointer = cursor.filter(|_| dragging);
+    if let (Some(touch), None, None) = (fingers.next(), fingers.next(), selected.0) {
+        delta += touch.delta();
+        pointer = Some(touch.position());
+    }
+    // Drags on the UI are meant for it
+    if delta == Vec2::ZERO || pointer.is_some_and(|pointer| pointer_over_ui.at(pointer)) {
+        return;
+    }
+
+    follow.0 = None;
+    for (mut camera_transform, projection) in &mut camera_query {
+        // Screen y points down, world y up
+        camera_transform.translation.x -= delta.x * projection.scale;
+        camera_transform.translation.y += delta.y * projection.scale;
+    }
+}
+
+/// Zooms by `factor` while keeping the world point under `anchor`, a viewport position, in place
+fn zoom_around(
+    camera: &Camera,
+    camera_global: &GlobalTransform,
+    camera_transform: &mut Transform,
+    projection: &mut OrthographicProjection,
+    anchor: Vec2,
+    factor: f32,
+) {
+    let scale = (projection.scale * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+    if let Ok(fixed) = camera.viewport_to_world_2d(camera_global, anchor) {
+        let offset = camera_transform.translation.truncate() - fixed;
+        let center = fixed + offset * scale / projection.scale;
+        camera_transform.translation = center.extend(camera_transform.translation.z);
+    }
+    projection.scale = scale;
+}
+
+fn zoom_camera(
+    mut wheel_events: EventReader<MouseWheel>,
+    touches: Res<Touches>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut camera_query: Query<
+        (&Camera, &GlobalTransform, &mut Transform, &mut OrthographicProjection),
+        With<Camera2d>,
+    >,
+) {
+    let Ok((camera, camera_global, mut camera_transform, mut projection)) =
+        camera_query.get_single_mut()
+    else {
+        return;
+    };
+
+    let steps: f32 = wheel_events
+        .read()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / PIXELS_PER_ZOOM_STEP,
+        })
+        .sum();
+    let window = window_query.get_single().ok();
+    if steps != 0.0 {
+        // Without a cursor, zoom around the center of the screen
+        let anchor = window
+            .and_then(|window| window.cursor_position().or(Some(window.size() / 2.0)))
+            .unwrap_or_default();
+        let factor = ZOOM_STEP.powf(-steps);
+        zoom_around(camera, camera_global, &mut camera_transform, &mut projection, anchor, factor);
+    }
+
+    let mut fingers = touches.iter();
+    if let (Some(first), Some(second), None) = (fingers.next(), fingers.next(), fingers.next()) {
+        let distance = first.position().distance(second.position());
+        let previous = first.previous_position().distance(second.previous_position());
+        if distance > 0.0 && previous > 0.0 && distance != previous {
+            let anchor = (first.position() + second.position()) / 2.0;
+            let factor = previous / distance;
+            zoom_around(camera, camera_global, &mut camera_transform, &mut projection, anchor, factor);
+        }
+    }
+}
+
+fn scroll_at_edges(
+    time: Res<Time>,
+    settings: Res<GameSettings>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut follow: ResMut<CameraFollow>,
+    mut camera_query: Query<(&mut Transform, &OrthographicProjection), With<Camera2d>>,
+) {
+    if !settings.edge_scrolling {
+        return;
+    }
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+    let Some(cursor) = window.cursor_position().filter(|_| window.focused) else {
+        return;
+    };
+    let size = window.size();
+    let mut direction = Vec2::ZERO;
+    if cursor.x <= EDGE_SCROLL_MARGIN {
+        direction.x -= 1.0;
+    }
+    if cursor.x >= size.x - EDGE_SCROLL_MARGIN {
+        direction.x += 1.0;
+    }
+    // Screen y points down, world y up
+    if cursor.y <= EDGE_SCROLL_MARGIN {
+        direction.y += 1.0;
+    }
+    if cursor.y >= size.y - EDGE_SCROLL_MARGIN {
+        direction.y -= 1.0;
+    }
+    if direction == Vec2::ZERO {
+        return;
+    }
+
+    follow.0 = None;
+    for (mut camera_transform, projection) in &mut camera_query {
+        let distance = PAN_SPEED * projection.scale * time.delta_secs();
+        camera_transform.translation += (direction.normalize() * distance).extend(0.);
+    }
+}
+
+/// `F` follows the inspected pop, or stops following
+fn toggle_follow(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    inspected: Res<Inspected>,
+    pop_query: Query<(), With<Pop>>,
+    mut follow: ResMut<CameraFollow>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::KeyF) {
+        return;
+    }
+    follow.0 = match (follow.0, inspected.0) {
+        (None, Some(entity)) if pop_query.contains(entity) => Some(entity),
+        _ => None,
+    };
+}
+
+fn follow_pop(
+    mut follow: ResMut<CameraFollow>,
+    pop_query: Query<&Pop>,
+    mut camera_query: Query<&mut Transform, With<Camera2d>>,
+) {
+    let Some(entity) = follow.0 else {
+        return;
+    };
+    let Ok(pop) = pop_query.get(entity) else {
+        // The pop died or left the city
+        follow.0 = None;
+        return;
+    };
+    for mut camera_transform in &mut camera_query {
+        camera_transform.translation.x = pop.position.x;
+        camera_transform.translation.y = pop.position.y;
+    }
+}
+
+/// Keeps the center of the screen over the map
+fn keep_camera_over_map(
+    geometry: Option<Res<MapGeometry>>,
+    mut camera_query: Query<&mut Transform, With<Camera2d>>,
+) {
+    let Some(geometry) = geometry else {
+        return;
+    };
+    for mut camera_transform in &mut camera_query {
+        let center = geometry.clamp(WorldPos(camera_transform.translation.truncate()));
+        camera_transform.translation = center.extend(camera_transform.translation.z);
+    }
+}
+
+fn stop_following(mut follow: ResMut<CameraFollow>) {
+    follow.0 = None;
+}
@@ -0,0 +1,424 @@
+use bevy::prelude::*;
+use bevy::ui::RelativeCursorPosition;
+use bevy::window::PrimaryWindow;
+
+use crate::history::{StatsHistory, StatsSample};
+use crate::tilemap::GameClock;
+use crate::GameState;
+
+pub struct ChartsPlugin;
+
+/// This plugin shows line charts of the city aggregates recorded in [`StatsHistory`] over the last
+/// few days. The panel is toggled with `C` or its button, hovering a chart shows the exact values.
+impl Plugin for ChartsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChartSettings>()
+            .add_systems(OnEnter(GameState::Playing), spawn_charts_panel)
+            .add_systems(
+                Update,
+                (click_chart_buttons, draw_charts, show_chart_tooltip)
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnExit(GameState::Playing), despawn_charts_panel);
+    }
+}
+
+/// Days the charts can look back, picked with the buttons above them
+const DAY_RANGES: [u64; 4] = [1, 7, 30, 60];
+/// Every chart is drawn as this many columns, each covering an equal share of the samples
+const CHART_COLUMNS: usize = 120;
+const COLUMN_WIDTH: f32 = 2.0;
+const CHART_HEIGHT: f32 = 40.0;
+const LINE_WIDTH: f32 = 2.0;
+
+const BUTTON_NORMAL: Color = Color::linear_rgb(0.15, 0.15, 0.15);
+const BUTTON_HOVERED: Color = Color::linear_rgb(0.25, 0.25, 0.25);
+const BUTTON_SELECTED: Color = Color::linear_rgb(0.2, 0.4, 0.6);
+
+#[derive(Resource)]
+struct ChartSettings {
+    visible: bool,
+    days: u64,
+}
+
+impl Default for ChartSettings {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            days: DAY_RANGES[1],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ChartMetric {
+    Population,
+    EmploymentRate,
+    HomelessRate,
+    AverageMoney,
+    AverageHunger,
+    AverageEnergy,
+}
+
+impl ChartMetric {
+    const ALL: [ChartMetric; 6] = [
+        ChartMetric::Population,
+        ChartMetric::EmploymentRate,
+        ChartMetric::HomelessRate,
+        ChartMetric::AverageMoney,
+        ChartMetric::AverageHunger,
+        ChartMetric::AverageEnergy,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            ChartMetric::Population => "Population",
+            ChartMetric::EmploymentRate => "Employed",
+            ChartMetric::HomelessRate => "Homeless",
+            ChartMetric::AverageMoney => "Average money",
+            ChartMetric::AverageHunger => "Average hunger",
+            ChartMetric::AverageEnergy => "Average energy",
+        }
+    }
+
+    fn value(self, sample: &StatsSample) -> f32 {
+        let city = &sample.city;
+        match self {
+            ChartMetric::Population => city.population as f32,
+            ChartMetric::EmploymentRate => city.employment_rate(),
+            ChartMetric::HomelessRate => city.homeless_rate(),
+            ChartMetric::AverageMoney => city.average_money,
+            ChartMetric::AverageHunger => city.average_hunger,
+            ChartMetric::AverageEnergy => city.average_energy,
+        }
+    }
+
+    fn format(self, value: f32) -> String {
+        match self {
+            ChartMetric::Population => format!("{value:.0}"),
+            ChartMetric::EmploymentRate | ChartMetric::HomelessRate => format!("{value:.1}%"),
+            ChartMetric::AverageMoney => format!("${value:.2}"),
+            ChartMetric::AverageHunger | ChartMetric::AverageEnergy => format!("{value:.0}/10000"),
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            ChartMetric::Population => Color::linear_rgb(0.9, 0.9, 0.9),
+            ChartMetric::EmploymentRate => Color::linear_rgb(0.3, 0.8, 0.3),
+            ChartMetric::HomelessRate => Color::linear_rgb(0.9, 0.3, 0.3),
+            ChartMetric::AverageMoney => Color::linear_rgb(0.9, 0.8, 0.2),
+            ChartMetric::AverageHunger => Color::linear_rgb(0.9, 0.5, 0.1),
+            ChartMetric::AverageEnergy => Color::linear_rgb(0.3, 0.6, 0.9),
+        }
+    }
+}
+
+#[derive(Component)]
+struct ChartsPanel;
+
+#[derive(Component)]
+struct ChartsToggle;
+
+#[derive(Component)]
+struct RangeButton(u64);
+
+/// The plot area of a chart
+#[derive(Component)]
+struct Chart(ChartMetric);
+
+#[derive(Component)]
+struct ChartColumn {
+    metric: ChartMetric,
+    index: usize,
+}
+
+#[derive(Component)]
+struct ChartLabel(ChartMetric);
+
+#[derive(Component)]
+struct ChartTooltip;
+
+fn spawn_charts_panel(mut commands: Commands, mut settings: ResMut<ChartSettings>) {
+    let text_font = TextFont {
+        font_size: 14.0,
+        ..default()
+    };
+    let button_node = Node {
+        padding: UiRect::axes(Val::Px(6.0), Val::Px(2.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+
+    commands
+        .spawn((
+            Button,
+            Node {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(60.0),
+                left: Val::Px(10.0),
+                ..button_node.clone()
+            },
+            BackgroundColor(BUTTON_NORMAL),
+            ChartsPanel,
+            ChartsToggle,
+        ))
+        .with_child((Text::new("C Charts"), text_font.clone()));
+
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(90.0),
+                left: Val::Px(10.0),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(4.0),
+                padding: UiRect::all(Val::Px(8.0)),
+                display: Display::None,
+                ..default()
+            },
+            BackgroundColor(Color::linear_rgba(0.0, 0.0, 0.0, 0.6)),
+            ChartsPanel,
+        ))
+        .with_children(|panel| {
+            panel
+                .spawn(Node {
+                    flex_direction: FlexDirection::Row,
+                    column_gap: Val::Px(4.0),
+                    ..default()
+                })
+                .with_children(|row| {
+                    for days in DAY_RANGES {
+                        row.spawn((
+                            Button,
+                            button_node.clone(),
+                            BackgroundColor(BUTTON_NORMAL),
+                            RangeButton(days),
+                        ))
+                        .with_child((Text::new(format!("{days}d")), text_font.clone()));
+                    }
+                });
+
+            for metric in ChartMetric::ALL {
+                panel.spawn((
+                    Text::new(metric.label()),
+                    text_font.clone(),
+                    TextColor(metric.color()),
+                    ChartLabel(metric),
+                ));
+                panel
+                    .spawn((
+                        Node {
+                            width: Val::Px(CHART_COLUMNS as f32 * COLUMN_WIDTH),
+                            height: Val::Px(CHART_HEIGHT),
+                            ..default()
+                        },
+                        BackgroundColor(Color::linear_rgba(1.0, 1.0, 1.0, 0.05)),
+                        RelativeCursorPosition::default(),
+                        Chart(metric),
+                    ))
+                    .with_children(|chart| {
+                        for index in 0..CHART_COLUMNS {
+                            chart.spawn((
+                                Node {
+                                    position_type: PositionType::Absolute,
+                                    left: Val::Px(index as f32 * COLUMN_WIDTH),
+                                    width: Val::Px(COLUMN_WIDTH),
+                                    display: Display::None,
+                                    ..default()
+                                },
+                                BackgroundColor(metric.color()),
+                                ChartColumn { metric, index },
+                            ));
+                        }
+                    });
+            }
+        });
+
+    commands.spawn((
+        Text::default(),
+        text_font,
+        Node {
+            position_type: PositionType::Absolute,
+            padding: UiRect::all(Val::Px(4.0)),
+            display: Display::None,
+            ..default()
+        },
+        BackgroundColor(Color::linear_rgba(0.0, 0.0, 0.0, 0.8)),
+        GlobalZIndex(1),
+        ChartsPanel,
+        ChartTooltip,
+    ));
+
+    // The fresh columns need to be drawn once
+    settings.set_changed();
+}
+
+fn despawn_charts_panel(mut commands: Commands, panel_query: Query<Entity, With<ChartsPanel>>) {
+    for entity in panel_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn click_chart_buttons(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut toggle_query: Query<(Ref<Interaction>, &mut BackgroundColor), With<ChartsToggle>>,
+    mut range_query: Query<
+        (&Interaction, &RangeButton, &mut BackgroundColor),
+        Without<ChartsToggle>,
+    >,
+    mut settings: ResMut<ChartSettings>,
+) {
+    let toggled = toggle_query
+        .iter()
+        .any(|(interaction, _)| interaction.is_changed() && *interaction == Interaction::Pressed);
+    if toggled || keyboard_input.just_pressed(KeyCode::KeyC) {
+        settings.visible = !settings.visible;
+    }
+    for (interaction, mut color) in toggle_query.iter_mut() {
+        *color = if settings.visible {
+            BUTTON_SELECTED.into()
+        } else if *interaction == Interaction::Hovered {
+            BUTTON_HOVERED.into()
+        } else {
+            BUTTON_NORMAL.into()
+        };
+    }
+    for (interaction, button, mut color) in range_query.iter_mut() {
+        if *interaction == Interaction::Pressed && settings.days != button.0 {
+            settings.days = button.0;
+        }
+        *color = if settings.days == button.0 {
+            BUTTON_SELECTED.into()
+        } else if *interaction == Interaction::Hovered {
+            BUTTON_HOVERED.into()
+        } else {
+            BUTTON_NORMAL.into()
+        };
+    }
+}
+
+/// The samples taken during the last `days` days, oldest first
+fn recent_samples(history: &StatsHistory, days: u64, ticks_per_day: u64) -> Vec<&StatsSample> {
+    let Some(latest) = history.latest() else {
+        return Vec::new();
+    };
+    let since = latest.tick.saturating_sub(days * ticks_per_day);
+    history.samples().filter(|sample| sample.tick > since).collect()
+}
+
+/// Index into `len` samples of the sample drawn in `column`
+fn sample_in_column(column: usize, len: usize) -> usize {
+    column * len / CHART_COLUMNS
+}
+
+fn draw_charts(
+    settings: Res<ChartSettings>,
+    history: Res<StatsHistory>,
+    game_clock: Res<GameClock>,
+    mut panel_query: Query<&mut Node, (With<ChartsPanel>, Without<ChartsToggle>, Without<ChartTooltip>)>,
+    mut column_query: Query<(&ChartColumn, &mut Node), Without<ChartsPanel>>,
+    mut label_query: Query<(&ChartLabel, &mut Text)>,
+) {
+    if !settings.is_changed() && !history.is_changed() {
+        return;
+    }
+    for mut node in panel_query.iter_mut() {
+        node.display = if settings.visible { Display::Flex } else { Display::None };
+    }
+    if !settings.visible {
+        return;
+    }
+
+    let samples = recent_samples(&history, settings.days, game_clock.ticks_per_day());
+    let series: Vec<Vec<f32>> = ChartMetric::ALL
+        .iter()
+        .map(|metric| samples.iter().map(|sample| metric.value(sample)).collect())
+        .collect();
+    let ranges: Vec<(f32, f32)> = series
+        .iter()
+        .map(|values| {
+            let min = values.iter().copied().fold(f32::INFINITY, f32::min);
+            let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+            (min, max)
+        })
+        .collect();
+
+    for (label, mut text) in label_query.iter_mut() {
+        let metric = label.0 as usize;
+        let (min, max) = ranges[metric];
+        **text = match series[metric].last() {
+            Some(current) => format!(
+                "{}: {} (low {}, high {})",
+                label.0.label(),
+                label.0.format(*current),
+                label.0.format(min),
+                label.0.format(max)
+            ),
+            None => format!("{}: no samples yet", label.0.label()),
+        };
+    }
+
+    for (column, mut node) in column_query.iter_mut() {
+        let values = &series[column.metric as usize];
+        if values.is_empty() {
+            node.display = Display::None;
+            continue;
+        }
+        let (min, max) = ranges[column.metric as usize];
+        let height_of = |value: f32| {
+            let share = if max > min { (value - min) / (max - min) } else { 0.5 };
+            (1.0 - share) * (CHART_HEIGHT - LINE_WIDTH)
+        };
+        // Each column reaches back to the previous one so the columns join up into a line
+        let y = height_of(values[sample_in_column(column.index, values.len())]);
+        let previous_y = height_of(values[sample_in_column(column.index.saturating_sub(1), values.len())]);
+        node.display = Display::Flex;
+        node.top = Val::Px(y.min(previous_y));
+        node.height = Val::Px((y - previous_y).abs() + LINE_WIDTH);
+    }
+}
+
+fn show_chart_tooltip(
+    settings: Res<ChartSettings>,
+    history: Res<StatsHistory>,
+    game_clock: Res<GameClock>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    chart_query: Query<(&Chart, &RelativeCursorPosition)>,
+    mut tooltip_query: Query<(&mut Node, &mut Text), With<ChartTooltip>>,
+) {
+    let Ok((mut node, mut text)) = tooltip_query.get_single_mut() else {
+        return;
+    };
+    if !settings.visible {
+        node.display = Display::None;
+        return;
+    }
+    let cursor = window_query.get_single().ok().and_then(Window::cursor_position);
+    let hovered = chart_query.iter().find_map(|(chart, cursor_position)| {
+        let position = cursor_position.normalized.filter(|_| cursor_position.mouse_over())?;
+        Some((chart.0, position.x))
+    });
+    let samples = recent_samples(&history, settings.days, game_clock.ticks_per_day());
+    let (Some(cursor), Some((metric, x)), false) = (cursor, hovered, samples.is_empty()) else {
+        node.display = Display::None;
+        return;
+    };
+
+    let column = ((x * CHART_COLUMNS as f32) as usize).min(CHART_COLUMNS - 1);
+    let sample = samples[sample_in_column(column, samples.len())];
+    let minute_of_day = (sample.tick % game_clock.ticks_per_day()) * 60 / game_clock.ticks_per_hour;
+    **text = format!(
+        "Day {}, {:02}:{:02}\n{}: {}",
+        sample.day,
+        minute_of_day / 60,
+        minute_of_day % 60,
+        metric.label(),
+        metric.format(metric.value(sample))
+    );
+    node.display = Display::Flex;
+    node.left = Val::Px(cursor.x + 12.0);
+    node.top = Val::Px(cursor.y + 12.0);
+}
@@ -1,5 +1,115 @@
 use bevy_ecs_tilemap::map::TilemapSize;
 
 pub const TILE_SIZE: f32 = 16.0;
-pub const MAP_SIZE: TilemapSize = TilemapSize { x: 32, y: 32 };
-pub const POP_MOVE_SPEED: f32 = 1.6;
\ No newline at end of file
+/// Size of a new city unless the player picks another one
+pub const DEFAULT_MAP_SIZE: TilemapSize = TilemapSize { x: 32, y: 32 };
+/// Longest side a new city may have, in tiles
+pub const MAX_MAP_SIDE: u32 = 512;
+/// Residents a house has room for
+pub const HOUSE_CAPACITY: u32 = 4;
+/// Most adults a house of a newly generated city starts out with
+pub const MAX_STARTING_RESIDENTS: usize = 3;
+/// Side length in tiles of the square chunks the tilemap is rendered and redrawn in
+pub const CHUNK_SIZE: u32 = 32;
+/// Fastest the clock runs, as a multiple of one simulated minute per real second
+pub const MAX_SPEED: u32 = 512;
+/// Hour the time controls skip ahead to when advancing to the next morning
+pub const MORNING_HOUR: u64 = 6;
+pub const POP_MOVE_SPEED: f32 = 1.6;
+/// What a seated diner pays a restaurant for every tick of eating while its pantry is full
+pub const MEAL_COST_PER_TICK: i32 = 1;
+/// How much a restaurant with no food left charges on top of `MEAL_COST_PER_TICK`, as a multiple of it
+pub const MAX_MEAL_MARKUP: f32 = 2.0;
+/// Food a restaurant uses up for every tick a diner eats
+pub const FOOD_PER_MEAL_TICK: f32 = 1.0;
+/// Goods a restaurant uses up for every tick a diner eats
+pub const GOODS_PER_MEAL_TICK: f32 = 0.5;
+/// Units of each product a restaurant keeps in stock
+pub const RESTAURANT_STOCK_TARGET: f32 = 1000.0;
+/// Share of `RESTAURANT_STOCK_TARGET` below which a restaurant orders more
+pub const RESTAURANT_REORDER_LEVEL: f32 = 0.5;
+/// Ticks a shipment takes per unit of path cost between supplier and restaurant
+pub const TICKS_PER_PATH_COST: u64 = 2;
+/// Food one farm worker grows per hour on site
+pub const FOOD_PER_WORKER_HOUR: f32 = 20.0;
+/// Goods one employee of any other workplace makes per hour on site
+pub const GOODS_PER_WORKER_HOUR: f32 = 10.0;
+/// What restaurants pay farms per unit of food
+pub const FOOD_PRICE: f32 = 0.5;
+/// What restaurants pay workplaces per unit of goods
+pub const GOODS_PRICE: f32 = 1.0;
+/// Cash a new workplace starts out with to pay salaries until its goods sell
+pub const FIRM_STARTING_CAPITAL: i32 = 2000;
+/// Daily wage a new workplace offers
+pub const BASE_WAGE: f32 = 75.0;
+pub const MIN_WAGE: f32 = 20.0;
+pub const MAX_WAGE: f32 = 400.0;
+/// How far a workplace moves its wage in one daily review, as a share of the current wage
+pub const WAGE_ADJUSTMENT_RATE: f32 = 0.1;
+/// Workplaces judge unemployment among the pops within this many tiles
+pub const LOCAL_LABOR_RADIUS: f32 = 8.0;
+/// How much a workplace's sales against its payroll count in its wage review, next to vacancies
+/// and local unemployment
+pub const SALES_WAGE_WEIGHT: f32 = 0.5;
+/// Daily wage a pop is willing to give up per unit of path cost between home and work
+pub const COMMUTE_COST: f32 = 0.5;
+/// How much better, in daily wage minus commute, an offer has to be for an employed pop to quit
+pub const JOB_SWITCH_MARGIN: f32 = 10.0;
+/// Daily rent a new house charges each resident
+pub const BASE_RENT: f32 = 20.0;
+pub const MIN_RENT: f32 = 5.0;
+pub const MAX_RENT: f32 = 200.0;
+/// How far a landlord moves the rent in one day, as a share of the current rent
+pub const RENT_ADJUSTMENT_RATE: f32 = 0.1;
+/// Share of a house's capacity at which its landlord keeps the rent where it is
+pub const TARGET_OCCUPANCY: f32 = 0.75;
+/// Days a resident can fall behind on rent before being evicted
+pub const EVICTION_DAYS: u32 = 3;
+/// Days an employee can miss work before being fired
+pub const FIRING_ABSENCE_DAYS: u64 = 2;
+/// Money in the city's treasury when it is founded
+pub const CITY_STARTING_FUNDS: i64 = 10_000;
+/// Default share of their earnings pops pay in income tax
+pub const INCOME_TAX_RATE: f32 = 0.1;
+/// Default share of the rent the city takes in property tax
+pub const PROPERTY_TAX_RATE: f32 = 0.2;
+/// Default share of their sales workplaces pay in sales tax
+pub const SALES_TAX_RATE: f32 = 0.05;
+/// No tax rate can be set higher than this
+pub const MAX_TAX_RATE: f32 = 0.5;
+/// Default number of days between tax collections
+pub const TAX_INTERVAL_DAYS: u32 = 7;
+pub const MAX_TAX_INTERVAL_DAYS: u32 = 30;
+/// Default daily welfare for every homeless adult
+pub const WELFARE_PER_HOMELESS: i32 = 10;
+pub const MAX_WELFARE: i32 = 100;
+/// Daily cost of maintaining one road tile
+pub const ROAD_UPKEEP: i64 = 1;
+/// Daily interest on the city's debt
+pub const DEBT_INTEREST_RATE: f64 = 0.01;
+/// Ticks between two samples of the statistics history, one simulated hour by default
+pub const STATS_SAMPLE_INTERVAL_TICKS: u64 = 60;
+/// Samples the statistics history keeps before dropping the oldest, 60 days of hourly samples
+pub const STATS_HISTORY_LENGTH: usize = 24 * 60;
+/// Ticks between two snapshots of every building in the history, one simulated day by default
+pub const BUILDING_SNAPSHOT_INTERVAL_TICKS: u64 = 24 * 60;
+/// Building snapshots the history keeps, a row per building each, so a week rather than 60 days
+pub const BUILDING_HISTORY_LENGTH: usize = 7;
+/// Pops age one year per simulated day, so generations turn over within a playable session
+pub const ADULT_AGE: u32 = 18;
+/// Pops stop working at this age and no longer look for jobs
+pub const RETIREMENT_AGE: u32 = 60;
+/// From this age on pops may die of old age, with a chance that grows every year
+pub const OLD_AGE: u32 = 65;
+/// No pop lives past this age
+pub const MAX_AGE: u32 = 100;
+/// Oldest age at which a pop has children
+pub const MAX_PARENT_AGE: u32 = 45;
+/// Daily chance that a household with room in its house has a child
+pub const BIRTH_CHANCE: f64 = 0.05;
+/// At most this many pops move to the city per day
+pub const MAX_IMMIGRANTS_PER_DAY: u32 = 5;
+/// Money a pop brings along when moving to the city
+pub const IMMIGRANT_MONEY: i32 = 100;
+/// Daily chance that an adult without job and home leaves a city that has no room for them
+pub const EMIGRATION_CHANCE: f64 = 0.2;
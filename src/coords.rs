@@ -0,0 +1,164 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::constants::{CHUNK_SIZE, TILE_SIZE};
+
+/// A point in world space, the space the tilemap's `Transform` and all sprites live in.
+/// Discrete tiles are addressed with [`TilePos`]; use [`MapGeometry`] to convert between the two.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deref, DerefMut)]
+pub struct WorldPos(pub Vec2);
+
+impl WorldPos {
+    pub fn distance(self, other: WorldPos) -> f32 {
+        self.0.distance(other.0)
+    }
+
+    pub fn distance_squared(self, other: WorldPos) -> f32 {
+        self.0.distance_squared(other.0)
+    }
+}
+
+/// Where the city's tiles are in world space.
+/// The tilemap is centered on the origin with [`get_tilemap_center_transform`],
+/// so tile centers are offset by half the map size.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct MapGeometry {
+    pub size: TilemapSize,
+    /// World position of the center of tile (0, 0)
+    origin: Vec2,
+}
+
+impl MapGeometry {
+    pub fn new(size: TilemapSize) -> Self {
+        Self {
+            size,
+            origin: Self::transform_for(size).translation.truncate(),
+        }
+    }
+
+    pub fn grid_size() -> TilemapGridSize {
+        TilemapGridSize { x: TILE_SIZE, y: TILE_SIZE }
+    }
+
+    /// Transform that centers a tilemap of `size` on the world origin
+    pub fn transform_for(size: TilemapSize) -> Transform {
+        get_tilemap_center_transform(&size, &Self::grid_size(), &TilemapType::default(), 0.0)
+    }
+
+    /// World position of the center of `tile`
+    pub fn tile_center(&self, tile: TilePos) -> WorldPos {
+        WorldPos(self.origin + Vec2::new(tile.x as f32, tile.y as f32) * TILE_SIZE)
+    }
+
+    /// The tile covering `position`, if it is on the map
+    pub fn tile_at(&self, position: WorldPos) -> Option<TilePos> {
+        let local = (position.0 - self.origin) / TILE_SIZE + Vec2::splat(0.5);
+        if local.x < 0.0 || local.y < 0.0 {
+            return None;
+        }
+        let tile = TilePos {
+            x: local.x as u32,
+            y: local.y as u32,
+        };
+        tile.within_map_bounds(&self.size).then_some(tile)
+    }
+
+    /// Moves `position` onto the map, between the centers of the outermost tiles
+    pub fn clamp(&self, position: WorldPos) -> WorldPos {
+        let max = self.tile_center(TilePos {
+            x: self.size.x - 1,
+            y: self.size.y - 1,
+        });
+        WorldPos(position.0.clamp(self.origin, max.0))
+    }
+
+    /// The chunk containing `tile`; chunk (0, 0) starts at tile (0, 0)
+    pub fn chunk_of(tile: TilePos) -> UVec2 {
+        UVec2::new(tile.x, tile.y) / CHUNK_SIZE
+    }
+
+    /// The tiles of `chunk` that are on the map
+    pub fn chunk_tiles(&self, chunk: UVec2) -> impl Iterator<Item = TilePos> {
+        let min = chunk * CHUNK_SIZE;
+        let max = (min + UVec2::splat(CHUNK_SIZE)).min(UVec2::new(self.size.x, self.size.y));
+        (min.x..max.x).flat_map(move |x| (min.y..max.y).map(move |y| TilePos { x, y }))
+    }
+
+    /// Every chunk with a tile overlapping `area`, a rectangle in world space
+    pub fn chunks_in(&self, area: Rect) -> impl Iterator<Item = UVec2> {
+        // Clamped points are tile centers at worst, which are always on the map
+        let corner_chunk = |corner: Vec2| {
+            let tile = self.tile_at(self.clamp(WorldPos(corner))).unwrap_or(TilePos { x: 0, y: 0 });
+            Self::chunk_of(tile)
+        };
+        let min = corner_chunk(area.min);
+        let max = corner_chunk(area.max);
+        (min.x..=max.x).flat_map(move |x| (min.y..=max.y).map(move |y| UVec2::new(x, y)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+    use bevy_ecs_tilemap::prelude::*;
+
+    use crate::constants::{CHUNK_SIZE, TILE_SIZE};
+    use crate::coords::{MapGeometry, WorldPos};
+
+    #[test]
+    fn test_tile_centers_and_positions_round_trip() {
+        let geometry = MapGeometry::new(TilemapSize { x: 7, y: 4 });
+        // Just short of the edges of a tile is still on it
+        let inside = TILE_SIZE / 2.0 - 0.01;
+        for x in 0..7 {
+            for y in 0..4 {
+                let tile = TilePos { x, y };
+                let center = geometry.tile_center(tile);
+                assert_eq!(geometry.tile_at(center), Some(tile));
+                assert_eq!(geometry.tile_at(WorldPos(center.0 + Vec2::new(inside, -inside))), Some(tile));
+                assert_eq!(geometry.tile_at(WorldPos(center.0 - Vec2::new(inside, -inside))), Some(tile));
+                assert_eq!(geometry.clamp(center), center);
+            }
+        }
+    }
+
+    #[test]
+    fn test_positions_off_the_map_have_no_tile_and_clamp_to_the_edge() {
+        let geometry = MapGeometry::new(TilemapSize { x: 7, y: 4 });
+        let first = geometry.tile_center(TilePos { x: 0, y: 0 });
+        let last = geometry.tile_center(TilePos { x: 6, y: 3 });
+        let past_edge = Vec2::splat(TILE_SIZE / 2.0 + 0.01);
+
+        assert_eq!(geometry.tile_at(WorldPos(first.0 - past_edge)), None);
+        assert_eq!(geometry.tile_at(WorldPos(last.0 + past_edge)), None);
+        assert_eq!(geometry.tile_at(WorldPos(Vec2::new(first.x, last.y + TILE_SIZE * 10.0))), None);
+
+        // Far off the top left corner ends up on the center of the corner tile
+        let clamped = geometry.clamp(WorldPos(Vec2::new(-1000.0, 1000.0)));
+        assert_eq!(clamped, WorldPos(Vec2::new(first.x, last.y)));
+        assert_eq!(geometry.tile_at(clamped), Some(TilePos { x: 0, y: 3 }));
+        // Positions on the map stay where they are
+        let between = WorldPos((first.0 + last.0) / 2.0);
+        assert_eq!(geometry.clamp(between), between);
+    }
+
+    #[test]
+    fn test_chunks_cover_the_map_and_the_visible_area() {
+        let geometry = MapGeometry::new(TilemapSize { x: CHUNK_SIZE * 2 + 5, y: CHUNK_SIZE });
+
+        // The last column of chunks is cut off by the edge of the map
+        assert_eq!(geometry.chunk_tiles(UVec2::new(0, 0)).count(), (CHUNK_SIZE * CHUNK_SIZE) as usize);
+        assert_eq!(geometry.chunk_tiles(UVec2::new(2, 0)).count(), (5 * CHUNK_SIZE) as usize);
+        let tile = TilePos { x: CHUNK_SIZE * 2 + 4, y: 7 };
+        assert_eq!(MapGeometry::chunk_of(tile), UVec2::new(2, 0));
+
+        // A small area around one tile only touches its chunk
+        let center = geometry.tile_center(TilePos { x: CHUNK_SIZE + 3, y: 3 }).0;
+        let area = Rect::from_center_size(center, Vec2::splat(TILE_SIZE));
+        assert_eq!(geometry.chunks_in(area).collect::<Vec<_>>(), vec![UVec2::new(1, 0)]);
+
+        // Areas reaching past the map are cut to the chunks on it
+        let area = Rect::from_center_size(Vec2::ZERO, Vec2::splat(TILE_SIZE * 1000.0));
+        assert_eq!(geometry.chunks_in(area).count(), 3);
+    }
+}
@@ -0,0 +1,278 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::bookkeeping::leave_job;
+use crate::constants::{
+    FOOD_PER_MEAL_TICK, FOOD_PER_WORKER_HOUR, FOOD_PRICE, GOODS_PER_MEAL_TICK,
+    GOODS_PER_WORKER_HOUR, GOODS_PRICE, RESTAURANT_REORDER_LEVEL, RESTAURANT_STOCK_TARGET,
+    TICKS_PER_PATH_COST,
+};
+use crate::coords::MapGeometry;
+use crate::pathfinding::NavGrid;
+use crate::tilemap::{spawn_empty_tile, GameClock, Pop, Restaurant, Tilemap, Workplace};
+
+/// What a workplace produces. Restaurants need both to serve meals.
+#[derive(Default, Eq, PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum Product {
+    /// Made by regular workplaces
+    #[default]
+    Goods,
+    /// Grown on farms
+    Food,
+}
+
+impl Product {
+    pub const ALL: [Product; 2] = [Product::Goods, Product::Food];
+
+    /// What restaurants pay per unit
+    pub fn price(self) -> f32 {
+        match self {
+            Product::Goods => GOODS_PRICE,
+            Product::Food => FOOD_PRICE,
+        }
+    }
+
+    fn output_per_worker_hour(self) -> f32 {
+        match self {
+            Product::Goods => GOODS_PER_WORKER_HOUR,
+            Product::Food => FOOD_PER_WORKER_HOUR,
+        }
+    }
+
+    /// Tile texture of the workplaces making this
+    pub fn texture_index(self) -> u32 {
+        match self {
+            Product::Goods => 3,
+            Product::Food => 1,
+        }
+    }
+
+    /// Units a restaurant uses up for every tick a diner eats
+    pub fn used_per_meal_tick(self) -> f32 {
+        match self {
+            Product::Goods => GOODS_PER_MEAL_TICK,
+            Product::Food => FOOD_PER_MEAL_TICK,
+        }
+    }
+}
+
+/// Products bought by a restaurant that are still on their way
+pub(crate) struct Shipment {
+    pub(crate) restaurant: Entity,
+    pub(crate) product: Product,
+    pub(crate) amount: f32,
+    pub(crate) arrival_tick: u64,
+}
+
+#[derive(Resource, Default)]
+pub struct Shipments(pub(crate) Vec<Shipment>);
+
+impl Shipments {
+    fn in_transit(&self, restaurant: Entity, product: Product) -> f32 {
+        self.0
+            .iter()
+            .filter(|shipment| shipment.restaurant == restaurant && shipment.product == product)
+            .map(|shipment| shipment.amount)
+            .sum()
+    }
+}
+
+/// Every employee at work adds their share of an hour's output to the workplace's inventory
+pub(crate) fn produce_goods(
+    game_clock: Res<GameClock>,
+    geometry: Res<MapGeometry>,
+    pop_query: Query<&Pop>,
+    mut workplace_query: Query<&mut Workplace>,
+) {
+    for pop in pop_query.iter() {
+        let Some(job) = &pop.job else {
+            continue;
+        };
+        if !pop.is_at_work(&geometry) {
+            continue;
+        }
+        if let Ok(mut workplace) = workplace_query.get_mut(job.workplace) {
+            workplace.inventory +=
+                workplace.product.output_per_worker_hour() / game_clock.ticks_per_hour as f32;
+        }
+    }
+}
+
+/// Restaurants whose stock of a product, counting what is already on the way, fell below
+/// `RESTAURANT_REORDER_LEVEL` order up to `RESTAURANT_STOCK_TARGET` from the closest workplaces
+/// making it, for as long as their takings last. Orders are paid up front and arrive after a delay
+/// that grows with the path cost between supplier and restaurant.
+pub(crate) fn order_supplies(
+    game_clock: Res<GameClock>,
+    mut nav_grid: ResMut<NavGrid>,
+    mut shipments: ResMut<Shipments>,
+    mut restaurant_query: Query<(Entity, &mut Restaurant)>,
+    mut workplace_query: Query<(Entity, &mut Workplace)>,
+) {
+    let mut restaurants: Vec<_> = restaurant_query.iter_mut().collect();
+    restaurants.sort_unstable_by_key(|(entity, _)| *entity);
+    for (restaurant_entity, mut restaurant) in restaurants {
+        for product in Product::ALL {
+            let expected = restaurant.stock(product) + shipments.in_transit(restaurant_entity, product);
+            if expected >= RESTAURANT_STOCK_TARGET * RESTAURANT_REORDER_LEVEL {
+                continue;
+            }
+
+            // Both ends are buildings, so the way from the restaurant costs what the way to it does
+            let distances = nav_grid.distances_from(restaurant.position);
+            let mut suppliers: Vec<(u32, Entity)> = workplace_query
+                .iter()
+                .filter(|(_, workplace)| workplace.product == product && workplace.inventory >= 1.0)
+                .filter_map(|(entity, workplace)| Some((distances.cost_to(workplace.position)?, entity)))
+                .collect();
+            suppliers.sort_unstable();
+
+            let mut wanted = (RESTAURANT_STOCK_TARGET - expected).floor();
+            for (cost, supplier) in suppliers {
+                let affordable = (restaurant.revenue.max(0) as f32 / product.price()).floor();
+                let Ok((_, mut workplace)) = workplace_query.get_mut(supplier) else {
+                    continue;
+                };
+                let amount = wanted.min(affordable).min(workplace.inventory.floor());
+                if amount < 1.0 {
+                    break;
+                }
+                let price = (amount * product.price()).round() as i32;
+                workplace.inventory -= amount;
+                workplace.treasury = workplace.treasury.saturating_add(price);
+                workplace.sales = workplace.sales.saturating_add(price);
+                workplace.daily_sales = workplace.daily_sales.saturating_add(price);
+                restaurant.revenue -= price;
+                wanted -= amount;
+                shipments.0.push(Shipment {
+                    restaurant: restaurant_entity,
+                    product,
+                    amount,
+                    arrival_tick: game_clock.current_tick + cost as u64 * TICKS_PER_PATH_COST,
+                });
+            }
+        }
+    }
+}
+
+/// Adds shipments that arrived to their restaurant's stock. Shipments to demolished restaurants are lost.
+pub(crate) fn deliver_shipments(
+    game_clock: Res<GameClock>,
+    mut shipments: ResMut<Shipments>,
+    mut restaurant_query: Query<&mut Restaurant>,
+) {
+    shipments.0.retain(|shipment| {
+        if shipment.arrival_tick > game_clock.current_tick {
+            return true;
+        }
+        if let Ok(mut restaurant) = restaurant_query.get_mut(shipment.restaurant) {
+            *restaurant.stock_mut(shipment.product) += shipment.amount;
+        }
+        false
+    });
+}
+
+/// Once a day workplaces that ran out of money close down: their employees are laid off
+/// and the tile is cleared
+pub(crate) fn close_bankrupt_firms(
+    mut commands: Commands,
+    game_clock: Res<GameClock>,
+    mut tilemap_query: Query<(Entity, &mut TileStorage), With<Tilemap>>,
+    mut pop_query: Query<(Entity, &mut Pop)>,
+    mut workplace_query: Query<&mut Workplace>,
+) {
+    if !game_clock.is_day_start() {
+        return;
+    }
+    let Ok((tilemap_entity, mut tile_storage)) = tilemap_query.get_single_mut() else {
+        return;
+    };
+
+    let mut bankrupt: Vec<(Entity, TilePos, Vec<Entity>)> = workplace_query
+        .iter()
+        .filter(|workplace| workplace.treasury <= 0)
+        .filter_map(|workplace| {
+            let entity = tile_storage.get(&workplace.position)?;
+            Some((entity, workplace.position, workplace.employees.clone()))
+        })
+        .collect();
+    bankrupt.sort_unstable_by_key(|(entity, ..)| *entity);
+
+    for (workplace, position, employees) in bankrupt {
+        for employee in employees {
+            if let Ok((_, mut pop)) = pop_query.get_mut(employee) {
+                leave_job(employee, &mut pop, &mut workplace_query);
+            }
+        }
+        // The nav grid and markets notice the missing building on their own
+        commands.entity(workplace).despawn_recursive();
+        let empty = spawn_empty_tile(&mut commands, position, tilemap_entity);
+        tile_storage.set(&position, empty);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::*;
+    use bevy_ecs_tilemap::prelude::*;
+
+    use crate::constants::{BASE_WAGE, RESTAURANT_STOCK_TARGET, TICKS_PER_PATH_COST};
+    use crate::firms::{deliver_shipments, order_supplies, Product, Shipments};
+    use crate::pathfinding::{NavGrid, GROUND_COST};
+    use crate::tilemap::{GameClock, Restaurant, Workplace};
+
+    #[test]
+    fn test_restaurants_order_food_from_the_nearest_farms() {
+        let mut world = World::new();
+        world.insert_resource(GameClock::default());
+        world.insert_resource(NavGrid::new(TilemapSize { x: 10, y: 1 }));
+        world.init_resource::<Shipments>();
+        let supplier = |x, product, inventory| Workplace {
+            capacity: 10,
+            employees: Vec::new(),
+            position: TilePos { x, y: 0 },
+            wage: BASE_WAGE,
+            treasury: 0,
+            inventory,
+            sales: 0,
+            daily_sales: 0,
+            product,
+        };
+        let near = world.spawn(supplier(1, Product::Food, 30.0)).id();
+        let far = world.spawn(supplier(9, Product::Food, 1000.0)).id();
+        let factory = world.spawn(supplier(2, Product::Goods, 1000.0)).id();
+        let restaurant = world.spawn(Restaurant {
+            capacity: 20,
+            position: TilePos { x: 0, y: 0 },
+            diners: Vec::new(),
+            queue: VecDeque::new(),
+            revenue: 100,
+            food: 0.0,
+            goods: RESTAURANT_STOCK_TARGET,
+        }).id();
+
+        world.run_system_once(order_supplies).unwrap();
+
+        // The near farm sells out, the rest of what the restaurant can afford comes from further away
+        let near_price = (30.0 * Product::Food.price()) as i32;
+        assert_eq!(world.get::<Workplace>(near).unwrap().inventory, 0.0);
+        assert_eq!(world.get::<Workplace>(near).unwrap().treasury, near_price);
+        let far_amount = ((100 - near_price) as f32 / Product::Food.price()).floor();
+        assert_eq!(world.get::<Workplace>(far).unwrap().inventory, 1000.0 - far_amount);
+        assert_eq!(world.get::<Workplace>(factory).unwrap().inventory, 1000.0);
+
+        // Nothing arrives before the far farm's delivery had time to cross the map
+        let far_arrival = 9 * GROUND_COST as u64 * TICKS_PER_PATH_COST;
+        world.resource_mut::<GameClock>().current_tick = far_arrival - 1;
+        world.run_system_once(deliver_shipments).unwrap();
+        assert_eq!(world.get::<Restaurant>(restaurant).unwrap().food, 30.0);
+        world.resource_mut::<GameClock>().current_tick = far_arrival;
+        world.run_system_once(deliver_shipments).unwrap();
+        assert_eq!(world.get::<Restaurant>(restaurant).unwrap().food, 30.0 + far_amount);
+        assert!(world.resource::<Shipments>().0.is_empty());
+    }
+}
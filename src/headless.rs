@@ -0,0 +1,95 @@
+use std::path::PathBuf;
+
+use bevy::ecs::schedule::ScheduleLabel;
+use bevy::prelude::*;
+
+use crate::history::StatsHistory;
+use crate::lifecycle::Demographics;
+use crate::rng::SimSeed;
+use crate::scenario::{start_scenario, ActiveScenario};
+use crate::stats::CityStats;
+use crate::tilemap::{
+    spawn_tilemap, GameClock, House, JobMarket, Pop, Restaurant, SimulationPlugin, SimulationSet,
+    Workplace,
+};
+use crate::treasury::CityTreasury;
+
+/// This plugin runs the city simulation without a window, renderer, audio or assets.
+/// Combined with [`MinimalPlugins`], every app update advances the simulation by one tick,
+/// so it runs as fast as the CPU allows. The aggregates shown in the HUD are printed at the
+/// end of every simulated day and the app exits after `days` days, or as soon as a scenario is decided.
+/// With `export` set, the recorded [`StatsHistory`] is written to that directory before exiting.
+pub struct HeadlessPlugin {
+    pub days: u64,
+    pub export: Option<PathBuf>,
+}
+
+#[derive(Resource)]
+struct HeadlessRun {
+    days: u64,
+    export: Option<PathBuf>,
+}
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(HeadlessRun {
+            days: self.days,
+            export: self.export.clone(),
+        })
+            .add_plugins(SimulationPlugin {
+                schedule: Update.intern(),
+            })
+            .add_systems(Startup, (spawn_tilemap, start_scenario).chain())
+            .add_systems(Update, report_day.after(SimulationSet));
+    }
+}
+
+fn report_day(
+    game_clock: Res<GameClock>,
+    seed: Res<SimSeed>,
+    run: Res<HeadlessRun>,
+    job_market: Res<JobMarket>,
+    demographics: Res<Demographics>,
+    treasury: Res<CityTreasury>,
+    history: Res<StatsHistory>,
+    scenario: Option<Res<ActiveScenario>>,
+    pop_query: Query<&Pop>,
+    house_query: Query<&House>,
+    workplace_query: Query<&Workplace>,
+    restaurant_query: Query<&Restaurant>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if game_clock.current_tick % game_clock.ticks_per_day() != 0 {
+        return;
+    }
+    let finished_day = game_clock.day() - 1;
+    let mut stats =
+        CityStats::collect(&pop_query, &house_query, &workplace_query, &restaurant_query);
+    stats.wage_trend = job_market.wage_trend();
+    println!(
+        "Day: {} (seed {})\n{}\n{}\n{}\n",
+        finished_day, seed.0, stats, *demographics, *treasury
+    );
+
+    let mut decided = false;
+    if let Some(scenario) = &scenario {
+        if let Some(outcome) = &scenario.outcome {
+            println!("Scenario {}: {outcome}\n", scenario.scenario.name);
+            decided = true;
+        }
+    }
+    if finished_day < run.days && !decided {
+        return;
+    }
+    if let Some(directory) = &run.export {
+        match history.export(directory) {
+            Ok(()) => println!("Exported statistics to {}", directory.display()),
+            Err(error) => {
+                eprintln!("Failed to export statistics to {}: {error}", directory.display());
+                exit.send(AppExit::error());
+                return;
+            }
+        }
+    }
+    exit.send(AppExit::Success);
+}
@@ -0,0 +1,399 @@
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::constants::{
+    BUILDING_HISTORY_LENGTH, BUILDING_SNAPSHOT_INTERVAL_TICKS, STATS_HISTORY_LENGTH,
+    STATS_SAMPLE_INTERVAL_TICKS,
+};
+use crate::firms::Product;
+use crate::stats::CityStats;
+use crate::tilemap::{GameClock, House, JobMarket, Pop, Restaurant, Tilemap, Workplace};
+use crate::treasury::CityTreasury;
+use crate::GameState;
+
+/// Where the game writes the history when exporting
+pub(crate) const EXPORT_DIRECTORY: &str = "exports";
+
+pub struct HistoryPlugin;
+
+/// This plugin exports the recorded statistics of the running city, see [`StatsHistory`].
+/// The recording itself is part of the simulation so headless runs have it too.
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            export_on_hotkey.run_if(in_state(GameState::Playing)),
+        );
+    }
+}
+
+/// The city aggregates, sampled every `interval_ticks` ticks, and the state of every building,
+/// snapshotted every `building_interval_ticks` ticks. Only the last `capacity` samples and
+/// `building_capacity` snapshots are kept, older ones are dropped as new ones come in.
+/// Snapshots hold a row per building, so they are taken less often and kept for a shorter time.
+/// Cleared whenever a new city is generated or loaded.
+#[derive(Resource, Serialize)]
+pub struct StatsHistory {
+    interval_ticks: u64,
+    #[serde(skip)]
+    capacity: usize,
+    samples: VecDeque<StatsSample>,
+    building_interval_ticks: u64,
+    #[serde(skip)]
+    building_capacity: usize,
+    building_snapshots: VecDeque<BuildingSnapshot>,
+}
+
+#[derive(Serialize)]
+pub struct StatsSample {
+    pub(crate) day: u64,
+    pub(crate) tick: u64,
+    pub(crate) city_funds: i64,
+    pub(crate) city: CityStats,
+}
+
+#[derive(Serialize)]
+pub struct BuildingSnapshot {
+    pub(crate) day: u64,
+    pub(crate) tick: u64,
+    pub(crate) buildings: Vec<BuildingSample>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug)]
+pub enum BuildingKind {
+    House,
+    Workplace,
+    Farm,
+    Restaurant,
+}
+
+/// One building at the time of a sample. What the numbers mean depends on the kind of building.
+#[derive(Serialize)]
+pub struct BuildingSample {
+    pub(crate) kind: BuildingKind,
+    pub(crate) x: u32,
+    pub(crate) y: u32,
+    /// Residents, employees or diners
+    pub(crate) occupants: usize,
+    pub(crate) capacity: u32,
+    /// Rent, daily wage or meal price per tick
+    pub(crate) price: f32,
+    /// Rent collected since the last tax day, firm treasury or restaurant takings
+    pub(crate) money: f32,
+    /// Unsold products of a workplace or food left in a restaurant
+    pub(crate) stock: f32,
+}
+
+impl Default for StatsHistory {
+    fn default() -> Self {
+        StatsHistory::new(STATS_SAMPLE_INTERVAL_TICKS, STATS_HISTORY_LENGTH)
+            .with_building_snapshots(BUILDING_SNAPSHOT_INTERVAL_TICKS, BUILDING_HISTORY_LENGTH)
+    }
+}
+
+impl StatsHistory {
+    pub fn new(interval_ticks: u64, capacity: usize) -> Self {
+        assert!(interval_ticks > 0, "the sample interval must be at least one tick");
+        StatsHistory {
+            interval_ticks,
+            capacity,
+            samples: VecDeque::with_capacity(capacity),
+            building_interval_ticks: BUILDING_SNAPSHOT_INTERVAL_TICKS,
+            building_capacity: BUILDING_HISTORY_LENGTH,
+            building_snapshots: VecDeque::new(),
+        }
+    }
+
+    /// Snapshots every building each `interval_ticks` ticks and keeps the last `capacity` of them
+    pub fn with_building_snapshots(mut self, interval_ticks: u64, capacity: usize) -> Self {
+        assert!(interval_ticks > 0, "the snapshot interval must be at least one tick");
+        self.building_interval_ticks = interval_ticks;
+        self.building_capacity = capacity;
+        self.building_snapshots = VecDeque::with_capacity(capacity);
+        self
+    }
+
+    /// Ticks between two samples
+    pub fn interval_ticks(&self) -> u64 {
+        self.interval_ticks
+    }
+
+    /// Number of samples kept
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.building_snapshots.clear();
+    }
+
+    pub fn latest(&self) -> Option<&StatsSample> {
+        self.samples.back()
+    }
+
+    pub fn samples(&self) -> impl DoubleEndedIterator<Item = &StatsSample> {
+        self.samples.iter()
+    }
+
+    pub fn building_snapshots(&self) -> impl DoubleEndedIterator<Item = &BuildingSnapshot> {
+        self.building_snapshots.iter()
+    }
+
+    fn push(&mut self, sample: StatsSample) {
+        push_bounded(&mut self.samples, self.capacity, sample);
+    }
+
+    fn push_buildings(&mut self, snapshot: BuildingSnapshot) {
+        push_bounded(&mut self.building_snapshots, self.building_capacity, snapshot);
+    }
+
+    /// The city aggregates, one row per sample
+    pub fn city_csv(&self) -> String {
+        let mut csv = String::from(
+            "day,tick,city_funds,population,children,average_age,employed,employment_rate,\
+            homeless,homeless_rate,average_money,median_wage,wage_trend,average_hunger,\
+            average_energy,houses,house_capacity,workplaces,job_capacity,restaurants,\
+            restaurants_out_of_stock,food_in_stock\n",
+        );
+        for sample in &self.samples {
+            let city = &sample.city;
+            // Writing to a String can't fail
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                sample.day,
+                sample.tick,
+                sample.city_funds,
+                city.population,
+                city.children,
+                city.average_age,
+                city.employed,
+                city.employment_rate(),
+                city.homeless,
+                city.homeless_rate(),
+                city.average_money,
+                city.median_wage,
+                city.wage_trend,
+                city.average_hunger,
+                city.average_energy,
+                city.houses,
+                city.house_capacity,
+                city.workplaces,
+                city.job_capacity,
+                city.restaurants,
+                city.restaurants_out_of_stock,
+                city.food_in_stock,
+            );
+        }
+        csv
+    }
+
+    /// Every building in every snapshot, one row each
+    pub fn buildings_csv(&self) -> String {
+        let mut csv = String::from("day,tick,kind,x,y,occupants,capacity,price,money,stock\n");
+        for snapshot in &self.building_snapshots {
+            for building in &snapshot.buildings {
+                let _ = writeln!(
+                    csv,
+                    "{},{},{:?},{},{},{},{},{},{},{}",
+                    snapshot.day,
+                    snapshot.tick,
+                    building.kind,
+                    building.x,
+                    building.y,
+                    building.occupants,
+                    building.capacity,
+                    building.price,
+                    building.money,
+                    building.stock,
+                );
+            }
+        }
+        csv
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Writes `city.csv`, `buildings.csv` and `history.json` to `directory`, creating it if needed
+    pub fn export(&self, directory: &Path) -> std::io::Result<()> {
+        std::fs::create_dir_all(directory)?;
+        std::fs::write(directory.join("city.csv"), self.city_csv())?;
+        std::fs::write(directory.join("buildings.csv"), self.buildings_csv())?;
+        std::fs::write(directory.join("history.json"), self.to_json()?)
+    }
+}
+
+/// Appends `item`, dropping the oldest one once `capacity` items are kept
+fn push_bounded<T>(queue: &mut VecDeque<T>, capacity: usize, item: T) {
+    if capacity == 0 {
+        return;
+    }
+    if queue.len() == capacity {
+        queue.pop_front();
+    }
+    queue.push_back(item);
+}
+
+/// Takes a sample every `StatsHistory::interval_ticks` ticks and a building snapshot every
+/// `building_interval_ticks` ticks, starting over when a new city appears
+pub(crate) fn record_stats(
+    game_clock: Res<GameClock>,
+    job_market: Res<JobMarket>,
+    treasury: Res<CityTreasury>,
+    mut history: ResMut<StatsHistory>,
+    new_city_query: Query<(), Added<Tilemap>>,
+    pop_query: Query<&Pop>,
+    house_query: Query<&House>,
+    workplace_query: Query<&Workplace>,
+    restaurant_query: Query<&Restaurant>,
+) {
+    if !new_city_query.is_empty() {
+        history.clear();
+    }
+    let tick = game_clock.current_tick;
+    // The clock doesn't move while paused
+    let sample_due = tick % history.interval_ticks == 0
+        && history.latest().is_none_or(|sample| sample.tick != tick);
+    let snapshot_due = tick % history.building_interval_ticks == 0
+        && history.building_snapshots.back().is_none_or(|snapshot| snapshot.tick != tick);
+
+    if sample_due {
+        let mut city = CityStats::collect(&pop_query, &house_query, &workplace_query, &restaurant_query);
+        city.wage_trend = job_market.wage_trend();
+        history.push(StatsSample {
+            day: game_clock.day(),
+            tick,
+            city_funds: treasury.balance,
+            city,
+        });
+    }
+    if !snapshot_due {
+        return;
+    }
+
+    let mut buildings = Vec::new();
+    for house in house_query.iter() {
+        buildings.push(BuildingSample {
+            kind: BuildingKind::House,
+            x: house.position.x,
+            y: house.position.y,
+            occupants: house.residents.len(),
+            capacity: house.capacity,
+            price: house.rent,
+            money: house.rent_collected,
+            stock: 0.0,
+        });
+    }
+    for workplace in workplace_query.iter() {
+        buildings.push(BuildingSample {
+            kind: match workplace.product {
+                Product::Goods => BuildingKind::Workplace,
+                Product::Food => BuildingKind::Farm,
+            },
+            x: workplace.position.x,
+            y: workplace.position.y,
+            occupants: workplace.employees.len(),
+            capacity: workplace.capacity,
+            price: workplace.wage,
+            money: workplace.treasury as f32,
+            stock: workplace.inventory,
+        });
+    }
+    for restaurant in restaurant_query.iter() {
+        buildings.push(BuildingSample {
+            kind: BuildingKind::Restaurant,
+            x: restaurant.position.x,
+            y: restaurant.position.y,
+            occupants: restaurant.diners.len(),
+            capacity: restaurant.capacity,
+            price: restaurant.meal_price() as f32,
+            money: restaurant.revenue as f32,
+            stock: restaurant.food,
+        });
+    }
+    // Query order depends on spawn history, rows are easier to compare by position
+    buildings.sort_unstable_by_key(|building| (building.x, building.y));
+
+    history.push_buildings(BuildingSnapshot {
+        day: game_clock.day(),
+        tick,
+        buildings,
+    });
+}
+
+fn export_on_hotkey(keyboard_input: Res<ButtonInput<KeyCode>>, history: Res<StatsHistory>) {
+    if !keyboard_input.just_pressed(KeyCode::F6) {
+        return;
+    }
+    match history.export(Path::new(EXPORT_DIRECTORY)) {
+        Ok(()) => info!("Exported {} samples to {EXPORT_DIRECTORY}/", history.samples.len()),
+        Err(error) => warn!("Failed to export statistics to {EXPORT_DIRECTORY}/: {error}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::history::{BuildingKind, BuildingSample, BuildingSnapshot, StatsHistory, StatsSample};
+    use crate::stats::CityStats;
+
+    fn sample(tick: u64) -> StatsSample {
+        StatsSample {
+            day: 1,
+            tick,
+            city_funds: 100,
+            city: CityStats { population: 4, employed: 1, ..Default::default() },
+        }
+    }
+
+    fn snapshot(tick: u64) -> BuildingSnapshot {
+        BuildingSnapshot {
+            day: 1,
+            tick,
+            buildings: vec![BuildingSample {
+                kind: BuildingKind::House,
+                x: 1,
+                y: 2,
+                occupants: 3,
+                capacity: 4,
+                price: 20.0,
+                money: 0.0,
+                stock: 0.0,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_history_keeps_the_latest_samples_and_exports_them() {
+        let mut history = StatsHistory::new(60, 2).with_building_snapshots(120, 1);
+        for tick in [60, 120, 180] {
+            history.push(sample(tick));
+        }
+        history.push_buildings(snapshot(120));
+        history.push_buildings(snapshot(240));
+
+        let ticks: Vec<u64> = history.samples().map(|sample| sample.tick).collect();
+        assert_eq!(ticks, vec![120, 180]);
+
+        let city_csv = history.city_csv();
+        let mut rows = city_csv.lines();
+        let columns = rows.next().unwrap().split(',').count();
+        assert!(rows.clone().all(|row| row.split(',').count() == columns));
+        assert_eq!(rows.next().unwrap().split(',').take(7).collect::<Vec<_>>(), [
+            "1", "120", "100", "4", "0", "0", "1"
+        ]);
+        // Only the latest building snapshot is kept
+        assert_eq!(history.buildings_csv().lines().skip(1).collect::<Vec<_>>(), ["1,240,House,1,2,3,4,20,0,0"]);
+
+        let json: serde_json::Value = serde_json::from_str(&history.to_json().unwrap()).unwrap();
+        assert_eq!(json["interval_ticks"], 60);
+        assert_eq!(json["samples"][1]["city"]["population"], 4);
+        assert_eq!(json["building_snapshots"][0]["tick"], 240);
+    }
+}
@@ -0,0 +1,414 @@
+use bevy::ecs::system::SystemParam;
+use bevy::picking::backend::prelude::*;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::build::SelectedTool;
+use crate::constants::TILE_SIZE;
+use crate::coords::{MapGeometry, WorldPos};
+use crate::firms::Product;
+use crate::tilemap::{Building, House, Pop, Restaurant, Tilemap, Workplace};
+use crate::{GameState, PlayState};
+
+pub struct InspectorPlugin;
+
+/// This plugin opens an inspector card for the pop or building the player clicks while no build
+/// tool is selected. Pops are picked through their sprites, tiles through [`pick_tiles`].
+/// The card links to the pops and buildings the inspected one is connected to, and the path
+/// of an inspected pop is drawn on the map.
+impl Plugin for InspectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Inspected>()
+            .add_observer(inspect_clicked)
+            .add_systems(
+                PreUpdate,
+                pick_tiles
+                    .in_set(PickSet::Backend)
+                    .run_if(in_state(PlayState::Running)),
+            )
+            .add_systems(OnEnter(GameState::Playing), spawn_inspector)
+            .add_systems(
+                Update,
+                (click_inspector_buttons, update_inspector, draw_inspected)
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnExit(GameState::Playing), despawn_inspector);
+    }
+}
+
+/// The pop or building shown in the inspector card
+#[derive(Resource, Default)]
+pub(crate) struct Inspected(pub Option<Entity>);
+
+#[derive(Component)]
+struct InspectorCard;
+
+#[derive(Component)]
+struct InspectorText;
+
+/// Holds one button per linked pop or building
+#[derive(Component)]
+struct InspectorLinks;
+
+#[derive(Component)]
+struct InspectorLink(Entity);
+
+#[derive(Component)]
+struct InspectorClose;
+
+const BUTTON_NORMAL: Color = Color::linear_rgb(0.15, 0.15, 0.15);
+const BUTTON_HOVERED: Color = Color::linear_rgb(0.25, 0.25, 0.25);
+const HIGHLIGHT: Color = Color::linear_rgb(1.0, 0.9, 0.2);
+
+/// Reports the tile under each pointer as hit, so tiles take part in picking like sprites do.
+/// Tiles lie beneath the pop sprites, which are hit first and block them.
+fn pick_tiles(
+    pointers: Query<(&PointerId, &PointerLocation)>,
+    camera_query: Query<(Entity, &Camera, &GlobalTransform, &OrthographicProjection)>,
+    window_query: Query<Entity, With<PrimaryWindow>>,
+    geometry: Option<Res<MapGeometry>>,
+    tilemap_query: Query<&TileStorage, With<Tilemap>>,
+    mut output: EventWriter<PointerHits>,
+) {
+    let (Some(geometry), Ok(tile_storage)) = (geometry, tilemap_query.get_single()) else {
+        return;
+    };
+    let primary_window = window_query.get_single().ok();
+
+    for (pointer, location) in pointers
+        .iter()
+        .filter_map(|(pointer, location)| Some((pointer, location.location()?)))
+    {
+        let Some((camera_entity, camera, camera_transform, projection)) =
+            camera_query.iter().find(|(_, camera, ..)| {
+                camera.is_active
+                    && camera.target.normalize(primary_window) == Some(location.target.clone())
+            })
+        else {
+            continue;
+        };
+        let viewport_min = camera.logical_viewport_rect().map(|rect| rect.min).unwrap_or_default();
+        let Ok(world_position) =
+            camera.viewport_to_world_2d(camera_transform, location.position - viewport_min)
+        else {
+            continue;
+        };
+        let Some(entity) = geometry
+            .tile_at(WorldPos(world_position))
+            .and_then(|tile| tile_storage.get(&tile))
+        else {
+            continue;
+        };
+
+        // Measured from the near plane like the sprite backend does, the tilemap sits at z = 0
+        let hit_position = world_position.extend(0.0);
+        let depth = -projection.near
+            - camera_transform.affine().inverse().transform_point3(hit_position).z;
+        let hit = HitData::new(camera_entity, depth, Some(hit_position), None);
+        output.send(PointerHits::new(*pointer, vec![(entity, hit)], camera.order as f32));
+    }
+}
+
+/// Clicking a pop or building inspects it, clicking any other tile closes the card.
+/// Clicks on the UI don't reach the map, the UI nodes block them.
+fn inspect_clicked(
+    trigger: Trigger<Pointer<Click>>,
+    selected_tool: Res<SelectedTool>,
+    inspectable_query: Query<(), Or<(With<Pop>, With<Building>)>>,
+    tile_query: Query<(), With<TilePos>>,
+    mut inspected: ResMut<Inspected>,
+) {
+    // With a tool in hand, clicks build instead
+    if trigger.event().button != PointerButton::Primary || selected_tool.0.is_some() {
+        return;
+    }
+    let entity = trigger.entity();
+    if inspectable_query.contains(entity) {
+        inspected.0 = Some(entity);
+    } else if tile_query.contains(entity) {
+        inspected.0 = None;
+    }
+}
+
+/// Everything the inspector can show
+#[derive(SystemParam)]
+struct Inspectables<'w, 's> {
+    pops: Query<'w, 's, &'static Pop>,
+    houses: Query<'w, 's, &'static House>,
+    workplaces: Query<'w, 's, &'static Workplace>,
+    restaurants: Query<'w, 's, &'static Restaurant>,
+}
+
+impl Inspectables<'_, '_> {
+    /// Short label of a pop or building, used for the links
+    fn name(&self, entity: Entity) -> String {
+        let at = |position: TilePos| format!("({}, {})", position.x, position.y);
+        if self.pops.contains(entity) {
+            format!("Pop {entity}")
+        } else if let Ok(house) = self.houses.get(entity) {
+            format!("House {}", at(house.position))
+        } else if let Ok(workplace) = self.workplaces.get(entity) {
+            match workplace.product {
+                Product::Goods => format!("Workplace {}", at(workplace.position)),
+                Product::Food => format!("Farm {}", at(workplace.position)),
+            }
+        } else if let Ok(restaurant) = self.restaurants.get(entity) {
+            format!("Restaurant {}", at(restaurant.position))
+        } else {
+            format!("Gone {entity}")
+        }
+    }
+
+    /// The card's text and the entities it links to, `None` once the entity is gone
+    fn describe(&self, entity: Entity) -> Option<(String, Vec<Entity>)> {
+        if let Ok(pop) = self.pops.get(entity) {
+            let mut links = Vec::new();
+            let home = match pop.home {
+                Some(home) => {
+                    links.push(home);
+                    self.name(home)
+                }
+                None => "homeless".to_string(),
+            };
+            let job = match &pop.job {
+                Some(job) => {
+                    links.push(job.workplace);
+                    format!("{} for ${:.2}/day", self.name(job.workplace), job.salary)
+                }
+                None => "unemployed".to_string(),
+            };
+            links.extend(pop.partner);
+            links.extend(pop.restaurant);
+            let text = format!(
+                "{}\nAge {}, {:?}\nState: {:?}\nMoney: ${}\nHunger: {}/10000\nEnergy: {}/10000\n\
+                Home: {}\nJob: {}",
+                self.name(entity),
+                pop.age,
+                pop.sex,
+                pop.state,
+                pop.money,
+                pop.hunger,
+                pop.energy,
+                home,
+                job
+            );
+            return Some((text, links));
+        }
+
+        if let Ok(house) = self.houses.get(entity) {
+            let text = format!(
+                "{}\nResidents: {}/{}\nRent: ${:.2}/day",
+                self.name(entity),
+                house.residents.len(),
+                house.capacity,
+                house.rent
+            );
+            return Some((text, house.residents.clone()));
+        }
+
+        if let Ok(workplace) = self.workplaces.get(entity) {
+            let text = format!(
+                "{}\nEmployees: {}/{}\nWage: ${:.2}/day\nTreasury: ${}\nIn stock: {:.0} {:?}",
+                self.name(entity),
+                workplace.employees.len(),
+                workplace.capacity,
+                workplace.wage,
+                workplace.treasury,
+                workplace.inventory,
+                workplace.product
+            );
+            return Some((text, workplace.employees.clone()));
+        }
+
+        if let Ok(restaurant) = self.restaurants.get(entity) {
+            let text = format!(
+                "{}\nDiners: {}/{} ({} waiting)\nMeal price: ${}/tick\nFood: {:.0}, goods: {:.0}\n\
+                Takings: ${}",
+                self.name(entity),
+                restaurant.diners.len(),
+                restaurant.capacity,
+                restaurant.queue.len(),
+                restaurant.meal_price(),
+                restaurant.food,
+                restaurant.goods,
+                restaurant.revenue
+            );
+            let links = restaurant.diners.iter().chain(&restaurant.queue).copied().collect();
+            return Some((text, links));
+        }
+
+        None
+    }
+}
+
+fn spawn_inspector(mut commands: Commands) {
+    let text_font = TextFont {
+        font_size: 14.0,
+        ..default()
+    };
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(60.0),
+                right: Val::Px(10.0),
+                width: Val::Px(260.0),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(6.0),
+                padding: UiRect::all(Val::Px(8.0)),
+                display: Display::None,
+                ..default()
+            },
+            BackgroundColor(Color::linear_rgba(0.0, 0.0, 0.0, 0.7)),
+            InspectorCard,
+        ))
+        .with_children(|card| {
+            card.spawn((
+                Button,
+                Node {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(4.0),
+                    right: Val::Px(4.0),
+                    width: Val::Px(20.0),
+                    height: Val::Px(20.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                BackgroundColor(BUTTON_NORMAL),
+                InspectorClose,
+            ))
+            .with_child((Text::new("x"), text_font.clone()));
+            card.spawn((Text::default(), text_font, InspectorText));
+            card.spawn((
+                Node {
+                    flex_direction: FlexDirection::Row,
+                    flex_wrap: FlexWrap::Wrap,
+                    column_gap: Val::Px(4.0),
+                    row_gap: Val::Px(4.0),
+                    ..default()
+                },
+                InspectorLinks,
+            ));
+        });
+}
+
+fn despawn_inspector(
+    mut commands: Commands,
+    card_query: Query<Entity, With<InspectorCard>>,
+    mut inspected: ResMut<Inspected>,
+) {
+    for entity in card_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    inspected.0 = None;
+}
+
+fn click_inspector_buttons(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    mut link_query: Query<
+        (&Interaction, Option<&InspectorLink>, &mut BackgroundColor),
+        (Changed<Interaction>, Or<(With<InspectorLink>, With<InspectorClose>)>),
+    >,
+    mut inspected: ResMut<Inspected>,
+) {
+    if mouse_input.just_pressed(MouseButton::Right) {
+        inspected.0 = None;
+    }
+    for (interaction, link, mut color) in link_query.iter_mut() {
+        if *interaction == Interaction::Pressed {
+            inspected.0 = link.map(|link| link.0);
+        }
+        *color = if *interaction == Interaction::None {
+            BUTTON_NORMAL.into()
+        } else {
+            BUTTON_HOVERED.into()
+        };
+    }
+}
+
+fn update_inspector(
+    mut commands: Commands,
+    mut inspected: ResMut<Inspected>,
+    inspectables: Inspectables,
+    mut card_query: Query<&mut Node, With<InspectorCard>>,
+    mut text_query: Query<&mut Text, With<InspectorText>>,
+    links_query: Query<Entity, With<InspectorLinks>>,
+    mut shown_links: Local<Vec<Entity>>,
+) {
+    let description = inspected.0.and_then(|entity| inspectables.describe(entity));
+    if description.is_none() && inspected.0.is_some() {
+        // Died, moved away or was bulldozed
+        inspected.0 = None;
+    }
+    for mut node in card_query.iter_mut() {
+        node.display = if description.is_some() { Display::Flex } else { Display::None };
+    }
+    let Some((description, links)) = description else {
+        shown_links.clear();
+        return;
+    };
+
+    for mut text in text_query.iter_mut() {
+        **text = description.clone();
+    }
+
+    // Only rebuild the buttons when the links changed, so they can be hovered and clicked
+    if *shown_links == links {
+        return;
+    }
+    for container in links_query.iter() {
+        commands.entity(container).despawn_descendants().with_children(|container| {
+            for link in &links {
+                container
+                    .spawn((
+                        Button,
+                        Node {
+                            padding: UiRect::axes(Val::Px(4.0), Val::Px(2.0)),
+                            ..default()
+                        },
+                        BackgroundColor(BUTTON_NORMAL),
+                        InspectorLink(*link),
+                    ))
+                    .with_child((
+                        Text::new(inspectables.name(*link)),
+                        TextFont {
+                            font_size: 12.0,
+                            ..default()
+                        },
+                    ));
+            }
+        });
+    }
+    *shown_links = links;
+}
+
+/// Outlines the inspected building, or circles the inspected pop and traces the rest of its path
+fn draw_inspected(
+    inspected: Res<Inspected>,
+    geometry: Option<Res<MapGeometry>>,
+    pop_query: Query<&Pop>,
+    building_query: Query<&TilePos, With<Building>>,
+    mut gizmos: Gizmos,
+) {
+    let (Some(entity), Some(geometry)) = (inspected.0, geometry) else {
+        return;
+    };
+    if let Ok(pop) = pop_query.get(entity) {
+        gizmos.circle_2d(Isometry2d::from_translation(pop.position.0), TILE_SIZE / 2.0, HIGHLIGHT);
+        // The next waypoint is the last one
+        let path = std::iter::once(pop.position)
+            .chain(pop.waypoints.iter().rev().copied())
+            .chain(pop.destination)
+            .map(|position| position.0);
+        gizmos.linestrip_2d(path, HIGHLIGHT);
+    } else if let Ok(tile) = building_query.get(entity) {
+        gizmos.rect_2d(
+            Isometry2d::from_translation(geometry.tile_center(*tile).0),
+            Vec2::splat(TILE_SIZE),
+            HIGHLIGHT,
+        );
+    }
+}
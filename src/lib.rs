@@ -2,18 +2,47 @@
 
 mod actions;
 mod audio;
+mod bookkeeping;
+mod build;
+mod camera;
+mod charts;
+mod coords;
+mod firms;
+mod headless;
+mod history;
+mod inspector;
+mod lifecycle;
 mod loading;
+mod mapfile;
+mod mapgen;
 mod menu;
-mod player;
+mod overlay;
+mod pathfinding;
+mod pause;
+mod rng;
+mod save;
+mod scenario;
+mod stats;
 mod tilemap;
+mod treasury;
 mod ui;
 mod constants;
 
 use crate::actions::ActionsPlugin;
 use crate::audio::InternalAudioPlugin;
+use crate::build::BuildPlugin;
+use crate::camera::CameraPlugin;
+use crate::charts::ChartsPlugin;
+use crate::history::HistoryPlugin;
+use crate::inspector::InspectorPlugin;
 use crate::loading::LoadingPlugin;
+use crate::mapfile::MapFilePlugin;
 use crate::menu::MenuPlugin;
-use crate::player::PlayerPlugin;
+use crate::overlay::OverlayPlugin;
+use crate::pause::PausePlugin;
+use crate::save::SaveLoadPlugin;
+use crate::scenario::ScenarioPlugin;
+use crate::treasury::PolicyPlugin;
 
 use bevy::app::App;
 #[cfg(debug_assertions)]
@@ -22,6 +51,14 @@ use bevy::prelude::*;
 use crate::tilemap::TilePlugin;
 use crate::ui::UiPlugin;
 
+pub use crate::headless::HeadlessPlugin;
+pub use crate::history::StatsHistory;
+pub use crate::rng::SimSeed;
+pub use crate::scenario::Scenario;
+pub use crate::mapfile::CityMap;
+pub use crate::mapgen::{GeneratorParams, Layout, NewCitySettings};
+pub use crate::constants::MAX_MAP_SIDE;
+
 // This example game uses States to separate logic
 // See https://bevy-cheatbook.github.io/programming/states.html
 // Or https://github.com/bevyengine/bevy/blob/main/examples/ecs/state.rs
@@ -36,19 +73,37 @@ enum GameState {
     Menu,
 }
 
+// Only exists while a city is being played. The simulation and the player's input on the map
+// stop while the pause menu is open.
+#[derive(SubStates, Default, Clone, Eq, PartialEq, Debug, Hash)]
+#[source(GameState = GameState::Playing)]
+enum PlayState {
+    #[default]
+    Running,
+    Paused,
+}
+
 pub struct GamePlugin;
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
-        app.init_state::<GameState>().add_plugins((
+        app.init_state::<GameState>().add_sub_state::<PlayState>().add_plugins((
             LoadingPlugin,
             MenuPlugin,
             ActionsPlugin,
             InternalAudioPlugin,
-            PlayerPlugin,
+            CameraPlugin,
             TilePlugin,
             UiPlugin,
-        ));
+            BuildPlugin,
+            SaveLoadPlugin,
+            PolicyPlugin,
+            HistoryPlugin,
+            ChartsPlugin,
+            InspectorPlugin,
+            OverlayPlugin,
+        ))
+        .add_plugins((MapFilePlugin, ScenarioPlugin, PausePlugin));
 
         #[cfg(debug_assertions)]
         {
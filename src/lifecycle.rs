@@ -0,0 +1,289 @@
+use std::fmt;
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::bookkeeping::{leave_job, move_out};
+use crate::constants::{
+    ADULT_AGE, BIRTH_CHANCE, EMIGRATION_CHANCE, IMMIGRANT_MONEY, MAX_AGE, MAX_IMMIGRANTS_PER_DAY,
+    MAX_PARENT_AGE, OLD_AGE,
+};
+use crate::coords::MapGeometry;
+use crate::rng::SimRng;
+use crate::tilemap::{GameClock, House, Pop, Sex, TileBasedEntity, Workplace};
+
+/// Running totals of how the population changed since the city was founded
+#[derive(Resource, Default, Clone, Debug, Serialize, Deserialize)]
+pub struct Demographics {
+    pub births: u32,
+    pub deaths: u32,
+    pub immigrants: u32,
+    pub emigrants: u32,
+}
+
+impl fmt::Display for Demographics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Births: {}, Deaths: {}\nImmigrants: {}, Emigrants: {}",
+            self.births, self.deaths, self.immigrants, self.emigrants
+        )
+    }
+}
+
+/// Despawns the pop and drops it from its home, its workplace and its partner
+pub(crate) fn remove_pop(
+    commands: &mut Commands,
+    entity: Entity,
+    pop_query: &mut Query<(Entity, &mut Pop)>,
+    house_query: &mut Query<&mut House>,
+    workplace_query: &mut Query<&mut Workplace>,
+) {
+    let Ok((_, mut pop)) = pop_query.get_mut(entity) else {
+        return;
+    };
+    move_out(entity, &mut pop, house_query);
+    leave_job(entity, &mut pop, workplace_query);
+    let partner = pop.partner.take();
+
+    if let Some((_, mut partner)) = partner.and_then(|partner| pop_query.get_mut(partner).ok()) {
+        partner.partner = None;
+    }
+    commands.entity(entity).despawn_recursive();
+}
+
+/// Chance to die of old age on a given day
+fn old_age_death_chance(age: u32) -> f64 {
+    (age.saturating_sub(OLD_AGE) as f64 / (MAX_AGE - OLD_AGE) as f64).min(1.0)
+}
+
+/// Pops die once they have been starving or exhausted for a whole day.
+/// Once a day everyone also gets a year older and the old may die of old age.
+pub(crate) fn check_deaths(
+    mut commands: Commands,
+    game_clock: Res<GameClock>,
+    mut rng: ResMut<SimRng>,
+    mut demographics: ResMut<Demographics>,
+    mut pop_query: Query<(Entity, &mut Pop)>,
+    mut house_query: Query<&mut House>,
+    mut workplace_query: Query<&mut Workplace>,
+) {
+    let new_day = game_clock.is_day_start();
+    let ticks_per_day = game_clock.ticks_per_day() as u32;
+
+    let mut pops: Vec<_> = pop_query.iter_mut().collect();
+    pops.sort_unstable_by_key(|(entity, _)| *entity);
+    let mut dead = Vec::new();
+    for (entity, mut pop) in pops {
+        pop.starving_ticks = if pop.hunger >= 10000 { pop.starving_ticks + 1 } else { 0 };
+        pop.exhausted_ticks = if pop.energy == 0 { pop.exhausted_ticks + 1 } else { 0 };
+        if new_day {
+            pop.age += 1;
+        }
+
+        let worn_out = pop.starving_ticks >= ticks_per_day || pop.exhausted_ticks >= ticks_per_day;
+        let old_age = new_day && pop.age >= OLD_AGE && rng.gen_bool(old_age_death_chance(pop.age));
+        if worn_out || old_age {
+            dead.push(entity);
+        }
+    }
+
+    demographics.deaths += dead.len() as u32;
+    for entity in dead {
+        remove_pop(&mut commands, entity, &mut pop_query, &mut house_query, &mut workplace_query);
+    }
+}
+
+/// Once a day single adults sharing a house pair up into households
+pub(crate) fn form_households(
+    game_clock: Res<GameClock>,
+    house_query: Query<&House>,
+    mut pop_query: Query<&mut Pop>,
+) {
+    if !game_clock.is_day_start() {
+        return;
+    }
+
+    for house in house_query.iter() {
+        let mut singles: Vec<Entity> = house
+            .residents
+            .iter()
+            .copied()
+            .filter(|resident| {
+                pop_query
+                    .get(*resident)
+                    .is_ok_and(|pop| pop.is_adult() && pop.partner.is_none())
+            })
+            .collect();
+        singles.sort_unstable();
+        for pair in singles.chunks_exact(2) {
+            for (pop, partner) in [(pair[0], pair[1]), (pair[1], pair[0])] {
+                if let Ok(mut pop) = pop_query.get_mut(pop) {
+                    pop.partner = Some(partner);
+                }
+            }
+        }
+    }
+}
+
+/// Once a day households living together may have a child, if their house has room for it.
+/// Every household needs a female partner of parenting age for that.
+pub(crate) fn have_children(
+    mut commands: Commands,
+    game_clock: Res<GameClock>,
+    geometry: Res<MapGeometry>,
+    mut rng: ResMut<SimRng>,
+    mut demographics: ResMut<Demographics>,
+    pop_query: Query<(Entity, &Pop)>,
+    mut house_query: Query<&mut House>,
+) {
+    if !game_clock.is_day_start() {
+        return;
+    }
+
+    let mut mothers: Vec<(Entity, Entity)> = pop_query
+        .iter()
+        .filter(|(_, pop)| pop.sex == Sex::Female && pop.is_adult() && pop.age <= MAX_PARENT_AGE)
+        .filter_map(|(entity, pop)| {
+            let home = pop.home?;
+            let (_, partner) = pop_query.get(pop.partner?).ok()?;
+            (partner.home == Some(home)).then_some((entity, home))
+        })
+        .collect();
+    mothers.sort_unstable();
+
+    for (_, home) in mothers {
+        let Ok(mut house) = house_query.get_mut(home) else {
+            continue;
+        };
+        if house.residents.len() >= house.capacity as usize || !rng.gen_bool(BIRTH_CHANCE) {
+            continue;
+        }
+        let sex = Sex::random(&mut rng);
+        let child = commands
+            .spawn(Pop {
+                home: Some(home),
+                ..Pop::new(house.world_position(&geometry), 0, sex, 0)
+            })
+            .id();
+        house.residents.push(child);
+        demographics.births += 1;
+    }
+}
+
+/// Once a day pops move to the city while it has both free jobs and free homes, arriving at the
+/// edge of the map. Without room, adults who have neither a job nor a home may leave instead.
+pub(crate) fn migrate(
+    mut commands: Commands,
+    game_clock: Res<GameClock>,
+    geometry: Res<MapGeometry>,
+    mut rng: ResMut<SimRng>,
+    mut demographics: ResMut<Demographics>,
+    mut pop_query: Query<(Entity, &mut Pop)>,
+    mut house_query: Query<&mut House>,
+    mut workplace_query: Query<&mut Workplace>,
+) {
+    if !game_clock.is_day_start() {
+        return;
+    }
+
+    let free_jobs: u32 = workplace_query
+        .iter()
+        .map(|workplace| workplace.capacity.saturating_sub(workplace.employees.len() as u32))
+        .sum();
+    let free_homes: u32 = house_query
+        .iter()
+        .map(|house| house.capacity.saturating_sub(house.residents.len() as u32))
+        .sum();
+
+    if free_jobs > 0 && free_homes > 0 {
+        let arrivals = free_jobs.min(free_homes).min(MAX_IMMIGRANTS_PER_DAY);
+        for _ in 0..arrivals {
+            let tile = random_edge_tile(&mut rng, geometry.size);
+            let age = rng.gen_range(ADULT_AGE..=40);
+            let sex = Sex::random(&mut rng);
+            commands.spawn(Pop::new(geometry.tile_center(tile), age, sex, IMMIGRANT_MONEY));
+        }
+        demographics.immigrants += arrivals;
+        return;
+    }
+
+    let mut leaving: Vec<Entity> = pop_query
+        .iter()
+        .filter(|(_, pop)| pop.is_adult() && pop.job.is_none() && pop.home.is_none())
+        .map(|(entity, _)| entity)
+        .collect();
+    leaving.sort_unstable();
+    leaving.retain(|_| rng.gen_bool(EMIGRATION_CHANCE));
+
+    demographics.emigrants += leaving.len() as u32;
+    for entity in leaving {
+        remove_pop(&mut commands, entity, &mut pop_query, &mut house_query, &mut workplace_query);
+    }
+}
+
+fn random_edge_tile(rng: &mut SimRng, size: TilemapSize) -> TilePos {
+    let x = rng.gen_range(0..size.x);
+    let y = rng.gen_range(0..size.y);
+    match rng.gen_range(0..4) {
+        0 => TilePos { x, y: 0 },
+        1 => TilePos { x, y: size.y - 1 },
+        2 => TilePos { x: 0, y },
+        _ => TilePos { x: size.x - 1, y },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::*;
+    use bevy_ecs_tilemap::prelude::*;
+
+    use crate::lifecycle::{check_deaths, Demographics};
+    use crate::rng::{SimRng, SimSeed};
+    use crate::tilemap::{GameClock, House, Pop};
+
+    #[test]
+    fn test_starving_pops_die_and_leave_their_home() {
+        let mut app = App::new();
+        let game_clock = GameClock::default();
+        let ticks_per_day = game_clock.ticks_per_day() as u32;
+        app.insert_resource(game_clock)
+            .insert_resource(SimRng::new(SimSeed(1)))
+            .init_resource::<Demographics>();
+
+        let world = app.world_mut();
+        let house = world.spawn_empty().id();
+        let starving = world.spawn(Pop {
+            hunger: 10000,
+            energy: 5000,
+            starving_ticks: ticks_per_day - 1,
+            home: Some(house),
+            ..Default::default()
+        }).id();
+        let fed = world.spawn(Pop {
+            hunger: 9999,
+            energy: 5000,
+            starving_ticks: ticks_per_day - 1,
+            home: Some(house),
+            ..Default::default()
+        }).id();
+        world.entity_mut(house).insert(House {
+            capacity: 4,
+            residents: vec![starving, fed],
+            position: TilePos { x: 0, y: 0 },
+            rent: 0.0,
+            rent_collected: 0.0,
+        });
+
+        world.run_system_once(check_deaths).unwrap();
+
+        let world = app.world();
+        assert!(world.get::<Pop>(starving).is_none());
+        assert_eq!(world.get::<Pop>(fed).unwrap().starving_ticks, 0);
+        assert_eq!(world.get::<House>(house).unwrap().residents, vec![fed]);
+        assert_eq!(world.resource::<Demographics>().deaths, 1);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::mapfile::CityMap;
+use crate::scenario::Scenario;
 use crate::GameState;
 use bevy::prelude::*;
 use bevy_asset_loader::prelude::*;
@@ -14,7 +16,9 @@ impl Plugin for LoadingPlugin {
             LoadingState::new(GameState::Loading)
                 .continue_to_state(GameState::Menu)
                 .load_collection::<AudioAssets>()
-                .load_collection::<TextureAssets>(),
+                .load_collection::<TextureAssets>()
+                .load_collection::<MapAssets>()
+                .load_collection::<ScenarioAssets>(),
         );
     }
 }
@@ -24,8 +28,8 @@ impl Plugin for LoadingPlugin {
 
 #[derive(AssetCollection, Resource)]
 pub struct AudioAssets {
-    #[asset(path = "audio/flying.ogg")]
-    pub flying: Handle<AudioSource>,
+    #[asset(path = "audio/build.wav")]
+    pub build: Handle<AudioSource>,
 }
 
 #[derive(AssetCollection, Resource)]
@@ -35,3 +39,20 @@ pub struct TextureAssets {
     #[asset(path = "textures/github.png")]
     pub github: Handle<Image>,
 }
+
+/// The hand-authored maps that can be picked in the menu
+#[derive(AssetCollection, Resource)]
+pub struct MapAssets {
+    #[asset(paths("maps/company_town.map.txt", "maps/food_desert.map.txt"), collection(typed))]
+    pub maps: Vec<Handle<CityMap>>,
+}
+
+/// The scenarios that can be picked in the menu
+#[derive(AssetCollection, Resource)]
+pub struct ScenarioAssets {
+    #[asset(
+        paths("scenarios/boomtown.scenario.ron", "scenarios/famine.scenario.ron"),
+        collection(typed)
+    )]
+    pub scenarios: Vec<Handle<Scenario>>,
+}
@@ -6,15 +6,19 @@ use bevy::prelude::*;
 use bevy::window::PrimaryWindow;
 use bevy::winit::WinitWindows;
 use bevy::DefaultPlugins;
-use backpop::GamePlugin; // ToDo: Replace bevy_game with your new crate name.
+use backpop::{GamePlugin, SimSeed}; // ToDo: Replace bevy_game with your new crate name.
 use std::io::Cursor;
 use bevy_ecs_tilemap::TilemapPlugin;
 use winit::window::Icon;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 
 fn main() {
-    App::new()
-        .insert_resource(ClearColor(Color::linear_rgb(0.4, 0.4, 0.4)))
+    let mut app = App::new();
+    // `--seed <number>` reproduces a previous city
+    if let Some(seed) = SimSeed::from_args() {
+        app.insert_resource(seed);
+    }
+    app.insert_resource(ClearColor(Color::linear_rgb(0.4, 0.4, 0.4)))
         .add_plugins(
             DefaultPlugins
                 .set(WindowPlugin {
@@ -0,0 +1,401 @@
+use std::fmt;
+use std::io::Cursor;
+use std::path::Path;
+
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, LoadContext};
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::constants::MAX_MAP_SIDE;
+use crate::firms::Product;
+use crate::history::EXPORT_DIRECTORY;
+use crate::mapgen::{CityPlan, Lot};
+use crate::tilemap::{House, Restaurant, Road, Tilemap, Workplace};
+use crate::GameState;
+
+/// The character and pixel color of every lot in map files
+const LEGEND: [(Lot, char, [u8; 3]); 6] = [
+    (Lot::Empty, '.', [255, 255, 255]),
+    (Lot::Road, '#', [128, 128, 128]),
+    (Lot::House, 'H', [60, 170, 60]),
+    (Lot::Workplace, 'W', [60, 90, 200]),
+    (Lot::Farm, 'F', [230, 200, 60]),
+    (Lot::Restaurant, 'R', [230, 120, 40]),
+];
+
+pub struct MapFilePlugin;
+
+/// This plugin loads hand-authored maps through the asset server and exports the running city
+/// as a map. Maps are ASCII grids (`.map.txt`) with one character per tile, or images (`.map.png`)
+/// with one pixel per tile, see [`LEGEND`]. The first line or pixel row is the top of the map.
+impl Plugin for MapFilePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<CityMap>()
+            .register_asset_loader(TextMapLoader)
+            .register_asset_loader(ImageMapLoader)
+            .add_systems(
+                Update,
+                export_on_hotkey.run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+/// A hand-authored map, picked instead of a generator in [`crate::NewCitySettings`].
+/// Houses get their first residents when the city is spawned, like in generated cities.
+#[derive(Asset, TypePath, Clone, Debug, PartialEq)]
+pub struct CityMap {
+    /// The file name without its extensions, e.g. `company_town`
+    pub name: String,
+    size: TilemapSize,
+    /// Row by row from the bottom, like [`TilePos`]
+    lots: Vec<Lot>,
+}
+
+#[derive(Debug)]
+pub enum MapFileError {
+    Io(std::io::Error),
+    Image(image::ImageError),
+    UnknownExtension(String),
+    Empty,
+    TooLarge(TilemapSize),
+    /// `line` counts every line of the file from 0, blank ones included
+    RaggedRow { line: usize, width: usize, expected: usize },
+    UnknownSymbol { symbol: char, line: usize, column: usize },
+    UnknownColor { color: [u8; 3], row: usize, column: usize },
+}
+
+impl fmt::Display for MapFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapFileError::Io(error) => write!(f, "{error}"),
+            MapFileError::Image(error) => write!(f, "{error}"),
+            MapFileError::UnknownExtension(path) => {
+                write!(f, "{path} is neither a .map.txt nor a .map.png file")
+            }
+            MapFileError::Empty => write!(f, "the map has no tiles"),
+            MapFileError::TooLarge(size) => write!(
+                f,
+                "the map is {}x{}, but its sides may be at most {MAX_MAP_SIDE} tiles",
+                size.x, size.y
+            ),
+            MapFileError::RaggedRow { line, width, expected } => write!(
+                f,
+                "line {} is {width} tiles wide instead of {expected}",
+                line + 1
+            ),
+            MapFileError::UnknownSymbol { symbol, line, column } => write!(
+                f,
+                "unknown tile '{symbol}' on line {}, column {}",
+                line + 1,
+                column + 1
+            ),
+            MapFileError::UnknownColor { color: [r, g, b], row, column } => write!(
+                f,
+                "unknown tile color #{r:02x}{g:02x}{b:02x} at pixel ({column}, {row})"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MapFileError {}
+
+impl From<std::io::Error> for MapFileError {
+    fn from(error: std::io::Error) -> Self {
+        MapFileError::Io(error)
+    }
+}
+
+impl CityMap {
+    /// Refuses maps without tiles or too large to play, before their lots are allocated
+    fn check_size(size: TilemapSize) -> Result<(), MapFileError> {
+        if size.x == 0 || size.y == 0 {
+            return Err(MapFileError::Empty);
+        }
+        if size.x > MAX_MAP_SIDE || size.y > MAX_MAP_SIDE {
+            return Err(MapFileError::TooLarge(size));
+        }
+        Ok(())
+    }
+
+    pub fn size(&self) -> TilemapSize {
+        self.size
+    }
+
+    pub fn lot(&self, tile: TilePos) -> Lot {
+        self.lots[(tile.y * self.size.x + tile.x) as usize]
+    }
+
+    /// The city to spawn for this map
+    pub fn plan(&self) -> CityPlan {
+        CityPlan::from_lots(self.size, self.lots.clone())
+    }
+
+    /// Reads a `.map.txt` or `.map.png` file without going through the asset server
+    pub fn read(path: &Path) -> Result<Self, MapFileError> {
+        let bytes = std::fs::read(path)?;
+        let name = map_name(path);
+        let file_name = path.to_string_lossy();
+        if file_name.ends_with(".map.txt") {
+            CityMap::from_text(name, &text_from_bytes(bytes)?)
+        } else if file_name.ends_with(".map.png") {
+            CityMap::from_png(name, &bytes)
+        } else {
+            Err(MapFileError::UnknownExtension(file_name.into_owned()))
+        }
+    }
+
+    /// Parses an ASCII grid. Blank lines and trailing whitespace are ignored.
+    pub fn from_text(name: String, text: &str) -> Result<Self, MapFileError> {
+        // Blank lines are skipped, but errors name the line in the file
+        let rows: Vec<(usize, &str)> = text
+            .lines()
+            .map(str::trim_end)
+            .enumerate()
+            .filter(|(_, row)| !row.is_empty())
+            .collect();
+        let width = rows.first().map_or(0, |(_, row)| row.chars().count());
+        let size = TilemapSize {
+            x: width.try_into().unwrap_or(u32::MAX),
+            y: rows.len().try_into().unwrap_or(u32::MAX),
+        };
+        CityMap::check_size(size)?;
+
+        let mut lots = vec![Lot::Empty; width * rows.len()];
+        for (index, &(line, row)) in rows.iter().enumerate() {
+            let symbols: Vec<char> = row.chars().collect();
+            if symbols.len() != width {
+                return Err(MapFileError::RaggedRow { line, width: symbols.len(), expected: width });
+            }
+            let y = rows.len() - 1 - index;
+            for (column, symbol) in symbols.into_iter().enumerate() {
+                let (lot, ..) = LEGEND
+                    .iter()
+                    .find(|(_, candidate, _)| *candidate == symbol)
+                    .ok_or(MapFileError::UnknownSymbol { symbol, line, column })?;
+                lots[y * width + column] = *lot;
+            }
+        }
+        Ok(CityMap { name, size, lots })
+    }
+
+    /// Decodes a PNG with one pixel per tile. Transparency is ignored.
+    pub fn from_png(name: String, bytes: &[u8]) -> Result<Self, MapFileError> {
+        let image = image::load_from_memory_with_format(bytes, image::ImageFormat::Png)
+            .map_err(MapFileError::Image)?
+            .into_rgb8();
+        let size = TilemapSize { x: image.width(), y: image.height() };
+        CityMap::check_size(size)?;
+
+        let mut lots = vec![Lot::Empty; size.x as usize * size.y as usize];
+        for (column, row, pixel) in image.enumerate_pixels() {
+            let (lot, ..) = LEGEND
+                .iter()
+                .find(|(.., color)| *color == pixel.0)
+                .ok_or(MapFileError::UnknownColor {
+                    color: pixel.0,
+                    row: row as usize,
+                    column: column as usize,
+                })?;
+            let y = size.y - 1 - row;
+            lots[(y * size.x + column) as usize] = *lot;
+        }
+        Ok(CityMap { name, size, lots })
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::with_capacity(((self.size.x + 1) * self.size.y) as usize);
+        for y in (0..self.size.y).rev() {
+            for x in 0..self.size.x {
+                text.push(legend_entry(self.lot(TilePos { x, y })).1);
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    pub fn to_png(&self) -> Result<Vec<u8>, MapFileError> {
+        let image = image::RgbImage::from_fn(self.size.x, self.size.y, |x, row| {
+            let y = self.size.y - 1 - row;
+            image::Rgb(legend_entry(self.lot(TilePos { x, y })).2)
+        });
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bytes), image::ImageFormat::Png)
+            .map_err(MapFileError::Image)?;
+        Ok(bytes)
+    }
+}
+
+fn legend_entry(lot: Lot) -> (Lot, char, [u8; 3]) {
+    *LEGEND
+        .iter()
+        .find(|(candidate, ..)| *candidate == lot)
+        .expect("every lot is in the legend")
+}
+
+/// `maps/company_town.map.txt` is called `company_town`
+fn map_name(path: &Path) -> String {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    file_name.split('.').next().unwrap_or_default().to_string()
+}
+
+fn text_from_bytes(bytes: Vec<u8>) -> Result<String, MapFileError> {
+    String::from_utf8(bytes)
+        .map_err(|error| MapFileError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, error)))
+}
+
+#[derive(Default)]
+struct TextMapLoader;
+
+impl AssetLoader for TextMapLoader {
+    type Asset = CityMap;
+    type Settings = ();
+    type Error = MapFileError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<CityMap, MapFileError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        CityMap::from_text(map_name(load_context.path()), &text_from_bytes(bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["map.txt"]
+    }
+}
+
+#[derive(Default)]
+struct ImageMapLoader;
+
+impl AssetLoader for ImageMapLoader {
+    type Asset = CityMap;
+    type Settings = ();
+    type Error = MapFileError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<CityMap, MapFileError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        CityMap::from_png(map_name(load_context.path()), &bytes)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["map.png"]
+    }
+}
+
+/// Writes the running city's tiles to `exports/city.map.txt` and `exports/city.map.png`
+fn export_on_hotkey(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    tilemap_query: Query<(&TilemapSize, &TileStorage), With<Tilemap>>,
+    tile_query: Query<(Has<Road>, Has<House>, Option<&Workplace>, Has<Restaurant>)>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F7) {
+        return;
+    }
+    let Ok((size, storage)) = tilemap_query.get_single() else {
+        return;
+    };
+    let mut lots = Vec::with_capacity((size.x * size.y) as usize);
+    for y in 0..size.y {
+        for x in 0..size.x {
+            let lot = match storage.get(&TilePos { x, y }).and_then(|tile| tile_query.get(tile).ok()) {
+                Some((true, ..)) => Lot::Road,
+                Some((_, true, ..)) => Lot::House,
+                Some((_, _, Some(workplace), _)) => match workplace.product {
+                    Product::Goods => Lot::Workplace,
+                    Product::Food => Lot::Farm,
+                },
+                Some((.., true)) => Lot::Restaurant,
+                _ => Lot::Empty,
+            };
+            lots.push(lot);
+        }
+    }
+    let map = CityMap { name: "city".to_string(), size: *size, lots };
+
+    let directory = Path::new(EXPORT_DIRECTORY);
+    let result = map.to_png().and_then(|png| {
+        std::fs::create_dir_all(directory)?;
+        std::fs::write(directory.join("city.map.txt"), map.to_text())?;
+        std::fs::write(directory.join("city.map.png"), png)?;
+        Ok(())
+    });
+    match result {
+        Ok(()) => info!("Exported the map to {EXPORT_DIRECTORY}/city.map.txt and .map.png"),
+        Err(error) => warn!("Failed to export the map to {EXPORT_DIRECTORY}/: {error}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use bevy_ecs_tilemap::prelude::*;
+
+    use crate::constants::MAX_MAP_SIDE;
+    use crate::mapfile::{CityMap, MapFileError};
+    use crate::mapgen::Lot;
+
+    const TOWN: &str = "\
+##H.
+#WFR
+";
+
+    #[test]
+    fn test_maps_round_trip_through_text_and_png() {
+        let map = CityMap::from_text("town".to_string(), TOWN).unwrap();
+        assert_eq!(map.size(), TilemapSize { x: 4, y: 2 });
+        // The first line is the top row
+        assert_eq!(map.lot(TilePos { x: 2, y: 1 }), Lot::House);
+        assert_eq!(map.lot(TilePos { x: 3, y: 1 }), Lot::Empty);
+        assert_eq!(map.lot(TilePos { x: 1, y: 0 }), Lot::Workplace);
+        assert_eq!(map.lot(TilePos { x: 2, y: 0 }), Lot::Farm);
+        assert_eq!(map.lot(TilePos { x: 3, y: 0 }), Lot::Restaurant);
+        assert_eq!(map.to_text(), TOWN);
+
+        let png = map.to_png().unwrap();
+        assert_eq!(CityMap::from_png("town".to_string(), &png).unwrap(), map);
+    }
+
+    #[test]
+    fn test_broken_maps_are_rejected() {
+        assert!(matches!(
+            CityMap::from_text("town".to_string(), "##H.\n#WF\n"),
+            Err(MapFileError::RaggedRow { line: 1, width: 3, expected: 4 })
+        ));
+        assert!(matches!(
+            CityMap::from_text("town".to_string(), "##H.\n#WFX\n"),
+            Err(MapFileError::UnknownSymbol { symbol: 'X', line: 1, column: 3 })
+        ));
+        // Blank lines still count when naming the broken line
+        assert!(matches!(
+            CityMap::from_text("town".to_string(), "\n##H.\n\n#WFX\n"),
+            Err(MapFileError::UnknownSymbol { symbol: 'X', line: 3, column: 3 })
+        ));
+        let too_wide = ".".repeat(MAX_MAP_SIDE as usize + 1);
+        assert!(matches!(
+            CityMap::from_text("town".to_string(), &too_wide),
+            Err(MapFileError::TooLarge(_))
+        ));
+        assert!(matches!(CityMap::from_text("town".to_string(), "\n\n"), Err(MapFileError::Empty)));
+    }
+
+    #[test]
+    fn test_shipped_maps_load() {
+        for path in ["assets/maps/company_town.map.txt", "assets/maps/food_desert.map.txt"] {
+            let map = CityMap::read(Path::new(path)).unwrap_or_else(|error| panic!("{path}: {error}"));
+            let houses = map.lots.iter().filter(|lot| **lot == Lot::House).count();
+            assert!(houses > 0, "{path} has no houses");
+        }
+    }
+}
@@ -0,0 +1,422 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::constants::DEFAULT_MAP_SIZE;
+use crate::mapfile::CityMap;
+use crate::rng::SimRng;
+use crate::scenario::Scenario;
+
+/// Side length in tiles of the districts a city is zoned in
+const DISTRICT_SIZE: u32 = 8;
+/// Distance between two parallel streets of a [`RoadGrid`]
+const BLOCK_SIZE: u32 = 5;
+/// Share of the tiles an [`Organic`] street network grows to
+const ORGANIC_ROAD_SHARE: f32 = 0.22;
+/// Chance an [`Organic`] street turns at a tile
+const TURN_CHANCE: f64 = 0.08;
+/// Chance a side street branches off an [`Organic`] street at a tile
+const BRANCH_CHANCE: f64 = 0.06;
+/// Chance an [`Organic`] street ends where it runs into another one
+const JOIN_CHANCE: f64 = 0.7;
+/// Share of tiles a [`Scattered`] city builds on at a density of 1
+const SCATTERED_BUILD_SHARE: f64 = 0.35;
+
+/// What the next generated city looks like, chosen in the main menu or on the command line.
+/// The layout is generated from the [`crate::SimSeed`].
+#[derive(Resource, Clone, Debug)]
+pub struct NewCitySettings {
+    pub map_size: TilemapSize,
+    pub layout: Layout,
+    pub params: GeneratorParams,
+    /// A hand-authored map to build instead, its size replaces `map_size`
+    pub map: Option<CityMap>,
+    /// Adults living in the houses when the city is founded, a random few per house if unset
+    pub starting_population: Option<usize>,
+    /// Started together with the city, see [`crate::scenario::ActiveScenario`]
+    pub scenario: Option<Scenario>,
+}
+
+impl Default for NewCitySettings {
+    fn default() -> Self {
+        Self {
+            map_size: DEFAULT_MAP_SIZE,
+            layout: Layout::default(),
+            params: GeneratorParams::default(),
+            map: None,
+            starting_population: None,
+            scenario: None,
+        }
+    }
+}
+
+impl NewCitySettings {
+    /// Lays out the next city, from the chosen map or generator
+    pub fn plan(&self, rng: &mut SimRng) -> CityPlan {
+        match &self.map {
+            Some(map) => map.plan(),
+            None => self.layout.generator().generate(self.map_size, &self.params, rng),
+        }
+    }
+}
+
+/// Tunables every generator understands
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GeneratorParams {
+    /// Chance a free lot is built on, from 0 to 1
+    pub density: f64,
+    /// Share of the districts zoned residential, relative to the other two shares
+    pub residential: f32,
+    /// Share of the districts zoned commercial
+    pub commercial: f32,
+    /// Share of the districts zoned industrial
+    pub industrial: f32,
+}
+
+impl Default for GeneratorParams {
+    fn default() -> Self {
+        Self {
+            density: 0.5,
+            residential: 0.6,
+            commercial: 0.15,
+            industrial: 0.25,
+        }
+    }
+}
+
+/// What a generator puts on a tile
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lot {
+    Empty,
+    Road,
+    House,
+    Workplace,
+    Farm,
+    Restaurant,
+}
+
+/// Decides which buildings go up in a district
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Zone {
+    /// Houses and the odd corner restaurant
+    Residential,
+    /// Restaurants and workplaces
+    Commercial,
+    /// Workplaces and farms
+    Industrial,
+}
+
+impl Zone {
+    /// Picks the building for a lot in this zone
+    fn building(self, rng: &mut SimRng) -> Lot {
+        match (self, rng.gen_range(0..10)) {
+            (Zone::Residential, 0) => Lot::Restaurant,
+            (Zone::Residential, _) => Lot::House,
+            (Zone::Commercial, 0..=3) => Lot::Restaurant,
+            (Zone::Commercial, _) => Lot::Workplace,
+            (Zone::Industrial, 0..=3) => Lot::Workplace,
+            (Zone::Industrial, _) => Lot::Farm,
+        }
+    }
+}
+
+/// A generated city before anything is spawned: the lot on every tile and the zone it is in
+pub struct CityPlan {
+    size: TilemapSize,
+    lots: Vec<Lot>,
+    zones: Vec<Zone>,
+}
+
+impl CityPlan {
+    /// An empty map zoned according to `params`: a commercial downtown, residential districts
+    /// around it and industry on the outskirts, with ragged borders between them
+    pub fn zoned(size: TilemapSize, params: &GeneratorParams, rng: &mut SimRng) -> Self {
+        let districts = UVec2::new(size.x.div_ceil(DISTRICT_SIZE), size.y.div_ceil(DISTRICT_SIZE));
+        let center = districts.as_vec2() / 2.0;
+        let mut ranked: Vec<(f32, UVec2)> = (0..districts.x)
+            .flat_map(|x| (0..districts.y).map(move |y| UVec2::new(x, y)))
+            .map(|district| {
+                let distance = (district.as_vec2() + Vec2::splat(0.5)).distance(center);
+                (distance * rng.gen_range(0.75..1.25), district)
+            })
+            .collect();
+        ranked.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+
+        let total = params.residential + params.commercial + params.industrial;
+        let (commercial, residential) = if total > 0.0 {
+            (params.commercial / total, (params.commercial + params.residential) / total)
+        } else {
+            (0.0, 1.0)
+        };
+        let mut district_zones = vec![Zone::Residential; ranked.len()];
+        for (rank, (_, district)) in ranked.iter().enumerate() {
+            let share = (rank as f32 + 0.5) / ranked.len() as f32;
+            district_zones[(district.y * districts.x + district.x) as usize] = if share < commercial {
+                Zone::Commercial
+            } else if share < residential {
+                Zone::Residential
+            } else {
+                Zone::Industrial
+            };
+        }
+
+        let mut zones = Vec::with_capacity((size.x * size.y) as usize);
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let district = UVec2::new(x, y) / DISTRICT_SIZE;
+                zones.push(district_zones[(district.y * districts.x + district.x) as usize]);
+            }
+        }
+        Self {
+            size,
+            lots: vec![Lot::Empty; (size.x * size.y) as usize],
+            zones,
+        }
+    }
+
+    /// A plan whose lots were decided elsewhere, e.g. in a map file. All of it counts as residential.
+    pub fn from_lots(size: TilemapSize, lots: Vec<Lot>) -> Self {
+        assert_eq!(lots.len(), (size.x * size.y) as usize, "every tile needs a lot");
+        Self {
+            size,
+            zones: vec![Zone::Residential; lots.len()],
+            lots,
+        }
+    }
+
+    pub fn size(&self) -> TilemapSize {
+        self.size
+    }
+
+    fn index(&self, tile: TilePos) -> usize {
+        (tile.y * self.size.x + tile.x) as usize
+    }
+
+    pub fn lot(&self, tile: TilePos) -> Lot {
+        self.lots[self.index(tile)]
+    }
+
+    /// Number of tiles with `lot` on them
+    pub fn count(&self, lot: Lot) -> usize {
+        self.lots.iter().filter(|candidate| **candidate == lot).count()
+    }
+
+    pub fn zone(&self, tile: TilePos) -> Zone {
+        self.zones[self.index(tile)]
+    }
+
+    pub fn set_lot(&mut self, tile: TilePos, lot: Lot) {
+        let index = self.index(tile);
+        self.lots[index] = lot;
+    }
+
+    /// The tile at `position` if it is on the map
+    fn tile(&self, position: IVec2) -> Option<TilePos> {
+        let inside = position.x >= 0
+            && position.y >= 0
+            && (position.x as u32) < self.size.x
+            && (position.y as u32) < self.size.y;
+        inside.then_some(TilePos { x: position.x as u32, y: position.y as u32 })
+    }
+
+    fn is_road(&self, position: IVec2) -> bool {
+        self.tile(position).is_some_and(|tile| self.lot(tile) == Lot::Road)
+    }
+
+    /// Fills the empty lots next to a road with the buildings of their zone, each with a chance of
+    /// `density`, so every building can be reached by road
+    pub fn build_along_roads(&mut self, density: f64, rng: &mut SimRng) {
+        for y in 0..self.size.y {
+            for x in 0..self.size.x {
+                let tile = TilePos { x, y };
+                let position = IVec2::new(x as i32, y as i32);
+                let on_road = [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y]
+                    .into_iter()
+                    .any(|step| self.is_road(position + step));
+                if self.lot(tile) == Lot::Empty && on_road && rng.gen_bool(density.clamp(0.0, 1.0)) {
+                    let building = self.zone(tile).building(rng);
+                    self.set_lot(tile, building);
+                }
+            }
+        }
+    }
+}
+
+/// Lays out a new city. Implement this and add a [`Layout`] for it to offer a new kind of map.
+pub trait MapGenerator {
+    fn generate(&self, size: TilemapSize, params: &GeneratorParams, rng: &mut SimRng) -> CityPlan;
+}
+
+/// Straight streets every [`BLOCK_SIZE`] tiles with buildings lining the blocks
+pub struct RoadGrid;
+
+impl MapGenerator for RoadGrid {
+    fn generate(&self, size: TilemapSize, params: &GeneratorParams, rng: &mut SimRng) -> CityPlan {
+        let mut plan = CityPlan::zoned(size, params, rng);
+        for y in 0..size.y {
+            for x in 0..size.x {
+                if x % BLOCK_SIZE == BLOCK_SIZE / 2 || y % BLOCK_SIZE == BLOCK_SIZE / 2 {
+                    plan.set_lot(TilePos { x, y }, Lot::Road);
+                }
+            }
+        }
+        plan.build_along_roads(params.density, rng);
+        plan
+    }
+}
+
+/// Streets that wind and branch outwards from the center of the map
+pub struct Organic;
+
+impl MapGenerator for Organic {
+    fn generate(&self, size: TilemapSize, params: &GeneratorParams, rng: &mut SimRng) -> CityPlan {
+        let mut plan = CityPlan::zoned(size, params, rng);
+        let target = ((size.x * size.y) as f32 * ORGANIC_ROAD_SHARE) as usize;
+        let center = IVec2::new(size.x as i32 / 2, size.y as i32 / 2);
+        let Some(center_tile) = plan.tile(center) else {
+            return plan;
+        };
+        plan.set_lot(center_tile, Lot::Road);
+        let mut roads = vec![center];
+        // Each street grows one tile at a time, the newest first so streets get long
+        let mut streets: Vec<(IVec2, IVec2)> = [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y]
+            .map(|direction| (center, direction))
+            .to_vec();
+        // Every tile can be visited a few times before the network is given up on
+        let mut steps_left = size.x as usize * size.y as usize * 4;
+
+        while roads.len() < target && steps_left > 0 {
+            steps_left -= 1;
+            let Some((position, mut direction)) = streets.pop() else {
+                // All streets ended, start a new one somewhere on the network
+                let start = roads[rng.gen_range(0..roads.len())];
+                let direction = [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y][rng.gen_range(0..4)];
+                streets.push((start, direction));
+                continue;
+            };
+            let next = position + direction;
+            let Some(tile) = plan.tile(next) else {
+                continue;
+            };
+            if plan.lot(tile) == Lot::Road {
+                if !rng.gen_bool(JOIN_CHANCE) {
+                    streets.push((next, direction));
+                }
+                continue;
+            }
+            plan.set_lot(tile, Lot::Road);
+            roads.push(next);
+
+            if rng.gen_bool(BRANCH_CHANCE) {
+                let side = if rng.gen_bool(0.5) { direction.perp() } else { -direction.perp() };
+                streets.push((next, side));
+            }
+            if rng.gen_bool(TURN_CHANCE) {
+                direction = if rng.gen_bool(0.5) { direction.perp() } else { -direction.perp() };
+            }
+            streets.push((next, direction));
+        }
+        plan.build_along_roads(params.density, rng);
+        plan
+    }
+}
+
+/// Buildings dropped on random tiles without any streets, how the first cities were made
+pub struct Scattered;
+
+impl MapGenerator for Scattered {
+    fn generate(&self, size: TilemapSize, params: &GeneratorParams, rng: &mut SimRng) -> CityPlan {
+        let mut plan = CityPlan::zoned(size, params, rng);
+        let chance = (params.density * SCATTERED_BUILD_SHARE).clamp(0.0, 1.0);
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let tile = TilePos { x, y };
+                if rng.gen_bool(chance) {
+                    let building = plan.zone(tile).building(rng);
+                    plan.set_lot(tile, building);
+                }
+            }
+        }
+        plan
+    }
+}
+
+/// The generators a new city can be made with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Layout {
+    #[default]
+    Grid,
+    Organic,
+    Scattered,
+}
+
+impl Layout {
+    pub const ALL: [Layout; 3] = [Layout::Grid, Layout::Organic, Layout::Scattered];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Layout::Grid => "grid",
+            Layout::Organic => "organic",
+            Layout::Scattered => "scattered",
+        }
+    }
+
+    pub fn generator(self) -> &'static dyn MapGenerator {
+        match self {
+            Layout::Grid => &RoadGrid,
+            Layout::Organic => &Organic,
+            Layout::Scattered => &Scattered,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+    use bevy_ecs_tilemap::prelude::*;
+
+    use crate::mapgen::{GeneratorParams, Layout, Lot, Zone};
+    use crate::rng::{SimRng, SimSeed};
+
+    #[test]
+    fn test_generated_cities_are_zoned_and_built_along_roads() {
+        let size = TilemapSize { x: 48, y: 40 };
+        let params = GeneratorParams::default();
+        for layout in [Layout::Grid, Layout::Organic] {
+            let plan = layout.generator().generate(size, &params, &mut SimRng::new(SimSeed(5)));
+            let same = layout.generator().generate(size, &params, &mut SimRng::new(SimSeed(5)));
+            assert_eq!(plan.lots, same.lots, "{layout:?} should only depend on the seed");
+
+            let mut buildings = 0;
+            for (x, y) in (0..size.x).flat_map(|x| (0..size.y).map(move |y| (x, y))) {
+                let tile = TilePos { x, y };
+                let lot = plan.lot(tile);
+                if matches!(lot, Lot::Empty | Lot::Road) {
+                    continue;
+                }
+                buildings += 1;
+                let position = IVec2::new(x as i32, y as i32);
+                let on_road = [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y]
+                    .into_iter()
+                    .any(|step| plan.is_road(position + step));
+                assert!(on_road, "{layout:?} built a {lot:?} at {tile:?} away from the roads");
+                match plan.zone(tile) {
+                    Zone::Residential => assert!(matches!(lot, Lot::House | Lot::Restaurant)),
+                    Zone::Commercial => assert!(matches!(lot, Lot::Workplace | Lot::Restaurant)),
+                    Zone::Industrial => assert!(matches!(lot, Lot::Workplace | Lot::Farm)),
+                }
+            }
+            assert!(buildings > 100, "{layout:?} only built {buildings} buildings");
+        }
+
+        // Downtown is commercial and the outskirts industrial
+        let plan = Layout::Grid.generator().generate(size, &params, &mut SimRng::new(SimSeed(5)));
+        assert_eq!(plan.zone(TilePos { x: 24, y: 20 }), Zone::Commercial);
+        assert_eq!(plan.zone(TilePos { x: 0, y: 0 }), Zone::Industrial);
+
+        let residential = GeneratorParams { residential: 1.0, commercial: 0.0, industrial: 0.0, ..params };
+        let plan = Layout::Scattered.generator().generate(size, &residential, &mut SimRng::new(SimSeed(5)));
+        assert!(plan.zones.iter().all(|zone| *zone == Zone::Residential));
+    }
+}
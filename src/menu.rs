@@ -1,6 +1,13 @@
-use crate::loading::TextureAssets;
+use crate::constants::MAX_MAP_SIDE;
+use crate::loading::{MapAssets, ScenarioAssets, TextureAssets};
+use crate::mapfile::CityMap;
+use crate::mapgen::{Layout, NewCitySettings};
+use crate::rng::SimSeed;
+use crate::save::LoadCity;
+use crate::scenario::Scenario;
 use crate::GameState;
 use bevy::prelude::*;
+use bevy_ecs_tilemap::map::TilemapSize;
 
 pub struct MenuPlugin;
 
@@ -32,9 +39,132 @@ impl Default for ButtonColors {
 #[derive(Component)]
 struct Menu;
 
-fn setup_menu(mut commands: Commands, textures: Res<TextureAssets>) {
+/// Sides of the square maps the map size button cycles through
+const MAP_SIDES: [u32; 5] = [32, 64, 128, 256, MAX_MAP_SIDE];
+/// Building densities the density button cycles through
+const DENSITIES: [(&str, f64); 3] = [("sparse", 0.3), ("normal", 0.5), ("dense", 0.8)];
+/// Residential, commercial and industrial shares the zoning button cycles through
+const ZONINGS: [(&str, [f32; 3]); 3] = [
+    ("balanced", [0.6, 0.15, 0.25]),
+    ("suburban", [0.75, 0.1, 0.15]),
+    ("industrial", [0.45, 0.15, 0.4]),
+];
+
+/// A choice about the next city, changed by clicking its button in the menu
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum NewCitySetting {
+    Scenario,
+    Map,
+    MapSize,
+    Layout,
+    Density,
+    Zoning,
+    Seed,
+}
+
+impl NewCitySetting {
+    const ALL: [NewCitySetting; 7] = [
+        NewCitySetting::Scenario,
+        NewCitySetting::Map,
+        NewCitySetting::MapSize,
+        NewCitySetting::Layout,
+        NewCitySetting::Density,
+        NewCitySetting::Zoning,
+        NewCitySetting::Seed,
+    ];
+
+    fn label(self, settings: &NewCitySettings, seed: SimSeed) -> String {
+        let params = &settings.params;
+        match self {
+            NewCitySetting::Scenario => match &settings.scenario {
+                Some(scenario) => format!("Scenario: {}", scenario.name),
+                None => "Scenario: none".to_string(),
+            },
+            NewCitySetting::Map => match &settings.map {
+                Some(map) => format!("City: {}", map.name.replace('_', " ")),
+                None => "City: generated".to_string(),
+            },
+            NewCitySetting::MapSize => format!("Map {}x{}", settings.map_size.x, settings.map_size.y),
+            NewCitySetting::Layout => format!("Streets: {}", settings.layout.label()),
+            NewCitySetting::Density => match DENSITIES.iter().find(|(_, density)| *density == params.density) {
+                Some((name, _)) => format!("Density: {name}"),
+                None => format!("Density: {:.2}", params.density),
+            },
+            NewCitySetting::Zoning => {
+                let shares = [params.residential, params.commercial, params.industrial];
+                match ZONINGS.iter().find(|(_, zoning)| *zoning == shares) {
+                    Some((name, _)) => format!("Zoning: {name}"),
+                    None => "Zoning: custom".to_string(),
+                }
+            }
+            NewCitySetting::Seed => format!("Seed {}", seed.0),
+        }
+    }
+
+    /// Moves on to the next choice. Values set on the command line aren't in the lists, those start over.
+    /// The city and scenario buttons go through the generator or no scenario and then each of the `choices`.
+    fn cycle(self, settings: &mut NewCitySettings, seed: &mut SimSeed, choices: &Choices) {
+        fn next<T: PartialEq>(choices: &[T], current: impl Fn(&T) -> bool) -> usize {
+            choices.iter().position(current).map_or(0, |index| (index + 1) % choices.len())
+        }
+        let params = &mut settings.params;
+        match self {
+            NewCitySetting::Scenario => {
+                let current = settings.scenario.as_ref().and_then(|chosen| {
+                    choices.scenarios.iter().position(|(scenario, _)| scenario.name == chosen.name)
+                });
+                match current.map_or(choices.scenarios.first(), |index| choices.scenarios.get(index + 1)) {
+                    Some((scenario, map)) => scenario.configure(settings, seed, map.cloned()),
+                    None => {
+                        settings.scenario = None;
+                        settings.starting_population = None;
+                        settings.map = None;
+                    }
+                }
+            }
+            NewCitySetting::Map => {
+                let maps = &choices.maps;
+                let current = settings.map.as_ref().and_then(|chosen| maps.iter().position(|map| *map == chosen));
+                settings.map = match current {
+                    None => maps.first(),
+                    Some(index) => maps.get(index + 1),
+                }
+                .map(|map| (*map).clone());
+            }
+            NewCitySetting::MapSize => {
+                let side = MAP_SIDES[next(&MAP_SIDES, |side| settings.map_size == TilemapSize { x: *side, y: *side })];
+                settings.map_size = TilemapSize { x: side, y: side };
+            }
+            NewCitySetting::Layout => {
+                settings.layout = Layout::ALL[next(&Layout::ALL, |layout| *layout == settings.layout)];
+            }
+            NewCitySetting::Density => {
+                params.density = DENSITIES[next(&DENSITIES, |(_, density)| *density == params.density)].1;
+            }
+            NewCitySetting::Zoning => {
+                let shares = [params.residential, params.commercial, params.industrial];
+                [params.residential, params.commercial, params.industrial] =
+                    ZONINGS[next(&ZONINGS, |(_, zoning)| *zoning == shares)].1;
+            }
+            NewCitySetting::Seed => *seed = SimSeed(rand::random()),
+        }
+    }
+}
+
+/// What the city and scenario buttons pick from, as far as it finished loading
+struct Choices<'a> {
+    maps: Vec<&'a CityMap>,
+    /// Each with its loaded map, if it has one
+    scenarios: Vec<(&'a Scenario, Option<&'a CityMap>)>,
+}
+
+fn setup_menu(
+    mut commands: Commands,
+    textures: Res<TextureAssets>,
+    settings: Res<NewCitySettings>,
+    seed: Res<SimSeed>,
+) {
     info!("menu");
-    commands.spawn((Camera2d, Msaa::Off));
     commands
         .spawn((
             Node {
@@ -71,6 +201,57 @@ fn setup_menu(mut commands: Commands, textures: Res<TextureAssets>) {
                     },
                     TextColor(Color::linear_rgb(0.9, 0.9, 0.9)),
                 ));
+            for setting in NewCitySetting::ALL {
+                let button_colors = ButtonColors::default();
+                children
+                    .spawn((
+                        Button,
+                        Node {
+                            width: Val::Px(260.0),
+                            height: Val::Px(30.0),
+                            margin: UiRect::top(Val::Px(6.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        BackgroundColor(button_colors.normal),
+                        button_colors,
+                        CycleSetting(setting),
+                    ))
+                    .with_child((
+                        Text::new(setting.label(&settings, *seed)),
+                        TextFont {
+                            font_size: 20.0,
+                            ..default()
+                        },
+                        TextColor(Color::linear_rgb(0.9, 0.9, 0.9)),
+                        SettingLabel(setting),
+                    ));
+            }
+            let button_colors = ButtonColors::default();
+            children
+                .spawn((
+                    Button,
+                    Node {
+                        width: Val::Px(140.0),
+                        height: Val::Px(50.0),
+                        margin: UiRect::top(Val::Px(10.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    BackgroundColor(button_colors.normal),
+                    button_colors,
+                    LoadSavedCity,
+                ))
+                .with_child((
+                    Text::new("Load"),
+                    TextFont {
+                        font_size: 40.0,
+                        ..default()
+                    },
+                    TextColor(Color::linear_rgb(0.9, 0.9, 0.9)),
+                ));
         });
     commands
         .spawn((
@@ -168,8 +349,25 @@ struct ChangeState(GameState);
 #[derive(Component)]
 struct OpenLink(&'static str);
 
+#[derive(Component)]
+struct LoadSavedCity;
+
+#[derive(Component)]
+struct CycleSetting(NewCitySetting);
+
+#[derive(Component)]
+struct SettingLabel(NewCitySetting);
+
 fn click_play_button(
     mut next_state: ResMut<NextState<GameState>>,
+    mut load_events: EventWriter<LoadCity>,
+    mut settings: ResMut<NewCitySettings>,
+    mut seed: ResMut<SimSeed>,
+    map_assets: Res<MapAssets>,
+    scenario_assets: Res<ScenarioAssets>,
+    city_maps: Res<Assets<CityMap>>,
+    scenarios: Res<Assets<Scenario>>,
+    mut label_query: Query<(&mut Text, &SettingLabel)>,
     mut interaction_query: Query<
         (
             &Interaction,
@@ -177,15 +375,39 @@ fn click_play_button(
             &ButtonColors,
             Option<&ChangeState>,
             Option<&OpenLink>,
+            Option<&LoadSavedCity>,
+            Option<&CycleSetting>,
         ),
         (Changed<Interaction>, With<Button>),
     >,
 ) {
-    for (interaction, mut color, button_colors, change_state, open_link) in &mut interaction_query {
+    for (interaction, mut color, button_colors, change_state, open_link, load_saved_city, cycle_setting) in
+        &mut interaction_query
+    {
         match *interaction {
             Interaction::Pressed => {
                 if let Some(state) = change_state {
                     next_state.set(state.0.clone());
+                } else if load_saved_city.is_some() {
+                    load_events.send(LoadCity);
+                } else if let Some(CycleSetting(setting)) = cycle_setting {
+                    let choices = Choices {
+                        maps: map_assets.maps.iter().filter_map(|map| city_maps.get(map)).collect(),
+                        scenarios: scenario_assets
+                            .scenarios
+                            .iter()
+                            .filter_map(|scenario| scenarios.get(scenario))
+                            .map(|scenario| {
+                                let map = scenario.map_handle.as_ref().and_then(|map| city_maps.get(map));
+                                (scenario, map)
+                            })
+                            .collect(),
+                    };
+                    setting.cycle(&mut settings, &mut seed, &choices);
+                    // A scenario changes the other settings too
+                    for (mut text, SettingLabel(labeled)) in &mut label_query {
+                        text.0 = labeled.label(&settings, *seed);
+                    }
                 } else if let Some(link) = open_link {
                     if let Err(error) = webbrowser::open(link.0) {
                         warn!("Failed to open link {error:?}");
@@ -0,0 +1,459 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::coords::MapGeometry;
+use crate::tilemap::{House, Pop, Workplace};
+use crate::GameState;
+
+pub struct OverlayPlugin;
+
+/// This plugin tints the map by one metric at a time, see [`OverlayMode`].
+/// Modes are picked with their hotkey or from the overlay bar, the legend beneath it
+/// explains the colors.
+impl Plugin for OverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Overlay>()
+            .add_systems(OnEnter(GameState::Playing), spawn_overlay_bar)
+            .add_systems(
+                Update,
+                (pick_overlay, tint_tiles, update_legend)
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnExit(GameState::Playing), despawn_overlay_bar);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum OverlayMode {
+    /// Share of the beds of each house that are taken
+    Occupancy,
+    /// Share of the jobs of each workplace that are open
+    JobVacancy,
+    /// Average money of the residents of each house
+    Wealth,
+    /// Average hunger of the pops on each tile
+    Hunger,
+    /// Average distance in tiles from the residents of each house to their jobs
+    Commute,
+    /// Number of pops on each tile
+    Density,
+}
+
+impl OverlayMode {
+    const ALL: [OverlayMode; 6] = [
+        OverlayMode::Occupancy,
+        OverlayMode::JobVacancy,
+        OverlayMode::Wealth,
+        OverlayMode::Hunger,
+        OverlayMode::Commute,
+        OverlayMode::Density,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            OverlayMode::Occupancy => "O Occupancy",
+            OverlayMode::JobVacancy => "J Vacancies",
+            OverlayMode::Wealth => "M Wealth",
+            OverlayMode::Hunger => "H Hunger",
+            OverlayMode::Commute => "T Commute",
+            OverlayMode::Density => "P Density",
+        }
+    }
+
+    fn hotkey(self) -> KeyCode {
+        match self {
+            OverlayMode::Occupancy => KeyCode::KeyO,
+            OverlayMode::JobVacancy => KeyCode::KeyJ,
+            OverlayMode::Wealth => KeyCode::KeyM,
+            OverlayMode::Hunger => KeyCode::KeyH,
+            OverlayMode::Commute => KeyCode::KeyT,
+            OverlayMode::Density => KeyCode::KeyP,
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            OverlayMode::Occupancy => "Beds taken in each house",
+            OverlayMode::JobVacancy => "Open jobs at each workplace",
+            OverlayMode::Wealth => "Average money of the residents",
+            OverlayMode::Hunger => "Average hunger of the pops on each tile",
+            OverlayMode::Commute => "Average distance from home to work, in tiles",
+            OverlayMode::Density => "Pops on each tile",
+        }
+    }
+
+    /// Values at the two ends of the color scale. Modes without a natural scale
+    /// stretch it over the values found on the map.
+    fn fixed_range(self) -> Option<(f32, f32)> {
+        match self {
+            OverlayMode::Occupancy | OverlayMode::JobVacancy => Some((0.0, 1.0)),
+            OverlayMode::Hunger => Some((0.0, 10000.0)),
+            OverlayMode::Wealth | OverlayMode::Commute | OverlayMode::Density => None,
+        }
+    }
+
+    fn format(self, value: f32) -> String {
+        match self {
+            OverlayMode::Occupancy | OverlayMode::JobVacancy => format!("{:.0}%", value * 100.0),
+            OverlayMode::Wealth => format!("${value:.0}"),
+            OverlayMode::Hunger => format!("{value:.0}"),
+            OverlayMode::Commute => format!("{value:.1}"),
+            OverlayMode::Density => format!("{value:.0}"),
+        }
+    }
+
+    /// The metric of every tile it applies to, tiles without a value are left out
+    fn tile_values(
+        self,
+        geometry: &MapGeometry,
+        pop_query: &Query<&Pop>,
+        house_query: &Query<&House>,
+        workplace_query: &Query<&Workplace>,
+    ) -> HashMap<TilePos, f32> {
+        let mut values = HashMap::new();
+        match self {
+            OverlayMode::Occupancy => {
+                for house in house_query.iter() {
+                    let occupancy = house.residents.len() as f32 / house.capacity.max(1) as f32;
+                    values.insert(house.position, occupancy);
+                }
+            }
+            OverlayMode::JobVacancy => {
+                for workplace in workplace_query.iter() {
+                    let capacity = workplace.capacity.max(1) as f32;
+                    let open = capacity - workplace.employees.len() as f32;
+                    values.insert(workplace.position, open / capacity);
+                }
+            }
+            OverlayMode::Wealth => {
+                for house in house_query.iter() {
+                    let money = house
+                        .residents
+                        .iter()
+                        .filter_map(|resident| pop_query.get(*resident).ok())
+                        .map(|pop| pop.money as f32);
+                    if let Some(average) = average(money) {
+                        values.insert(house.position, average);
+                    }
+                }
+            }
+            OverlayMode::Commute => {
+                for house in house_query.iter() {
+                    let distances = house
+                        .residents
+                        .iter()
+                        .filter_map(|resident| pop_query.get(*resident).ok()?.job.as_ref())
+                        .map(|job| {
+                            let (from, to) = (house.position, job.position);
+                            Vec2::new(from.x as f32, from.y as f32)
+                                .distance(Vec2::new(to.x as f32, to.y as f32))
+                        });
+                    if let Some(average) = average(distances) {
+                        values.insert(house.position, average);
+                    }
+                }
+            }
+            OverlayMode::Hunger | OverlayMode::Density => {
+                let mut per_tile: HashMap<TilePos, (f32, u32)> = HashMap::new();
+                for pop in pop_query.iter() {
+                    if let Some(tile) = geometry.tile_at(pop.position) {
+                        let (hunger, count) = per_tile.entry(tile).or_default();
+                        *hunger += pop.hunger as f32;
+                        *count += 1;
+                    }
+                }
+                for (tile, (hunger, count)) in per_tile {
+                    let value = match self {
+                        OverlayMode::Hunger => hunger / count as f32,
+                        _ => count as f32,
+                    };
+                    values.insert(tile, value);
+                }
+            }
+        }
+        values
+    }
+}
+
+fn average(values: impl Iterator<Item = f32>) -> Option<f32> {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+    (count > 0).then(|| sum / count as f32)
+}
+
+/// Color of `share` of the way along the scale, from cool blue at 0 to hot red at 1
+fn heat_color(share: f32) -> Color {
+    let share = share.clamp(0.0, 1.0);
+    Color::linear_rgb(share, 0.3 * (1.0 - (2.0 * share - 1.0).abs()) + 0.2, 1.0 - share)
+}
+
+/// Tiles the selected overlay has no value for are dimmed
+const NO_DATA: Color = Color::linear_rgb(0.25, 0.25, 0.25);
+/// Metrics are recomputed this often while an overlay is shown
+const REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+/// Number of color steps in the legend
+const LEGEND_STEPS: usize = 10;
+
+const BUTTON_NORMAL: Color = Color::linear_rgb(0.15, 0.15, 0.15);
+const BUTTON_HOVERED: Color = Color::linear_rgb(0.25, 0.25, 0.25);
+const BUTTON_SELECTED: Color = Color::linear_rgb(0.2, 0.4, 0.6);
+
+/// The overlay tinting the map, if any, and the range of the values it last showed
+#[derive(Resource, Default)]
+pub(crate) struct Overlay {
+    pub mode: Option<OverlayMode>,
+    range: (f32, f32),
+}
+
+#[derive(Component)]
+struct OverlayBar;
+
+#[derive(Component)]
+struct OverlayButton(OverlayMode);
+
+#[derive(Component)]
+struct Legend;
+
+#[derive(Component)]
+struct LegendText;
+
+fn spawn_overlay_bar(mut commands: Commands) {
+    let text_font = TextFont {
+        font_size: 14.0,
+        ..default()
+    };
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(10.0),
+                width: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(4.0),
+                ..default()
+            },
+            PickingBehavior::IGNORE,
+            OverlayBar,
+        ))
+        .with_children(|bar| {
+            bar.spawn(Node {
+                flex_direction: FlexDirection::Row,
+                column_gap: Val::Px(4.0),
+                ..default()
+            })
+            .with_children(|row| {
+                for mode in OverlayMode::ALL {
+                    row.spawn((
+                        Button,
+                        Node {
+                            padding: UiRect::axes(Val::Px(6.0), Val::Px(2.0)),
+                            ..default()
+                        },
+                        BackgroundColor(BUTTON_NORMAL),
+                        OverlayButton(mode),
+                    ))
+                    .with_child((Text::new(mode.label()), text_font.clone()));
+                }
+            });
+
+            bar.spawn((
+                Node {
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    padding: UiRect::all(Val::Px(4.0)),
+                    display: Display::None,
+                    ..default()
+                },
+                BackgroundColor(Color::linear_rgba(0.0, 0.0, 0.0, 0.6)),
+                Legend,
+            ))
+            .with_children(|legend| {
+                legend.spawn((Text::default(), text_font.clone(), LegendText));
+                legend
+                    .spawn(Node {
+                        flex_direction: FlexDirection::Row,
+                        ..default()
+                    })
+                    .with_children(|scale| {
+                        for step in 0..LEGEND_STEPS {
+                            let share = step as f32 / (LEGEND_STEPS - 1) as f32;
+                            scale.spawn((
+                                Node {
+                                    width: Val::Px(20.0),
+                                    height: Val::Px(10.0),
+                                    ..default()
+                                },
+                                BackgroundColor(heat_color(share)),
+                            ));
+                        }
+                    });
+            });
+        });
+}
+
+fn despawn_overlay_bar(
+    mut commands: Commands,
+    bar_query: Query<Entity, With<OverlayBar>>,
+    mut overlay: ResMut<Overlay>,
+) {
+    for entity in bar_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    overlay.mode = None;
+}
+
+/// Picking the shown overlay again turns it off
+fn pick_overlay(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut button_query: Query<(Ref<Interaction>, &OverlayButton, &mut BackgroundColor)>,
+    mut overlay: ResMut<Overlay>,
+) {
+    let toggle = |overlay: &mut Overlay, mode| {
+        overlay.mode = if overlay.mode == Some(mode) { None } else { Some(mode) };
+    };
+    for mode in OverlayMode::ALL {
+        if keyboard_input.just_pressed(mode.hotkey()) {
+            toggle(&mut overlay, mode);
+        }
+    }
+    for (interaction, button, mut color) in button_query.iter_mut() {
+        if interaction.is_changed() && *interaction == Interaction::Pressed {
+            toggle(&mut overlay, button.0);
+        }
+        *color = if overlay.mode == Some(button.0) {
+            BUTTON_SELECTED.into()
+        } else if *interaction == Interaction::Hovered {
+            BUTTON_HOVERED.into()
+        } else {
+            BUTTON_NORMAL.into()
+        };
+    }
+}
+
+fn tint_tiles(
+    time: Res<Time>,
+    mut overlay: ResMut<Overlay>,
+    mut refresh: Local<Timer>,
+    geometry: Option<Res<MapGeometry>>,
+    pop_query: Query<&Pop>,
+    house_query: Query<&House>,
+    workplace_query: Query<&Workplace>,
+    mut tile_query: Query<(&TilePos, &mut TileColor)>,
+) {
+    refresh.tick(time.delta());
+    if !overlay.is_changed() && !refresh.finished() {
+        return;
+    }
+    *refresh = Timer::new(REFRESH_INTERVAL, TimerMode::Once);
+
+    let Some(mode) = overlay.mode else {
+        for (_, mut color) in tile_query.iter_mut() {
+            // Only touch tiles that are tinted, so untouched ones aren't marked as changed
+            if color.0 != Color::WHITE {
+                color.0 = Color::WHITE;
+            }
+        }
+        return;
+    };
+    let Some(geometry) = geometry else {
+        return;
+    };
+
+    let values = mode.tile_values(&geometry, &pop_query, &house_query, &workplace_query);
+    let (min, max) = mode.fixed_range().unwrap_or_else(|| {
+        values.values().fold((0.0, 0.0), |(min, max): (f32, f32), value| {
+            (min.min(*value), max.max(*value))
+        })
+    });
+    // Avoid flagging the overlay as changed, which would recompute it every frame
+    if overlay.range != (min, max) {
+        overlay.range = (min, max);
+    }
+
+    for (tile, mut color) in tile_query.iter_mut() {
+        let tint = match values.get(tile) {
+            Some(value) if max > min => heat_color((value - min) / (max - min)),
+            Some(_) => heat_color(0.0),
+            None => NO_DATA,
+        };
+        if color.0 != tint {
+            color.0 = tint;
+        }
+    }
+}
+
+fn update_legend(
+    overlay: Res<Overlay>,
+    mut legend_query: Query<&mut Node, With<Legend>>,
+    mut text_query: Query<&mut Text, With<LegendText>>,
+) {
+    if !overlay.is_changed() {
+        return;
+    }
+    for mut node in legend_query.iter_mut() {
+        node.display = if overlay.mode.is_some() { Display::Flex } else { Display::None };
+    }
+    let Some(mode) = overlay.mode else {
+        return;
+    };
+    let (min, max) = overlay.range;
+    for mut text in text_query.iter_mut() {
+        **text = format!("{}\n{} to {}", mode.description(), mode.format(min), mode.format(max));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::*;
+    use bevy_ecs_tilemap::prelude::*;
+
+    use crate::constants::DEFAULT_MAP_SIZE;
+    use crate::coords::MapGeometry;
+    use crate::overlay::OverlayMode;
+    use crate::tilemap::{House, Pop, Workplace};
+
+    #[test]
+    fn test_overlays_measure_houses_and_tiles() {
+        let mut world = World::new();
+        let geometry = MapGeometry::new(DEFAULT_MAP_SIZE);
+        let tile = TilePos { x: 3, y: 4 };
+        let residents = [(100, 1000), (300, 3000)]
+            .map(|(money, hunger)| {
+                let position = geometry.tile_center(tile);
+                world.spawn(Pop { money, hunger, position, ..default() }).id()
+            })
+            .to_vec();
+        world.spawn(House {
+            capacity: 4,
+            residents,
+            position: TilePos { x: 0, y: 0 },
+            rent: 0.0,
+            rent_collected: 0.0,
+        });
+
+        let values_of = |world: &mut World, mode: OverlayMode| {
+            world
+                .run_system_once(
+                    move |pops: Query<&Pop>, houses: Query<&House>, workplaces: Query<&Workplace>| {
+                        mode.tile_values(&geometry, &pops, &houses, &workplaces)
+                    },
+                )
+                .unwrap()
+        };
+
+        let house_tile = TilePos { x: 0, y: 0 };
+        assert_eq!(values_of(&mut world, OverlayMode::Occupancy)[&house_tile], 0.5);
+        assert_eq!(values_of(&mut world, OverlayMode::Wealth)[&house_tile], 200.0);
+        // Nobody has a job, so there is no commute to show
+        assert!(values_of(&mut world, OverlayMode::Commute).is_empty());
+        // Both pops stand on the same tile, away from the house
+        assert_eq!(values_of(&mut world, OverlayMode::Hunger)[&tile], 2000.0);
+        assert_eq!(values_of(&mut world, OverlayMode::Density)[&tile], 2.0);
+        assert!(!values_of(&mut world, OverlayMode::Density).contains_key(&house_tile));
+    }
+}
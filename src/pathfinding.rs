@@ -0,0 +1,461 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::hash::Hash;
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::coords::MapGeometry;
+use crate::tilemap::{Building, Pop, Road};
+
+/// Cost of walking onto a road tile
+pub(crate) const ROAD_COST: u32 = 1;
+/// Cost of walking onto an empty tile
+pub(crate) const GROUND_COST: u32 = 3;
+/// Cost of stepping into a building, only allowed at the start or end of a path
+const ENTRANCE_COST: u32 = GROUND_COST;
+/// Paths kept in the cache, the least recently used half is dropped once it is full
+const MAX_CACHED_PATHS: usize = 20_000;
+/// Distance fields kept in the cache, each holds a cost for every tile of the map
+const MAX_CACHED_DISTANCE_FIELDS: usize = 32;
+
+/// A walkable route between two tiles
+pub struct Path {
+    /// Tiles to walk through, excluding the start and including the goal.
+    /// Empty when the start is the goal.
+    pub tiles: Vec<TilePos>,
+    /// Summed traversal cost of `tiles`
+    pub cost: u32,
+}
+
+/// The cost of the cheapest path from one tile to every tile of the map, see [`NavGrid::distances_from`]
+pub struct DistanceField {
+    size: TilemapSize,
+    /// `u32::MAX` for tiles that can't be reached
+    costs: Vec<u32>,
+}
+
+impl DistanceField {
+    /// The cost of the cheapest path to `tile`, or `None` if it can't be reached
+    pub fn cost_to(&self, tile: TilePos) -> Option<u32> {
+        if !tile.within_map_bounds(&self.size) {
+            return None;
+        }
+        let cost = self.costs[(tile.y * self.size.x + tile.x) as usize];
+        (cost != u32::MAX).then_some(cost)
+    }
+}
+
+/// Walkability and traversal cost of every tile, plus caches of the paths and distance fields found
+/// on it. Rebuilt (and the caches dropped) whenever buildings or roads change.
+#[derive(Resource)]
+pub struct NavGrid {
+    costs: TileCosts,
+    paths: LruCache<((u32, u32), (u32, u32)), Option<Path>>,
+    distance_fields: LruCache<(u32, u32), DistanceField>,
+    scratch: SearchScratch,
+}
+
+impl Default for NavGrid {
+    fn default() -> Self {
+        NavGrid::new(TilemapSize::default())
+    }
+}
+
+impl NavGrid {
+    pub fn new(size: TilemapSize) -> Self {
+        Self {
+            costs: TileCosts {
+                size,
+                costs: vec![Some(GROUND_COST); (size.x * size.y) as usize],
+            },
+            paths: LruCache::new(MAX_CACHED_PATHS),
+            distance_fields: LruCache::new(MAX_CACHED_DISTANCE_FIELDS),
+            scratch: SearchScratch::default(),
+        }
+    }
+
+    pub fn set_cost(&mut self, tile: TilePos, cost: Option<u32>) {
+        if !tile.within_map_bounds(&self.costs.size) {
+            return;
+        }
+        let index = self.costs.index(tile);
+        self.costs.costs[index] = cost;
+        self.paths.clear();
+        self.distance_fields.clear();
+    }
+
+    /// The cheapest path from `from` to `to`, or `None` if `to` can't be reached.
+    /// Buildings block the way, except for the ones the path starts or ends in.
+    pub fn find_path(&mut self, from: TilePos, to: TilePos) -> Option<&Path> {
+        let NavGrid { costs, paths, scratch, .. } = self;
+        paths
+            .get_or_insert_with(((from.x, from.y), (to.x, to.y)), || costs.search(scratch, from, to))
+            .as_ref()
+    }
+
+    /// The cost of the cheapest path from `from` to every tile, with the same rules as [`Self::find_path`].
+    /// One search answers for every destination, so it is the cheaper choice for comparing many of them.
+    pub fn distances_from(&mut self, from: TilePos) -> &DistanceField {
+        let NavGrid { costs, distance_fields, .. } = self;
+        distance_fields.get_or_insert_with((from.x, from.y), || costs.flood(from))
+    }
+}
+
+/// Results keyed by the tiles they were computed for. Once `capacity` entries are kept, the least
+/// recently used half of them is dropped to make room.
+struct LruCache<K, V> {
+    entries: HashMap<K, (u64, V)>,
+    /// Counts the lookups, entries remember the one that last used them
+    clock: u64,
+    capacity: usize,
+}
+
+impl<K: Copy + Eq + Hash, V> LruCache<K, V> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            clock: 0,
+            capacity,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    fn get_or_insert_with(&mut self, key: K, insert: impl FnOnce() -> V) -> &V {
+        self.clock += 1;
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.capacity {
+            self.evict();
+        }
+        let clock = self.clock;
+        let (last_used, value) = self.entries.entry(key).or_insert_with(|| (clock, insert()));
+        *last_used = clock;
+        value
+    }
+
+    /// Drops every entry used no later than the median one
+    fn evict(&mut self) {
+        let mut last_used: Vec<u64> = self.entries.values().map(|(last_used, _)| *last_used).collect();
+        if last_used.is_empty() {
+            return;
+        }
+        let middle = (last_used.len() - 1) / 2;
+        let median = *last_used.select_nth_unstable(middle).1;
+        self.entries.retain(|_, (last_used, _)| *last_used > median);
+    }
+}
+
+/// Buffers A* reuses from one search to the next, so large maps aren't allocated for every path
+#[derive(Default)]
+struct SearchScratch {
+    best_cost: Vec<u32>,
+    came_from: Vec<Option<TilePos>>,
+    /// Tiles the last search visited, the only ones that need resetting before the next
+    visited: Vec<usize>,
+    open: BinaryHeap<Reverse<(u32, u32, u32, u32)>>,
+}
+
+impl SearchScratch {
+    fn reset(&mut self, tile_count: usize) {
+        if self.best_cost.len() == tile_count {
+            for index in self.visited.drain(..) {
+                self.best_cost[index] = u32::MAX;
+                self.came_from[index] = None;
+            }
+        } else {
+            self.best_cost = vec![u32::MAX; tile_count];
+            self.came_from = vec![None; tile_count];
+            self.visited.clear();
+        }
+        self.open.clear();
+    }
+
+    fn visit(&mut self, index: usize, cost: u32, previous: Option<TilePos>) {
+        self.best_cost[index] = cost;
+        self.came_from[index] = previous;
+        self.visited.push(index);
+    }
+}
+
+#[derive(Default)]
+struct TileCosts {
+    size: TilemapSize,
+    /// Cost of entering each tile, `None` for buildings
+    costs: Vec<Option<u32>>,
+}
+
+impl TileCosts {
+    fn index(&self, tile: TilePos) -> usize {
+        (tile.y * self.size.x + tile.x) as usize
+    }
+
+    fn entry_cost(&self, tile: TilePos, from: TilePos, to: TilePos) -> Option<u32> {
+        match self.costs[self.index(tile)] {
+            None if tile == from || tile == to => Some(ENTRANCE_COST),
+            cost => cost,
+        }
+    }
+
+    fn neighbors(&self, tile: TilePos) -> impl Iterator<Item = TilePos> {
+        let size = self.size;
+        [(1, 0), (-1, 0), (0, 1), (0, -1)]
+            .into_iter()
+            .filter_map(move |(dx, dy): (i64, i64)| {
+                let x = tile.x as i64 + dx;
+                let y = tile.y as i64 + dy;
+                let in_bounds = x >= 0 && y >= 0 && x < size.x as i64 && y < size.y as i64;
+                in_bounds.then_some(TilePos {
+                    x: x as u32,
+                    y: y as u32,
+                })
+            })
+    }
+
+    /// A* over the 4-connected grid. Ties are broken by tile position so results are deterministic.
+    fn search(&self, scratch: &mut SearchScratch, from: TilePos, to: TilePos) -> Option<Path> {
+        if from == to {
+            return Some(Path { tiles: Vec::new(), cost: 0 });
+        }
+        let heuristic =
+            |tile: TilePos| (tile.x.abs_diff(to.x) + tile.y.abs_diff(to.y)) * ROAD_COST;
+
+        scratch.reset(self.costs.len());
+        scratch.visit(self.index(from), 0, None);
+        scratch.open.push(Reverse((heuristic(from), 0, from.x, from.y)));
+
+        while let Some(Reverse((_, cost, x, y))) = scratch.open.pop() {
+            let tile = TilePos { x, y };
+            if tile == to {
+                let mut tiles = vec![tile];
+                let mut current = tile;
+                while let Some(previous) = scratch.came_from[self.index(current)] {
+                    if previous == from {
+                        break;
+                    }
+                    tiles.push(previous);
+                    current = previous;
+                }
+                tiles.reverse();
+                return Some(Path { tiles, cost });
+            }
+            if cost > scratch.best_cost[self.index(tile)] {
+                continue;
+            }
+            for neighbor in self.neighbors(tile) {
+                let Some(step) = self.entry_cost(neighbor, from, to) else {
+                    continue;
+                };
+                let next_cost = cost + step;
+                let index = self.index(neighbor);
+                if next_cost < scratch.best_cost[index] {
+                    scratch.visit(index, next_cost, Some(tile));
+                    scratch.open.push(Reverse((
+                        next_cost + heuristic(neighbor),
+                        next_cost,
+                        neighbor.x,
+                        neighbor.y,
+                    )));
+                }
+            }
+        }
+        None
+    }
+
+    /// Dijkstra from `from` over the whole grid. Buildings can be entered but not walked through,
+    /// except for the one the search starts in.
+    fn flood(&self, from: TilePos) -> DistanceField {
+        let mut best_cost = vec![u32::MAX; self.costs.len()];
+        if !from.within_map_bounds(&self.size) {
+            return DistanceField { size: self.size, costs: best_cost };
+        }
+        let mut open = BinaryHeap::new();
+        best_cost[self.index(from)] = 0;
+        open.push(Reverse((0, from.x, from.y)));
+
+        while let Some(Reverse((cost, x, y))) = open.pop() {
+            let tile = TilePos { x, y };
+            if cost > best_cost[self.index(tile)] {
+                continue;
+            }
+            // Pops walk into buildings to stop there, never to pass through
+            if tile != from && self.costs[self.index(tile)].is_none() {
+                continue;
+            }
+            for neighbor in self.neighbors(tile) {
+                let step = self.costs[self.index(neighbor)].unwrap_or(ENTRANCE_COST);
+                let next_cost = cost + step;
+                let index = self.index(neighbor);
+                if next_cost < best_cost[index] {
+                    best_cost[index] = next_cost;
+                    open.push(Reverse((next_cost, neighbor.x, neighbor.y)));
+                }
+            }
+        }
+        DistanceField { size: self.size, costs: best_cost }
+    }
+}
+
+/// Rebuilds the [`NavGrid`] when the map was replaced or buildings and roads were added or removed
+pub(crate) fn update_nav_grid(
+    mut nav_grid: ResMut<NavGrid>,
+    geometry: Res<MapGeometry>,
+    building_query: Query<&TilePos, With<Building>>,
+    road_query: Query<&TilePos, With<Road>>,
+    added_query: Query<(), Or<(Added<Building>, Added<Road>)>>,
+    mut removed_buildings: RemovedComponents<Building>,
+    mut removed_roads: RemovedComponents<Road>,
+) {
+    let removed = removed_buildings.read().count() + removed_roads.read().count() > 0;
+    if nav_grid.costs.size == geometry.size && added_query.is_empty() && !removed {
+        return;
+    }
+
+    let mut grid = NavGrid::new(geometry.size);
+    grid.scratch = std::mem::take(&mut nav_grid.scratch);
+    for tile in road_query.iter() {
+        grid.set_cost(*tile, Some(ROAD_COST));
+    }
+    for tile in building_query.iter() {
+        grid.set_cost(*tile, None);
+    }
+    *nav_grid = grid;
+}
+
+/// Plans a path for every pop whose destination changed since its last plan.
+/// Pops give up on destinations they can't reach.
+pub(crate) fn plan_paths(
+    mut pop_query: Query<&mut Pop>,
+    mut nav_grid: ResMut<NavGrid>,
+    geometry: Res<MapGeometry>,
+) {
+    for mut pop in pop_query.iter_mut() {
+        if pop.path_goal == pop.destination {
+            continue;
+        }
+        let pop = &mut *pop;
+        pop.path_goal = pop.destination;
+        pop.waypoints.clear();
+
+        let Some(destination) = pop.destination else {
+            continue;
+        };
+        let (Some(from), Some(to)) = (geometry.tile_at(pop.position), geometry.tile_at(destination))
+        else {
+            continue;
+        };
+        match nav_grid.find_path(from, to) {
+            Some(path) => {
+                // `move_pops` walks the waypoints from the back and ends on the exact destination,
+                // a destination on the pop's own tile needs none
+                if let Some((_, before_goal)) = path.tiles.split_last() {
+                    pop.waypoints.extend(before_goal.iter().rev().map(|tile| geometry.tile_center(*tile)));
+                }
+            }
+            None => {
+                pop.destination = None;
+                pop.path_goal = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs_tilemap::prelude::*;
+
+    use crate::pathfinding::{LruCache, NavGrid, GROUND_COST, ROAD_COST};
+
+    #[test]
+    fn test_paths_avoid_buildings_and_prefer_roads() {
+        let mut grid = NavGrid::new(TilemapSize { x: 5, y: 5 });
+        // A wall of buildings at x = 2 with a single gap at the top
+        for y in 0..4 {
+            grid.set_cost(TilePos { x: 2, y }, None);
+        }
+        // The start is a building too, pops leave it through its entrance
+        grid.set_cost(TilePos { x: 0, y: 0 }, None);
+
+        let path = grid
+            .find_path(TilePos { x: 0, y: 0 }, TilePos { x: 4, y: 0 })
+            .expect("The gap should be walkable");
+        assert_eq!(path.tiles.last(), Some(&TilePos { x: 4, y: 0 }));
+        assert!(path.tiles.contains(&TilePos { x: 2, y: 4 }));
+        assert!(path.tiles.iter().all(|tile| tile.x != 2 || tile.y == 4));
+        assert_eq!(path.cost, path.tiles.len() as u32 * GROUND_COST);
+
+        grid.set_cost(TilePos { x: 2, y: 4 }, None);
+        assert!(grid.find_path(TilePos { x: 0, y: 0 }, TilePos { x: 4, y: 0 }).is_none());
+
+        let mut grid = NavGrid::new(TilemapSize { x: 4, y: 2 });
+        for x in 0..4 {
+            grid.set_cost(TilePos { x, y: 1 }, Some(ROAD_COST));
+        }
+        let path = grid
+            .find_path(TilePos { x: 0, y: 0 }, TilePos { x: 3, y: 0 })
+            .unwrap();
+        // Detouring over the road (1 + 1 + 1 + 1 + 3) beats walking straight over grass (3 + 3 + 3)
+        assert_eq!(path.cost, 4 * ROAD_COST + GROUND_COST);
+    }
+
+    #[test]
+    fn test_path_to_the_start_is_empty_and_searches_dont_leak_into_each_other() {
+        let mut grid = NavGrid::new(TilemapSize { x: 6, y: 6 });
+        let tile = TilePos { x: 3, y: 3 };
+        let path = grid.find_path(tile, tile).unwrap();
+        assert!(path.tiles.is_empty());
+        assert_eq!(path.cost, 0);
+
+        // The scratch buffers are reused, later searches find what a fresh grid finds
+        let searches = [
+            (TilePos { x: 0, y: 0 }, TilePos { x: 5, y: 5 }),
+            (TilePos { x: 5, y: 0 }, TilePos { x: 0, y: 4 }),
+            (TilePos { x: 2, y: 5 }, TilePos { x: 2, y: 1 }),
+        ];
+        for (from, to) in searches {
+            let reused = grid.find_path(from, to).map(|path| (path.tiles.clone(), path.cost));
+            let fresh = NavGrid::new(TilemapSize { x: 6, y: 6 })
+                .find_path(from, to)
+                .map(|path| (path.tiles.clone(), path.cost));
+            assert_eq!(reused, fresh);
+        }
+    }
+    #[test]
+    fn test_distance_fields_match_paths() {
+        let mut grid = NavGrid::new(TilemapSize { x: 6, y: 5 });
+        for y in 0..4 {
+            grid.set_cost(TilePos { x: 3, y }, None);
+        }
+        for x in 0..6 {
+            grid.set_cost(TilePos { x, y: 4 }, Some(ROAD_COST));
+        }
+        let from = TilePos { x: 1, y: 1 };
+        grid.set_cost(from, None);
+
+        for x in 0..6 {
+            for y in 0..5 {
+                let to = TilePos { x, y };
+                let path_cost = grid.find_path(from, to).map(|path| path.cost);
+                assert_eq!(grid.distances_from(from).cost_to(to), path_cost, "to {to:?}");
+            }
+        }
+        assert_eq!(grid.distances_from(from).cost_to(TilePos { x: 6, y: 0 }), None);
+    }
+
+    #[test]
+    fn test_full_caches_drop_the_least_recently_used_entries() {
+        let mut cache = LruCache::new(4);
+        for key in 0..4 {
+            cache.get_or_insert_with(key, || key);
+        }
+        // Using the oldest entries again keeps them around when the cache overflows
+        cache.get_or_insert_with(0, || unreachable!());
+        cache.get_or_insert_with(1, || unreachable!());
+        cache.get_or_insert_with(4, || 4);
+
+        let mut kept: Vec<_> = cache.entries.keys().copied().collect();
+        kept.sort_unstable();
+        assert_eq!(kept, [0, 1, 4]);
+    }
+}
@@ -0,0 +1,255 @@
+use bevy::prelude::*;
+
+use crate::save::{LoadCity, SaveCity};
+use crate::{GameState, PlayState};
+
+pub struct PausePlugin;
+
+/// This plugin opens the pause menu with Escape while playing. The simulation and the input on the
+/// map stop while it is open. From there the player resumes, changes the settings, saves or loads
+/// the city or quits to the main menu.
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameSettings>()
+            .add_systems(
+                Update,
+                toggle_pause_menu.run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnEnter(PlayState::Paused), spawn_pause_menu)
+            .add_systems(
+                Update,
+                (click_pause_buttons, click_setting_toggles)
+                    .chain()
+                    .run_if(in_state(PlayState::Paused)),
+            )
+            .add_systems(OnExit(PlayState::Paused), despawn_pause_menu);
+    }
+}
+
+/// Preferences the player changes in the pause menu
+#[derive(Resource)]
+pub(crate) struct GameSettings {
+    /// Play a sound when building or bulldozing
+    pub sound: bool,
+    /// Scroll the camera when the cursor touches the window edge
+    pub edge_scrolling: bool,
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        Self {
+            sound: true,
+            edge_scrolling: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Setting {
+    Sound,
+    EdgeScrolling,
+}
+
+impl Setting {
+    const ALL: [Setting; 2] = [Setting::Sound, Setting::EdgeScrolling];
+
+    fn label(self, settings: &GameSettings) -> String {
+        let (name, on) = match self {
+            Setting::Sound => ("Sound", settings.sound),
+            Setting::EdgeScrolling => ("Edge scrolling", settings.edge_scrolling),
+        };
+        format!("{name}: {}", if on { "on" } else { "off" })
+    }
+
+    fn toggle(self, settings: &mut GameSettings) {
+        match self {
+            Setting::Sound => settings.sound = !settings.sound,
+            Setting::EdgeScrolling => settings.edge_scrolling = !settings.edge_scrolling,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PauseAction {
+    Resume,
+    Settings,
+    Save,
+    Load,
+    QuitToMenu,
+}
+
+impl PauseAction {
+    const ALL: [PauseAction; 5] = [
+        PauseAction::Resume,
+        PauseAction::Settings,
+        PauseAction::Save,
+        PauseAction::Load,
+        PauseAction::QuitToMenu,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            PauseAction::Resume => "Resume",
+            PauseAction::Settings => "Settings",
+            PauseAction::Save => "Save city",
+            PauseAction::Load => "Load city",
+            PauseAction::QuitToMenu => "Quit to menu",
+        }
+    }
+}
+
+#[derive(Component)]
+struct PauseMenu;
+
+#[derive(Component)]
+struct PauseButton(PauseAction);
+
+/// Holds the setting toggles, hidden until the settings button is clicked
+#[derive(Component)]
+struct SettingsPanel;
+
+#[derive(Component)]
+struct SettingToggle(Setting);
+
+const BUTTON_NORMAL: Color = Color::linear_rgb(0.15, 0.15, 0.15);
+const BUTTON_HOVERED: Color = Color::linear_rgb(0.25, 0.25, 0.25);
+
+fn toggle_pause_menu(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    play_state: Res<State<PlayState>>,
+    mut next_play_state: ResMut<NextState<PlayState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        next_play_state.set(match play_state.get() {
+            PlayState::Running => PlayState::Paused,
+            PlayState::Paused => PlayState::Running,
+        });
+    }
+}
+
+fn spawn_pause_menu(mut commands: Commands, settings: Res<GameSettings>) {
+    let text_font = TextFont {
+        font_size: 20.0,
+        ..default()
+    };
+    let button_node = Node {
+        width: Val::Px(200.0),
+        height: Val::Px(40.0),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(10.0),
+                ..default()
+            },
+            BackgroundColor(Color::linear_rgba(0.0, 0.0, 0.0, 0.7)),
+            // Above the scenario outcome, the pause menu is opened on top of it
+            GlobalZIndex(20),
+            PauseMenu,
+        ))
+        .with_children(|menu| {
+            menu.spawn((
+                Text::new("Paused"),
+                TextFont {
+                    font_size: 48.0,
+                    ..default()
+                },
+            ));
+            for action in PauseAction::ALL {
+                menu.spawn((Button, button_node.clone(), BackgroundColor(BUTTON_NORMAL), PauseButton(action)))
+                    .with_child((Text::new(action.label()), text_font.clone()));
+            }
+            menu.spawn((
+                Node {
+                    display: Display::None,
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(10.0),
+                    ..default()
+                },
+                SettingsPanel,
+            ))
+            .with_children(|panel| {
+                for setting in Setting::ALL {
+                    panel
+                        .spawn((Button, button_node.clone(), BackgroundColor(BUTTON_NORMAL), SettingToggle(setting)))
+                        .with_child((Text::new(setting.label(&settings)), text_font.clone()));
+                }
+            });
+        });
+}
+
+fn despawn_pause_menu(mut commands: Commands, menu_query: Query<Entity, With<PauseMenu>>) {
+    for entity in menu_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn click_pause_buttons(
+    mut button_query: Query<(&Interaction, &PauseButton, &mut BackgroundColor), Changed<Interaction>>,
+    mut panel_query: Query<&mut Node, With<SettingsPanel>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut next_play_state: ResMut<NextState<PlayState>>,
+    mut save_events: EventWriter<SaveCity>,
+    mut load_events: EventWriter<LoadCity>,
+) {
+    for (interaction, button, mut color) in button_query.iter_mut() {
+        if *interaction == Interaction::Pressed {
+            match button.0 {
+                PauseAction::Resume => next_play_state.set(PlayState::Running),
+                PauseAction::Settings => {
+                    for mut node in panel_query.iter_mut() {
+                        node.display = match node.display {
+                            Display::None => Display::Flex,
+                            _ => Display::None,
+                        };
+                    }
+                }
+                PauseAction::Save => {
+                    save_events.send(SaveCity);
+                }
+                PauseAction::Load => {
+                    load_events.send(LoadCity);
+                    next_play_state.set(PlayState::Running);
+                }
+                PauseAction::QuitToMenu => next_state.set(GameState::Menu),
+            }
+        }
+        *color = if *interaction == Interaction::None {
+            BUTTON_NORMAL.into()
+        } else {
+            BUTTON_HOVERED.into()
+        };
+    }
+}
+
+fn click_setting_toggles(
+    mut button_query: Query<
+        (&Interaction, &SettingToggle, &Children, &mut BackgroundColor),
+        Changed<Interaction>,
+    >,
+    mut text_query: Query<&mut Text>,
+    mut settings: ResMut<GameSettings>,
+) {
+    for (interaction, toggle, children, mut color) in button_query.iter_mut() {
+        if *interaction == Interaction::Pressed {
+            toggle.0.toggle(&mut settings);
+            if let Some(mut text) = children.first().and_then(|child| text_query.get_mut(*child).ok()) {
+                **text = toggle.0.label(&settings);
+            }
+        }
+        *color = if *interaction == Interaction::None {
+            BUTTON_NORMAL.into()
+        } else {
+            BUTTON_HOVERED.into()
+        };
+    }
+}
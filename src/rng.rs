@@ -0,0 +1,53 @@
+use bevy::prelude::*;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+/// Seed a city is generated and simulated from.
+/// Insert it before the simulation starts (e.g. from `--seed`) to reproduce a run,
+/// otherwise a random one is picked.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SimSeed(pub u64);
+
+impl Default for SimSeed {
+    fn default() -> Self {
+        SimSeed(rand::random())
+    }
+}
+
+impl SimSeed {
+    /// Reads `--seed <number>` from the command line
+    pub fn from_args() -> Option<Self> {
+        let mut args = std::env::args();
+        while let Some(arg) = args.next() {
+            if arg == "--seed" {
+                let seed = args.next().expect("--seed requires a value");
+                return Some(SimSeed(seed.parse().expect("SEED must be a number")));
+            }
+        }
+        None
+    }
+}
+
+/// The only source of randomness in the simulation, reset from [`SimSeed`] whenever a city is generated.
+/// Systems draw from it in a fixed system order and iterate pops sorted by entity,
+/// so a run can be reproduced from `(seed, ticks)`.
+#[derive(Resource, Deref, DerefMut)]
+pub struct SimRng(ChaCha8Rng);
+
+impl SimRng {
+    pub fn new(seed: SimSeed) -> Self {
+        SimRng(ChaCha8Rng::seed_from_u64(seed.0))
+    }
+
+    /// Restores the generator of `seed` after `word_pos` words were drawn from it
+    pub fn resume(seed: SimSeed, word_pos: u64) -> Self {
+        let mut rng = SimRng::new(seed);
+        rng.0.set_word_pos(word_pos as u128);
+        rng
+    }
+
+    /// Number of words drawn since the generator was seeded
+    pub fn word_pos(&self) -> u64 {
+        self.0.get_word_pos() as u64
+    }
+}
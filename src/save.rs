@@ -0,0 +1,846 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::constants::{
+    ADULT_AGE, BASE_RENT, BASE_WAGE, FIRM_STARTING_CAPITAL, MAX_MAP_SIDE, RESTAURANT_STOCK_TARGET,
+};
+use crate::coords::WorldPos;
+use crate::firms::{Product, Shipment, Shipments};
+use crate::history::StatsHistory;
+use crate::lifecycle::Demographics;
+use crate::rng::{SimRng, SimSeed};
+use crate::scenario::ActiveScenario;
+use crate::tilemap::{
+    insert_tilemap, spawn_empty_tile, spawn_farm, spawn_house, spawn_restaurant, spawn_road, spawn_tilemap,
+    spawn_workplace, CityEntities, DirtyChunks, GameClock, House, HousingMarket, Job, JobMarket, Pop,
+    PopState, Restaurant, Road, Sex, Tilemap, Workplace,
+};
+use crate::treasury::{CityTreasury, TaxPolicy};
+use crate::GameState;
+
+const SAVE_PATH: &str = "saves/city.ron";
+/// Bump whenever the shape of [`SaveFile`] changes.
+/// Saves from a newer version are refused. Older ones are not migrated, they keep loading only
+/// because every field added since version 1 is `#[serde(default)]`, so new fields must be too.
+const SAVE_VERSION: u32 = 12;
+
+pub struct SaveLoadPlugin;
+
+/// This plugin writes the running city to disk and restores it again.
+/// Saving is only possible while playing. Loading from the menu starts the game with the saved city
+/// instead of generating a new one.
+impl Plugin for SaveLoadPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SaveCity>()
+            .add_event::<LoadCity>()
+            .add_systems(
+                Update,
+                (
+                    handle_save_hotkeys.run_if(in_state(GameState::Playing)),
+                    save_city.run_if(in_state(GameState::Playing)),
+                    load_city,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                OnEnter(GameState::Playing),
+                spawn_pending_city
+                    .after(spawn_tilemap)
+                    .run_if(resource_exists::<PendingLoad>),
+            );
+    }
+}
+
+/// Writes the running city to the save file
+#[derive(Event)]
+pub(crate) struct SaveCity;
+
+/// Replaces the running city with the one in the save file, or starts the game with it
+#[derive(Event)]
+pub(crate) struct LoadCity;
+
+/// A loaded save waiting for `GameState::Playing` to be entered
+#[derive(Resource)]
+pub(crate) struct PendingLoad(SaveFile);
+
+#[derive(Debug)]
+enum SaveError {
+    Io(std::io::Error),
+    Serialize(ron::Error),
+    Deserialize(ron::error::SpannedError),
+    UnsupportedVersion(u32),
+    Invalid(InvalidSave),
+}
+
+/// Contents of a save that would break the game if it were loaded
+#[derive(Debug)]
+enum InvalidSave {
+    MapSize((u32, u32)),
+    OutOfBounds((u32, u32)),
+    SharedTile((u32, u32)),
+    EmptyClock,
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(error) => write!(f, "{error}"),
+            SaveError::Serialize(error) => write!(f, "{error}"),
+            SaveError::Deserialize(error) => write!(f, "{error}"),
+            SaveError::UnsupportedVersion(version) => write!(
+                f,
+                "save version {version} is newer than the supported version {SAVE_VERSION}"
+            ),
+            SaveError::Invalid(InvalidSave::MapSize((x, y))) => write!(
+                f,
+                "the map is {x}x{y}, but its sides must be 1 to {MAX_MAP_SIDE} tiles"
+            ),
+            SaveError::Invalid(InvalidSave::OutOfBounds((x, y))) => {
+                write!(f, "the tile ({x}, {y}) lies outside the map")
+            }
+            SaveError::Invalid(InvalidSave::SharedTile((x, y))) => {
+                write!(f, "more than one building or road on the tile ({x}, {y})")
+            }
+            SaveError::Invalid(InvalidSave::EmptyClock) => {
+                write!(f, "the clock needs at least one tick per hour and one hour per day")
+            }
+        }
+    }
+}
+
+/// On-disk format of a city.
+/// Entities are stored as indices into the `pops`, `houses` and `workplaces` lists
+/// and get remapped to freshly spawned entities on load.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct SaveFile {
+    version: u32,
+    seed: u64,
+    rng_word_pos: u64,
+    clock: SavedClock,
+    map_size: (u32, u32),
+    pops: Vec<SavedPop>,
+    houses: Vec<SavedHouse>,
+    workplaces: Vec<SavedWorkplace>,
+    restaurants: Vec<SavedRestaurant>,
+    #[serde(default)]
+    roads: Vec<(u32, u32)>,
+    /// (workplace, salary)
+    available_jobs: Vec<(usize, f32)>,
+    available_houses: Vec<usize>,
+    #[serde(default)]
+    median_wage: f32,
+    #[serde(default)]
+    previous_median_wage: f32,
+    #[serde(default)]
+    demographics: Demographics,
+    #[serde(default)]
+    shipments: Vec<SavedShipment>,
+    #[serde(default)]
+    tax_policy: TaxPolicy,
+    #[serde(default)]
+    treasury: CityTreasury,
+    /// The scenario being played and its progress, `None` in sandbox games
+    #[serde(default)]
+    scenario: Option<ActiveScenario>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedClock {
+    current_tick: u64,
+    ticks_per_hour: u64,
+    hours_per_day: u64,
+    speed: u32,
+    paused: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedPop {
+    money: i32,
+    hunger: u32,
+    energy: u32,
+    job: Option<SavedJob>,
+    home: Option<usize>,
+    #[serde(default)]
+    restaurant: Option<usize>,
+    position: [f32; 2],
+    destination: Option<[f32; 2]>,
+    #[serde(default)]
+    shift_ticks: u32,
+    #[serde(default)]
+    earnings: i32,
+    #[serde(default)]
+    unpaid_rent_days: u32,
+    #[serde(default = "default_age")]
+    age: u32,
+    #[serde(default)]
+    sex: Sex,
+    #[serde(default)]
+    partner: Option<usize>,
+    #[serde(default)]
+    starving_ticks: u32,
+    #[serde(default)]
+    exhausted_ticks: u32,
+    state: PopState,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedJob {
+    workplace: usize,
+    salary: f32,
+    #[serde(default)]
+    last_worked_day: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedHouse {
+    position: (u32, u32),
+    capacity: u32,
+    residents: Vec<usize>,
+    #[serde(default = "default_rent")]
+    rent: f32,
+    #[serde(default)]
+    rent_collected: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedWorkplace {
+    position: (u32, u32),
+    capacity: u32,
+    employees: Vec<usize>,
+    #[serde(default = "default_wage")]
+    wage: f32,
+    #[serde(default = "default_treasury")]
+    treasury: i32,
+    #[serde(default)]
+    product: Product,
+    #[serde(default)]
+    inventory: f32,
+    #[serde(default)]
+    sales: i32,
+    #[serde(default)]
+    daily_sales: i32,
+}
+
+fn default_wage() -> f32 {
+    BASE_WAGE
+}
+
+fn default_treasury() -> i32 {
+    FIRM_STARTING_CAPITAL
+}
+
+fn default_stock() -> f32 {
+    RESTAURANT_STOCK_TARGET
+}
+
+fn default_rent() -> f32 {
+    BASE_RENT
+}
+
+/// Pops from saves without ages are treated as young adults
+fn default_age() -> u32 {
+    ADULT_AGE
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedRestaurant {
+    position: (u32, u32),
+    capacity: u32,
+    #[serde(default)]
+    diners: Vec<usize>,
+    #[serde(default)]
+    queue: Vec<usize>,
+    #[serde(default)]
+    revenue: i32,
+    #[serde(default = "default_stock")]
+    food: f32,
+    #[serde(default = "default_stock")]
+    goods: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedShipment {
+    restaurant: usize,
+    product: Product,
+    amount: f32,
+    arrival_tick: u64,
+}
+
+/// Everything that makes up the running city
+#[derive(SystemParam)]
+pub(crate) struct CityState<'w, 's> {
+    tilemap_query: Query<'w, 's, &'static TilemapSize, With<Tilemap>>,
+    pop_query: Query<'w, 's, (Entity, &'static Pop)>,
+    house_query: Query<'w, 's, (Entity, &'static House)>,
+    workplace_query: Query<'w, 's, (Entity, &'static Workplace)>,
+    restaurant_query: Query<'w, 's, (Entity, &'static Restaurant)>,
+    road_query: Query<'w, 's, &'static TilePos, With<Road>>,
+    game_clock: Res<'w, GameClock>,
+    seed: Res<'w, SimSeed>,
+    rng: Res<'w, SimRng>,
+    job_market: Res<'w, JobMarket>,
+    housing_market: Res<'w, HousingMarket>,
+    demographics: Res<'w, Demographics>,
+    shipments: Res<'w, Shipments>,
+    tax_policy: Res<'w, TaxPolicy>,
+    treasury: Res<'w, CityTreasury>,
+    scenario: Option<Res<'w, ActiveScenario>>,
+}
+
+impl CityState<'_, '_> {
+    pub(crate) fn snapshot(&self) -> SaveFile {
+        let map_size = self.tilemap_query.single();
+
+        // Sort by entity so saving the same city twice gives the same file
+        let mut pops: Vec<_> = self.pop_query.iter().collect();
+        pops.sort_unstable_by_key(|(entity, _)| *entity);
+        let mut houses: Vec<_> = self.house_query.iter().collect();
+        houses.sort_unstable_by_key(|(entity, _)| *entity);
+        let mut workplaces: Vec<_> = self.workplace_query.iter().collect();
+        workplaces.sort_unstable_by_key(|(entity, _)| *entity);
+        let mut restaurants: Vec<_> = self.restaurant_query.iter().collect();
+        restaurants.sort_unstable_by_key(|(entity, _)| *entity);
+        let mut roads: Vec<_> = self.road_query.iter().map(|tile| (tile.x, tile.y)).collect();
+        roads.sort_unstable();
+
+        let pop_indices = index_entities(&pops);
+        let house_indices = index_entities(&houses);
+        let workplace_indices = index_entities(&workplaces);
+        let restaurant_indices = index_entities(&restaurants);
+        let to_pop_indices = |entities: &[Entity]| -> Vec<usize> {
+            entities
+                .iter()
+                .filter_map(|entity| pop_indices.get(entity).copied())
+                .collect()
+        };
+
+        SaveFile {
+            version: SAVE_VERSION,
+            seed: self.seed.0,
+            rng_word_pos: self.rng.word_pos(),
+            clock: SavedClock {
+                current_tick: self.game_clock.current_tick,
+                ticks_per_hour: self.game_clock.ticks_per_hour,
+                hours_per_day: self.game_clock.hours_per_day,
+                speed: self.game_clock.speed,
+                paused: self.game_clock.paused,
+            },
+            map_size: (map_size.x, map_size.y),
+            pops: pops
+                .iter()
+                .map(|(_, pop)| SavedPop {
+                    money: pop.money,
+                    hunger: pop.hunger,
+                    energy: pop.energy,
+                    job: pop.job.as_ref().and_then(|job| {
+                        Some(SavedJob {
+                            workplace: *workplace_indices.get(&job.workplace)?,
+                            salary: job.salary,
+                            last_worked_day: Some(job.last_worked_day),
+                        })
+                    }),
+                    home: pop.home.and_then(|home| house_indices.get(&home).copied()),
+                    restaurant: pop
+                        .restaurant
+                        .and_then(|restaurant| restaurant_indices.get(&restaurant).copied()),
+                    position: pop.position.to_array(),
+                    destination: pop.destination.map(|destination| destination.to_array()),
+                    shift_ticks: pop.shift_ticks,
+                    earnings: pop.earnings,
+                    unpaid_rent_days: pop.unpaid_rent_days,
+                    age: pop.age,
+                    sex: pop.sex,
+                    partner: pop.partner.and_then(|partner| pop_indices.get(&partner).copied()),
+                    starving_ticks: pop.starving_ticks,
+                    exhausted_ticks: pop.exhausted_ticks,
+                    state: pop.state,
+                })
+                .collect(),
+            houses: houses
+                .iter()
+                .map(|(_, house)| SavedHouse {
+                    position: (house.position.x, house.position.y),
+                    capacity: house.capacity,
+                    residents: to_pop_indices(&house.residents),
+                    rent: house.rent,
+                    rent_collected: house.rent_collected,
+                })
+                .collect(),
+            workplaces: workplaces
+                .iter()
+                .map(|(_, workplace)| SavedWorkplace {
+                    position: (workplace.position.x, workplace.position.y),
+                    capacity: workplace.capacity,
+                    employees: to_pop_indices(&workplace.employees),
+                    wage: workplace.wage,
+                    treasury: workplace.treasury,
+                    product: workplace.product,
+                    inventory: workplace.inventory,
+                    sales: workplace.sales,
+                    daily_sales: workplace.daily_sales,
+                })
+                .collect(),
+            restaurants: restaurants
+                .iter()
+                .map(|(_, restaurant)| SavedRestaurant {
+                    position: (restaurant.position.x, restaurant.position.y),
+                    capacity: restaurant.capacity,
+                    diners: to_pop_indices(&restaurant.diners),
+                    queue: restaurant
+                        .queue
+                        .iter()
+                        .filter_map(|entity| pop_indices.get(entity).copied())
+                        .collect(),
+                    revenue: restaurant.revenue,
+                    food: restaurant.food,
+                    goods: restaurant.goods,
+                })
+                .collect(),
+            roads,
+            available_jobs: self
+                .job_market
+                .available_jobs
+                .iter()
+                .filter_map(|(workplace, salary, _)| {
+                    Some((*workplace_indices.get(workplace)?, *salary))
+                })
+                .collect(),
+            available_houses: self
+                .housing_market
+                .available_houses
+                .iter()
+                .filter_map(|(house, ..)| house_indices.get(house).copied())
+                .collect(),
+            median_wage: self.job_market.median_wage,
+            previous_median_wage: self.job_market.previous_median_wage,
+            demographics: self.demographics.clone(),
+            shipments: self
+                .shipments
+                .0
+                .iter()
+                .filter_map(|shipment| {
+                    Some(SavedShipment {
+                        restaurant: *restaurant_indices.get(&shipment.restaurant)?,
+                        product: shipment.product,
+                        amount: shipment.amount,
+                        arrival_tick: shipment.arrival_tick,
+                    })
+                })
+                .collect(),
+            tax_policy: self.tax_policy.clone(),
+            treasury: self.treasury.clone(),
+            scenario: self.scenario.as_deref().cloned(),
+        }
+    }
+}
+
+fn index_entities<T>(entities: &[(Entity, T)]) -> HashMap<Entity, usize> {
+    entities
+        .iter()
+        .enumerate()
+        .map(|(index, (entity, _))| (*entity, index))
+        .collect()
+}
+
+fn tile_pos((x, y): (u32, u32)) -> TilePos {
+    TilePos { x, y }
+}
+
+impl SaveFile {
+    /// Refuses saves whose map or clock can't be played, and buildings or roads that don't fit
+    /// on the map one per tile
+    fn validate(&self) -> Result<(), InvalidSave> {
+        let (width, height) = self.map_size;
+        if !(1..=MAX_MAP_SIDE).contains(&width) || !(1..=MAX_MAP_SIDE).contains(&height) {
+            return Err(InvalidSave::MapSize(self.map_size));
+        }
+        if self.clock.ticks_per_hour == 0 || self.clock.hours_per_day == 0 {
+            return Err(InvalidSave::EmptyClock);
+        }
+        let positions = self
+            .houses
+            .iter()
+            .map(|house| house.position)
+            .chain(self.workplaces.iter().map(|workplace| workplace.position))
+            .chain(self.restaurants.iter().map(|restaurant| restaurant.position))
+            .chain(self.roads.iter().copied());
+        let mut taken = HashSet::new();
+        for position in positions {
+            if position.0 >= width || position.1 >= height {
+                return Err(InvalidSave::OutOfBounds(position));
+            }
+            if !taken.insert(position) {
+                return Err(InvalidSave::SharedTile(position));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Spawns the city described by `save` and restores the simulation resources.
+/// The previous city has to be despawned beforehand.
+pub(crate) fn spawn_saved_city(
+    commands: &mut Commands,
+    save: &SaveFile,
+    asset_server: Option<Res<AssetServer>>,
+) {
+    let map_size = TilemapSize {
+        x: save.map_size.0,
+        y: save.map_size.1,
+    };
+    let tilemap_entity = commands.spawn_empty().id();
+    let mut tile_storage = TileStorage::empty(map_size);
+
+    // Reserve the pops first so the buildings can link to them
+    let pop_entities: Vec<Entity> = save.pops.iter().map(|_| commands.spawn_empty().id()).collect();
+    let to_pop_entities = |indices: &[usize]| -> Vec<Entity> {
+        indices
+            .iter()
+            .filter_map(|index| pop_entities.get(*index).copied())
+            .collect()
+    };
+
+    let mut house_entities = Vec::with_capacity(save.houses.len());
+    for saved in &save.houses {
+        let position = tile_pos(saved.position);
+        let entity = spawn_house(commands, position, tilemap_entity);
+        commands.entity(entity).insert(House {
+            capacity: saved.capacity,
+            residents: to_pop_entities(&saved.residents),
+            position,
+            rent: saved.rent,
+            rent_collected: saved.rent_collected,
+        });
+        tile_storage.set(&position, entity);
+        house_entities.push(entity);
+    }
+
+    let mut workplace_entities = Vec::with_capacity(save.workplaces.len());
+    for saved in &save.workplaces {
+        let position = tile_pos(saved.position);
+        let entity = match saved.product {
+            Product::Goods => spawn_workplace(commands, position, tilemap_entity),
+            Product::Food => spawn_farm(commands, position, tilemap_entity),
+        };
+        commands.entity(entity).insert(Workplace {
+            capacity: saved.capacity,
+            employees: to_pop_entities(&saved.employees),
+            position,
+            wage: saved.wage,
+            treasury: saved.treasury,
+            product: saved.product,
+            inventory: saved.inventory,
+            sales: saved.sales,
+            daily_sales: saved.daily_sales,
+        });
+        tile_storage.set(&position, entity);
+        workplace_entities.push(entity);
+    }
+
+    let mut restaurant_entities = Vec::with_capacity(save.restaurants.len());
+    for saved in &save.restaurants {
+        let position = tile_pos(saved.position);
+        let entity = spawn_restaurant(commands, position, tilemap_entity);
+        commands.entity(entity).insert(Restaurant {
+            capacity: saved.capacity,
+            position,
+            diners: to_pop_entities(&saved.diners),
+            queue: to_pop_entities(&saved.queue).into(),
+            revenue: saved.revenue,
+            food: saved.food,
+            goods: saved.goods,
+        });
+        tile_storage.set(&position, entity);
+        restaurant_entities.push(entity);
+    }
+
+    for road in &save.roads {
+        let position = tile_pos(*road);
+        let entity = spawn_road(commands, position, tilemap_entity);
+        tile_storage.set(&position, entity);
+    }
+
+    for x in 0..map_size.x {
+        for y in 0..map_size.y {
+            let position = TilePos { x, y };
+            if tile_storage.get(&position).is_none() {
+                let entity = spawn_empty_tile(commands, position, tilemap_entity);
+                tile_storage.set(&position, entity);
+            }
+        }
+    }
+
+    let game_clock = GameClock {
+        current_tick: save.clock.current_tick,
+        ticks_per_hour: save.clock.ticks_per_hour,
+        hours_per_day: save.clock.hours_per_day,
+        speed: save.clock.speed,
+        paused: save.clock.paused,
+        stop_at: None,
+    };
+
+    for (entity, saved) in pop_entities.iter().zip(&save.pops) {
+        let job = saved.job.as_ref().and_then(|job| {
+            Some(Job {
+                workplace: *workplace_entities.get(job.workplace)?,
+                salary: job.salary,
+                position: tile_pos(save.workplaces[job.workplace].position),
+                // Older saves don't know, so everyone starts with a clean record
+                last_worked_day: job.last_worked_day.unwrap_or(game_clock.day()),
+            })
+        });
+        commands.entity(*entity).insert(Pop {
+            money: saved.money,
+            hunger: saved.hunger,
+            energy: saved.energy,
+            job,
+            home: saved.home.and_then(|home| house_entities.get(home).copied()),
+            restaurant: saved
+                .restaurant
+                .and_then(|restaurant| restaurant_entities.get(restaurant).copied()),
+            position: WorldPos(Vec2::from_array(saved.position)),
+            destination: saved
+                .destination
+                .map(|destination| WorldPos(Vec2::from_array(destination))),
+            // Paths are planned again on the next tick
+            waypoints: Vec::new(),
+            path_goal: None,
+            shift_ticks: saved.shift_ticks,
+            earnings: saved.earnings,
+            unpaid_rent_days: saved.unpaid_rent_days,
+            age: saved.age,
+            sex: saved.sex,
+            partner: saved.partner.and_then(|partner| pop_entities.get(partner).copied()),
+            starving_ticks: saved.starving_ticks,
+            exhausted_ticks: saved.exhausted_ticks,
+            state: saved.state,
+        });
+    }
+
+    insert_tilemap(commands, tilemap_entity, map_size, tile_storage, asset_server);
+
+    commands.insert_resource(game_clock);
+    commands.insert_resource(SimSeed(save.seed));
+    commands.insert_resource(SimRng::resume(SimSeed(save.seed), save.rng_word_pos));
+    commands.insert_resource(JobMarket {
+        available_jobs: save
+            .available_jobs
+            .iter()
+            .filter_map(|(workplace, salary)| {
+                let entity = *workplace_entities.get(*workplace)?;
+                Some((entity, *salary, tile_pos(save.workplaces[*workplace].position)))
+            })
+            .collect(),
+        median_wage: save.median_wage,
+        previous_median_wage: save.previous_median_wage,
+    });
+    commands.insert_resource(save.demographics.clone());
+    commands.insert_resource(save.tax_policy.clone());
+    commands.insert_resource(save.treasury.clone());
+    match &save.scenario {
+        Some(scenario) => commands.insert_resource(scenario.clone()),
+        None => commands.remove_resource::<ActiveScenario>(),
+    }
+    commands.insert_resource(Shipments(
+        save.shipments
+            .iter()
+            .filter_map(|shipment| {
+                Some(Shipment {
+                    restaurant: *restaurant_entities.get(shipment.restaurant)?,
+                    product: shipment.product,
+                    amount: shipment.amount,
+                    arrival_tick: shipment.arrival_tick,
+                })
+            })
+            .collect(),
+    ));
+    commands.insert_resource(HousingMarket {
+        available_houses: save
+            .available_houses
+            .iter()
+            .filter_map(|house| {
+                let entity = *house_entities.get(*house)?;
+                let saved = &save.houses[*house];
+                Some((entity, saved.rent, tile_pos(saved.position)))
+            })
+            .collect(),
+    });
+}
+
+fn write_save(save: &SaveFile) -> Result<(), SaveError> {
+    let text = ron::ser::to_string_pretty(save, ron::ser::PrettyConfig::default())
+        .map_err(SaveError::Serialize)?;
+    if let Some(directory) = Path::new(SAVE_PATH).parent() {
+        std::fs::create_dir_all(directory).map_err(SaveError::Io)?;
+    }
+    std::fs::write(SAVE_PATH, text).map_err(SaveError::Io)
+}
+
+fn read_save() -> Result<SaveFile, SaveError> {
+    let text = std::fs::read_to_string(SAVE_PATH).map_err(SaveError::Io)?;
+    parse_save(&text)
+}
+
+fn parse_save(text: &str) -> Result<SaveFile, SaveError> {
+    let save: SaveFile = ron::from_str(text).map_err(SaveError::Deserialize)?;
+    if save.version > SAVE_VERSION {
+        return Err(SaveError::UnsupportedVersion(save.version));
+    }
+    save.validate().map_err(SaveError::Invalid)?;
+    Ok(save)
+}
+
+fn handle_save_hotkeys(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut save_events: EventWriter<SaveCity>,
+    mut load_events: EventWriter<LoadCity>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F5) {
+        save_events.send(SaveCity);
+    }
+    if keyboard_input.just_pressed(KeyCode::F9) {
+        load_events.send(LoadCity);
+    }
+}
+
+fn save_city(mut save_events: EventReader<SaveCity>, city: CityState) {
+    if save_events.read().last().is_none() {
+        return;
+    }
+    match write_save(&city.snapshot()) {
+        Ok(()) => info!("Saved city to {SAVE_PATH}"),
+        Err(error) => warn!("Failed to save city to {SAVE_PATH}: {error}"),
+    }
+}
+
+fn load_city(
+    mut commands: Commands,
+    mut load_events: EventReader<LoadCity>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut history: ResMut<StatsHistory>,
+    mut dirty: ResMut<DirtyChunks>,
+    city_query: Query<Entity, CityEntities>,
+    asset_server: Option<Res<AssetServer>>,
+) {
+    if load_events.read().last().is_none() {
+        return;
+    }
+    let save = match read_save() {
+        Ok(save) => save,
+        Err(error) => {
+            warn!("Failed to load city from {SAVE_PATH}: {error}");
+            return;
+        }
+    };
+
+    if *state.get() == GameState::Playing {
+        for entity in city_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        // The save brings its own clock, markets and budget, but not the history and dirty chunks
+        // of the city it replaces
+        history.clear();
+        dirty.clear();
+        spawn_saved_city(&mut commands, &save, asset_server);
+        info!("Loaded city from {SAVE_PATH}");
+    } else {
+        commands.insert_resource(PendingLoad(save));
+        next_state.set(GameState::Playing);
+    }
+}
+
+fn spawn_pending_city(
+    mut commands: Commands,
+    pending: Res<PendingLoad>,
+    asset_server: Option<Res<AssetServer>>,
+) {
+    spawn_saved_city(&mut commands, &pending.0, asset_server);
+    commands.remove_resource::<PendingLoad>();
+    info!("Loaded city from {SAVE_PATH}");
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::schedule::ScheduleLabel;
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::*;
+
+    use crate::rng::SimSeed;
+    use crate::scenario::{ActiveScenario, Scenario};
+    use crate::save::{parse_save, spawn_saved_city, CityState, InvalidSave, SaveError, SaveFile};
+    use crate::tilemap::{spawn_tilemap, SimulationPlugin};
+
+    fn simulation_app() -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(SimSeed(7))
+            .add_plugins(SimulationPlugin { schedule: Update.intern() });
+        app
+    }
+
+    fn save_to_string(app: &mut App) -> String {
+        let save = app
+            .world_mut()
+            .run_system_once(|city: CityState| city.snapshot())
+            .unwrap();
+        ron::to_string(&save).unwrap()
+    }
+
+    #[test]
+    fn test_save_round_trip() {
+        let mut original = simulation_app();
+        // The scenario and how far along it is are saved with the city
+        let scenario: Scenario =
+            ron::from_str("(name: \"Metropolis\", goals: [(metric: Population, above: Some(100000.0))])").unwrap();
+        original
+            .insert_resource(ActiveScenario::new(scenario))
+            .add_systems(Startup, spawn_tilemap);
+        for _ in 0..300 {
+            original.update();
+        }
+        let saved = save_to_string(&mut original);
+
+        let mut loaded = simulation_app();
+        let save: SaveFile = ron::from_str(&saved).unwrap();
+        loaded
+            .world_mut()
+            .run_system_once_with(save, |In(save): In<SaveFile>, mut commands: Commands| {
+                spawn_saved_city(&mut commands, &save, None)
+            })
+            .unwrap();
+
+        assert_eq!(saved, save_to_string(&mut loaded));
+    }
+
+    #[test]
+    fn test_saves_with_buildings_off_the_map_are_refused() {
+        let mut app = simulation_app();
+        app.add_systems(Startup, spawn_tilemap);
+        app.update();
+        let saved = save_to_string(&mut app);
+        assert!(parse_save(&saved).is_ok());
+
+        let mut save: SaveFile = ron::from_str(&saved).unwrap();
+        save.houses[0].position = (save.map_size.0, 0);
+        let broken = ron::to_string(&save).unwrap();
+        assert!(matches!(
+            parse_save(&broken),
+            Err(SaveError::Invalid(InvalidSave::OutOfBounds(_)))
+        ));
+
+        let mut save: SaveFile = ron::from_str(&saved).unwrap();
+        save.houses[0].position = save.workplaces[0].position;
+        let broken = ron::to_string(&save).unwrap();
+        assert!(matches!(
+            parse_save(&broken),
+            Err(SaveError::Invalid(InvalidSave::SharedTile(_)))
+        ));
+    }
+}
@@ -0,0 +1,571 @@
+use std::fmt;
+use std::path::Path;
+
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, LoadContext};
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::constants::{CITY_STARTING_FUNDS, MAX_MAP_SIDE};
+use crate::mapfile::CityMap;
+use crate::mapgen::{Layout, NewCitySettings};
+use crate::rng::SimSeed;
+use crate::stats::CityStats;
+use crate::tilemap::{GameClock, House, Pop, Restaurant, Workplace};
+use crate::treasury::{CityTreasury, TaxPolicy};
+use crate::GameState;
+
+const BUTTON_NORMAL: Color = Color::linear_rgb(0.15, 0.15, 0.15);
+const BUTTON_HOVERED: Color = Color::linear_rgb(0.25, 0.25, 0.25);
+
+pub struct ScenarioPlugin;
+
+/// This plugin loads `.scenario.ron` files and shows the victory or defeat screen once the running
+/// scenario is decided. The goals themselves are checked in the simulation, see [`ActiveScenario`].
+impl Plugin for ScenarioPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Scenario>()
+            .register_asset_loader(ScenarioLoader)
+            .add_systems(
+                Update,
+                (show_outcome, click_keep_playing)
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnExit(GameState::Playing), despawn_outcome_screen);
+    }
+}
+
+/// A city to start from and what the player has to achieve in it
+#[derive(Asset, TypePath, Clone, Debug, Serialize, Deserialize)]
+pub struct Scenario {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Asset path of a hand-authored map, the city is generated otherwise
+    #[serde(default)]
+    pub map: Option<String>,
+    #[serde(default)]
+    pub map_size: Option<(u32, u32)>,
+    #[serde(default)]
+    pub layout: Option<Layout>,
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Adults living in the houses when the city is founded
+    #[serde(default)]
+    pub starting_population: Option<usize>,
+    #[serde(default)]
+    pub starting_funds: Option<i64>,
+    /// Replaces the default tax rates and welfare
+    #[serde(default)]
+    pub tax_policy: Option<TaxPolicy>,
+    /// Won once all of these are met at the end of a day
+    pub goals: Vec<Condition>,
+    /// Lost if the goals aren't met by the end of this day
+    #[serde(default)]
+    pub deadline_day: Option<u64>,
+    /// Lost as soon as any of these is met
+    #[serde(default)]
+    pub failures: Vec<Condition>,
+    /// `map` as loaded by the asset server
+    #[serde(skip)]
+    pub(crate) map_handle: Option<Handle<CityMap>>,
+}
+
+/// Something about the city that holds or doesn't at the end of a day
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Condition {
+    pub metric: Metric,
+    /// Met while the metric is above this
+    #[serde(default)]
+    pub above: Option<f32>,
+    /// Met while the metric is below this
+    #[serde(default)]
+    pub below: Option<f32>,
+    /// Days in a row it has to be met
+    #[serde(default = "one_day")]
+    pub days: u32,
+}
+
+fn one_day() -> u32 {
+    1
+}
+
+/// The city aggregates conditions can check, see [`CityStats`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Metric {
+    Population,
+    /// Share of the population without a home, in percent
+    HomelessPercent,
+    /// Share of the population with a job, in percent
+    EmploymentPercent,
+    AverageHunger,
+    AverageMoney,
+    MedianWage,
+    CityFunds,
+}
+
+impl Metric {
+    fn label(self) -> &'static str {
+        match self {
+            Metric::Population => "population",
+            Metric::HomelessPercent => "homelessness (%)",
+            Metric::EmploymentPercent => "employment (%)",
+            Metric::AverageHunger => "average hunger",
+            Metric::AverageMoney => "average money",
+            Metric::MedianWage => "median wage",
+            Metric::CityFunds => "city funds",
+        }
+    }
+
+    fn measure(self, stats: &CityStats, treasury: &CityTreasury) -> f32 {
+        match self {
+            Metric::Population => stats.population as f32,
+            Metric::HomelessPercent => stats.homeless_rate(),
+            Metric::EmploymentPercent => stats.employment_rate(),
+            Metric::AverageHunger => stats.average_hunger,
+            Metric::AverageMoney => stats.average_money,
+            Metric::MedianWage => stats.median_wage,
+            Metric::CityFunds => treasury.balance as f32,
+        }
+    }
+}
+
+impl Condition {
+    fn holds(&self, value: f32) -> bool {
+        self.above.is_none_or(|above| value > above) && self.below.is_none_or(|below| value < below)
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.metric.label())?;
+        if let Some(above) = self.above {
+            write!(f, " above {above}")?;
+        }
+        if let Some(below) = self.below {
+            write!(f, " below {below}")?;
+        }
+        if self.days > 1 {
+            write!(f, " for {} days", self.days)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    MapSize((u32, u32)),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::Io(error) => write!(f, "{error}"),
+            ScenarioError::Parse(error) => write!(f, "{error}"),
+            ScenarioError::MapSize((x, y)) => write!(
+                f,
+                "the map is {x}x{y}, but its sides must be 1 to {MAX_MAP_SIDE} tiles"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl From<std::io::Error> for ScenarioError {
+    fn from(error: std::io::Error) -> Self {
+        ScenarioError::Io(error)
+    }
+}
+
+impl Scenario {
+    /// Reads a `.scenario.ron` file without going through the asset server, `map` is left unloaded
+    pub fn read(path: &Path) -> Result<Self, ScenarioError> {
+        Scenario::parse(&std::fs::read(path)?)
+    }
+
+    /// Parses the contents of a `.scenario.ron` file and refuses map sizes that can't be played
+    fn parse(bytes: &[u8]) -> Result<Self, ScenarioError> {
+        let scenario: Scenario = ron::de::from_bytes(bytes).map_err(ScenarioError::Parse)?;
+        if let Some((x, y)) = scenario.map_size {
+            if !(1..=MAX_MAP_SIDE).contains(&x) || !(1..=MAX_MAP_SIDE).contains(&y) {
+                return Err(ScenarioError::MapSize((x, y)));
+            }
+        }
+        Ok(scenario)
+    }
+
+    /// Makes the next city the start of this scenario, `map` being the loaded [`Scenario::map`]
+    pub fn configure(&self, settings: &mut NewCitySettings, seed: &mut SimSeed, map: Option<CityMap>) {
+        settings.map = map;
+        if let Some((x, y)) = self.map_size {
+            settings.map_size = TilemapSize { x, y };
+        }
+        if let Some(layout) = self.layout {
+            settings.layout = layout;
+        }
+        if let Some(scenario_seed) = self.seed {
+            *seed = SimSeed(scenario_seed);
+        }
+        settings.starting_population = self.starting_population;
+        settings.scenario = Some(self.clone());
+    }
+}
+
+/// How a scenario ended
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Outcome {
+    Victory { day: u64 },
+    Defeat { day: u64, reason: String },
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Victory { day } => write!(f, "Victory on day {day}"),
+            Outcome::Defeat { day, reason } => write!(f, "Defeat on day {day}: {reason}"),
+        }
+    }
+}
+
+/// The scenario being played and how far along it is. Started together with a new city and saved
+/// with it, it doesn't exist in sandbox games.
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+pub struct ActiveScenario {
+    pub scenario: Scenario,
+    /// Days in a row each goal has been met
+    goal_streaks: Vec<u32>,
+    /// Days in a row each failure condition has been met
+    failure_streaks: Vec<u32>,
+    pub outcome: Option<Outcome>,
+}
+
+impl ActiveScenario {
+    pub fn new(scenario: Scenario) -> Self {
+        Self {
+            goal_streaks: vec![0; scenario.goals.len()],
+            failure_streaks: vec![0; scenario.failures.len()],
+            scenario,
+            outcome: None,
+        }
+    }
+
+    /// Checks the city as it was at the end of `day` and decides the outcome once it is clear.
+    /// Failures are checked before goals, so meeting both on the same day is a defeat.
+    fn evaluate(&mut self, day: u64, stats: &CityStats, treasury: &CityTreasury) {
+        if self.outcome.is_some() {
+            return;
+        }
+        let update_streaks = |conditions: &[Condition], streaks: &mut [u32]| {
+            for (condition, streak) in conditions.iter().zip(streaks.iter_mut()) {
+                let met = condition.holds(condition.metric.measure(stats, treasury));
+                *streak = if met { *streak + 1 } else { 0 };
+            }
+        };
+        update_streaks(&self.scenario.failures, &mut self.failure_streaks);
+        update_streaks(&self.scenario.goals, &mut self.goal_streaks);
+
+        let failure = self
+            .scenario
+            .failures
+            .iter()
+            .zip(&self.failure_streaks)
+            .find(|(condition, streak)| **streak >= condition.days);
+        let goals_met = !self.scenario.goals.is_empty()
+            && self
+                .scenario
+                .goals
+                .iter()
+                .zip(&self.goal_streaks)
+                .all(|(condition, streak)| *streak >= condition.days);
+
+        self.outcome = if let Some((condition, _)) = failure {
+            Some(Outcome::Defeat { day, reason: condition.to_string() })
+        } else if goals_met {
+            Some(Outcome::Victory { day })
+        } else {
+            self.scenario
+                .deadline_day
+                .filter(|deadline| day >= *deadline)
+                .map(|deadline| Outcome::Defeat {
+                    day,
+                    reason: format!("the goals weren't met by day {deadline}"),
+                })
+        };
+    }
+}
+
+impl fmt::Display for ActiveScenario {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Scenario: {}", self.scenario.name)?;
+        if let Some(deadline) = self.scenario.deadline_day {
+            write!(f, " (until day {deadline})")?;
+        }
+        for (condition, streak) in self.scenario.goals.iter().zip(&self.goal_streaks) {
+            let mark = if *streak >= condition.days { "x" } else { " " };
+            write!(f, "\n[{mark}] {condition}")?;
+        }
+        if let Some(outcome) = &self.outcome {
+            write!(f, "\n{outcome}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Starts the scenario picked in [`NewCitySettings`] in the city that was just generated,
+/// or ends the previous one when none is picked
+pub(crate) fn start_scenario(mut commands: Commands, settings: Res<NewCitySettings>) {
+    let Some(scenario) = &settings.scenario else {
+        commands.remove_resource::<ActiveScenario>();
+        return;
+    };
+    commands.insert_resource(CityTreasury {
+        balance: scenario.starting_funds.unwrap_or(CITY_STARTING_FUNDS),
+        ..default()
+    });
+    if let Some(policy) = &scenario.tax_policy {
+        commands.insert_resource(policy.clone());
+    }
+    commands.insert_resource(ActiveScenario::new(scenario.clone()));
+}
+
+/// At the start of every day, checks the day that just ended against the scenario
+pub(crate) fn evaluate_scenario(
+    game_clock: Res<GameClock>,
+    treasury: Res<CityTreasury>,
+    mut scenario: ResMut<ActiveScenario>,
+    pop_query: Query<&Pop>,
+    house_query: Query<&House>,
+    workplace_query: Query<&Workplace>,
+    restaurant_query: Query<&Restaurant>,
+) {
+    if !game_clock.is_day_start() || game_clock.current_tick == 0 || scenario.outcome.is_some() {
+        return;
+    }
+    let stats = CityStats::collect(&pop_query, &house_query, &workplace_query, &restaurant_query);
+    scenario.evaluate(game_clock.day() - 1, &stats, &treasury);
+}
+
+#[derive(Default)]
+struct ScenarioLoader;
+
+impl AssetLoader for ScenarioLoader {
+    type Asset = Scenario;
+    type Settings = ();
+    type Error = ScenarioError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Scenario, ScenarioError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let mut scenario = Scenario::parse(&bytes)?;
+        scenario.map_handle = scenario.map.clone().map(|path| load_context.load(path));
+        Ok(scenario)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["scenario.ron"]
+    }
+}
+
+#[derive(Component)]
+struct OutcomeScreen;
+
+#[derive(Component)]
+struct KeepPlaying;
+
+/// Pauses the game and shows how the scenario ended
+fn show_outcome(
+    mut commands: Commands,
+    scenario: Option<Res<ActiveScenario>>,
+    mut game_clock: ResMut<GameClock>,
+    screen_query: Query<(), With<OutcomeScreen>>,
+) {
+    let Some(scenario) = scenario else {
+        return;
+    };
+    let Some(outcome) = &scenario.outcome else {
+        return;
+    };
+    if !scenario.is_changed() || !screen_query.is_empty() {
+        return;
+    }
+    game_clock.paused = true;
+
+    let (title, color) = match outcome {
+        Outcome::Victory { .. } => ("Victory!", Color::linear_rgb(0.4, 0.9, 0.4)),
+        Outcome::Defeat { .. } => ("Defeat", Color::linear_rgb(0.9, 0.3, 0.3)),
+    };
+    let text_font = TextFont {
+        font_size: 20.0,
+        ..default()
+    };
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(10.0),
+                ..default()
+            },
+            BackgroundColor(Color::linear_rgba(0.0, 0.0, 0.0, 0.7)),
+            GlobalZIndex(10),
+            OutcomeScreen,
+        ))
+        .with_children(|screen| {
+            screen.spawn((
+                Text::new(title),
+                TextFont {
+                    font_size: 48.0,
+                    ..default()
+                },
+                TextColor(color),
+            ));
+            screen.spawn((Text::new(scenario.to_string()), text_font.clone()));
+            screen
+                .spawn((
+                    Button,
+                    Node {
+                        width: Val::Px(180.0),
+                        height: Val::Px(40.0),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    BackgroundColor(BUTTON_NORMAL),
+                    KeepPlaying,
+                ))
+                .with_child((Text::new("Keep playing"), text_font));
+        });
+}
+
+fn click_keep_playing(
+    mut commands: Commands,
+    mut game_clock: ResMut<GameClock>,
+    mut button_query: Query<(&Interaction, &mut BackgroundColor), (Changed<Interaction>, With<KeepPlaying>)>,
+    screen_query: Query<Entity, With<OutcomeScreen>>,
+) {
+    for (interaction, mut color) in button_query.iter_mut() {
+        if *interaction == Interaction::Pressed {
+            game_clock.paused = false;
+            for entity in screen_query.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        *color = if *interaction == Interaction::None {
+            BUTTON_NORMAL.into()
+        } else {
+            BUTTON_HOVERED.into()
+        };
+    }
+}
+
+fn despawn_outcome_screen(mut commands: Commands, screen_query: Query<Entity, With<OutcomeScreen>>) {
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::scenario::{ActiveScenario, Condition, Metric, Outcome, Scenario, ScenarioError};
+    use crate::stats::CityStats;
+    use crate::treasury::CityTreasury;
+
+    fn scenario() -> Scenario {
+        ron::from_str(
+            "#![enable(implicit_some)]
+            (
+                name: \"Test\",
+                goals: [(metric: Population, above: 100.0), (metric: HomelessPercent, below: 5.0)],
+                deadline_day: 5,
+                failures: [(metric: AverageHunger, above: 8000.0, days: 3)],
+            )",
+        )
+        .unwrap()
+    }
+
+    fn stats(population: usize, homeless: usize, average_hunger: f32) -> CityStats {
+        CityStats { population, homeless, average_hunger, ..Default::default() }
+    }
+
+    #[test]
+    fn test_scenarios_are_won_once_all_goals_are_met() {
+        let treasury = CityTreasury::default();
+        let mut active = ActiveScenario::new(scenario());
+        active.evaluate(1, &stats(150, 20, 0.0), &treasury);
+        assert_eq!(active.outcome, None, "Too many pops are homeless");
+        active.evaluate(2, &stats(150, 2, 0.0), &treasury);
+        assert_eq!(active.outcome, Some(Outcome::Victory { day: 2 }));
+
+        // Nothing changes once the scenario is decided
+        active.evaluate(3, &stats(0, 0, 9000.0), &treasury);
+        assert_eq!(active.outcome, Some(Outcome::Victory { day: 2 }));
+    }
+
+    #[test]
+    fn test_scenarios_are_lost_on_failures_and_deadlines() {
+        let treasury = CityTreasury::default();
+        let mut active = ActiveScenario::new(scenario());
+        for (day, hunger) in [(1, 9000.0), (2, 9000.0), (3, 100.0), (4, 9000.0)] {
+            active.evaluate(day, &stats(50, 0, hunger), &treasury);
+        }
+        assert_eq!(active.outcome, None, "The hungry days weren't in a row");
+
+        // Three hungry days in a row lose the scenario before the deadline
+        let mut active = ActiveScenario::new(scenario());
+        for day in 1..=3 {
+            active.evaluate(day, &stats(50, 0, 9000.0), &treasury);
+        }
+        assert_eq!(
+            active.outcome,
+            Some(Outcome::Defeat { day: 3, reason: "average hunger above 8000 for 3 days".to_string() })
+        );
+
+        let mut active = ActiveScenario::new(scenario());
+        for day in 1..=5 {
+            active.evaluate(day, &stats(50, 0, 0.0), &treasury);
+        }
+        assert_eq!(
+            active.outcome,
+            Some(Outcome::Defeat { day: 5, reason: "the goals weren't met by day 5".to_string() })
+        );
+
+        let condition = Condition { metric: Metric::AverageHunger, above: Some(8000.0), below: None, days: 3 };
+        assert_eq!(condition.to_string(), "average hunger above 8000 for 3 days");
+    }
+
+    #[test]
+    fn test_scenarios_with_unplayable_map_sizes_are_refused() {
+        let with_size = |size: &str| format!("(name: \"Sized\", map_size: Some({size}), goals: [])");
+        assert!(Scenario::parse(with_size("(64, 32)").as_bytes()).is_ok());
+        for size in ["(0, 32)", "(32, 100000)"] {
+            assert!(
+                matches!(Scenario::parse(with_size(size).as_bytes()), Err(ScenarioError::MapSize(_))),
+                "{size} should be refused"
+            );
+        }
+    }
+
+    #[test]
+    fn test_shipped_scenarios_parse() {
+        for path in ["assets/scenarios/boomtown.scenario.ron", "assets/scenarios/famine.scenario.ron"] {
+            let scenario = Scenario::read(Path::new(path)).unwrap_or_else(|error| panic!("{path}: {error}"));
+            assert!(!scenario.goals.is_empty(), "{path} has no goals");
+        }
+    }
+}
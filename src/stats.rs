@@ -0,0 +1,171 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::tilemap::{House, Pop, Restaurant, Workplace};
+
+/// City-wide aggregates over all pops and buildings.
+/// Shared by the HUD and the headless runner so both report the same numbers.
+#[derive(Default, Clone, Debug, Serialize)]
+pub struct CityStats {
+    pub population: usize,
+    pub children: usize,
+    pub average_age: f32,
+    pub employed: usize,
+    pub homeless: usize,
+    pub average_money: f32,
+    pub average_hunger: f32,
+    pub average_energy: f32,
+    pub houses: usize,
+    pub house_capacity: u32,
+    pub workplaces: usize,
+    pub job_capacity: u32,
+    pub restaurants: usize,
+    /// Restaurants that ran out of food or goods and turn diners away
+    pub restaurants_out_of_stock: usize,
+    /// Food in stock across all restaurants
+    pub food_in_stock: f32,
+    /// Median daily salary of the employed pops
+    pub median_wage: f32,
+    /// Change of the median wage over the last day, see [`crate::tilemap::JobMarket::wage_trend`]
+    pub wage_trend: f32,
+}
+
+impl CityStats {
+    pub fn collect<'a>(
+        pops: impl IntoIterator<Item = &'a Pop>,
+        houses: impl IntoIterator<Item = &'a House>,
+        workplaces: impl IntoIterator<Item = &'a Workplace>,
+        restaurants: impl IntoIterator<Item = &'a Restaurant>,
+    ) -> Self {
+        let mut stats = CityStats::default();
+
+        let mut total_money: i64 = 0;
+        let mut total_hunger: u64 = 0;
+        let mut total_energy: u64 = 0;
+        let mut total_age: u64 = 0;
+        let mut wages = Vec::new();
+        for pop in pops {
+            stats.population += 1;
+            if !pop.is_adult() {
+                stats.children += 1;
+            }
+            total_age += pop.age as u64;
+            if let Some(job) = &pop.job {
+                stats.employed += 1;
+                wages.push(job.salary);
+            }
+            if pop.home.is_none() {
+                stats.homeless += 1;
+            }
+            total_money += pop.money as i64;
+            total_hunger += pop.hunger as u64;
+            total_energy += pop.energy as u64;
+        }
+        if stats.population > 0 {
+            let population = stats.population as f32;
+            stats.average_money = total_money as f32 / population;
+            stats.average_hunger = total_hunger as f32 / population;
+            stats.average_energy = total_energy as f32 / population;
+            stats.average_age = total_age as f32 / population;
+        }
+        stats.median_wage = median(&mut wages);
+
+        for house in houses {
+            stats.houses += 1;
+            stats.house_capacity += house.capacity;
+        }
+
+        for workplace in workplaces {
+            stats.workplaces += 1;
+            stats.job_capacity += workplace.capacity;
+        }
+
+        for restaurant in restaurants {
+            stats.restaurants += 1;
+            if !restaurant.can_serve() {
+                stats.restaurants_out_of_stock += 1;
+            }
+            stats.food_in_stock += restaurant.food;
+        }
+
+        stats
+    }
+
+    /// Share of the population with a job, in percent
+    pub fn employment_rate(&self) -> f32 {
+        self.percent_of_population(self.employed)
+    }
+
+    /// Share of the population without a home, in percent
+    pub fn homeless_rate(&self) -> f32 {
+        self.percent_of_population(self.homeless)
+    }
+
+    fn percent_of_population(&self, count: usize) -> f32 {
+        if self.population > 0 {
+            (count as f32 / self.population as f32) * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Median daily salary of the employed among `pops`, 0 if nobody has a job
+pub fn median_wage<'a>(pops: impl IntoIterator<Item = &'a Pop>) -> f32 {
+    let mut wages: Vec<f32> = pops
+        .into_iter()
+        .filter_map(|pop| pop.job.as_ref().map(|job| job.salary))
+        .collect();
+    median(&mut wages)
+}
+
+fn median(values: &mut [f32]) -> f32 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_unstable_by(f32::total_cmp);
+    let middle = values.len() / 2;
+    if values.len() % 2 == 0 {
+        (values[middle - 1] + values[middle]) / 2.0
+    } else {
+        values[middle]
+    }
+}
+
+impl fmt::Display for CityStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Population: {} ({} children, average age {:.1})\n\
+            Employed: {} ({:.1}%)\n\
+            Homeless: {} ({:.1}%)\n\
+            Average Money: ${:.2}\n\
+            Median Wage: ${:.2}/day ({:+.2} since yesterday)\n\
+            Average Hunger: {:.1}/10000\n\
+            Average Energy: {:.1}/10000\n\n\
+            Houses: {} (Capacity: {})\n\
+            Workplaces: {} (Capacity: {})\n\
+            Restaurants: {} ({} out of stock, {:.0} food left)",
+            self.population,
+            self.children,
+            self.average_age,
+            self.employed,
+            self.employment_rate(),
+            self.homeless,
+            self.homeless_rate(),
+            self.average_money,
+            self.median_wage,
+            self.wage_trend,
+            self.average_hunger,
+            self.average_energy,
+            self.houses,
+            self.house_capacity,
+            self.workplaces,
+            self.job_capacity,
+            self.restaurants,
+            self.restaurants_out_of_stock,
+            self.food_in_stock
+        )
+    }
+}
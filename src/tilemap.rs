@@ -1,11 +1,28 @@
 // src/tilemap.rs
 
-use bevy::ecs::schedule::ScheduleLabel;
+use std::collections::{HashSet, VecDeque};
+
+use bevy::ecs::schedule::{InternedScheduleLabel, ScheduleLabel};
 use bevy::prelude::*;
 use bevy_ecs_tilemap::prelude::*;
 use rand::Rng;
-
-use crate::GameState;
+use serde::{Deserialize, Serialize};
+
+use crate::bookkeeping::{hire, leave_job, move_in, review_employment};
+#[cfg(debug_assertions)]
+use crate::bookkeeping::check_links;
+use crate::firms::{
+    close_bankrupt_firms, deliver_shipments, order_supplies, produce_goods, Product, Shipments,
+};
+use crate::history::{record_stats, StatsHistory};
+use crate::mapgen::{Lot, NewCitySettings};
+use crate::lifecycle::{check_deaths, form_households, have_children, migrate, Demographics};
+use crate::pathfinding::{plan_paths, update_nav_grid, NavGrid};
+use crate::rng::{SimRng, SimSeed};
+use crate::save::PendingLoad;
+use crate::scenario::{evaluate_scenario, start_scenario, ActiveScenario};
+use crate::treasury::{balance_budget, CityTreasury, TaxPolicy};
+use crate::{GameState, PlayState};
 
 pub struct TilePlugin;
 
@@ -14,24 +31,93 @@ pub struct Tilemap;
 
 impl Plugin for TilePlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(TilemapPlugin)
-            .init_resource::<GameClock>()
-            .init_resource::<JobMarket>()
-            .init_resource::<HousingMarket>()
-            .add_systems(OnEnter(GameState::Playing), spawn_tilemap)
+        app.add_plugins((TilemapPlugin, SimulationPlugin::default()))
+            .init_resource::<DirtyChunks>()
+            .configure_sets(
+                FixedUpdate,
+                SimulationSet.run_if(in_state(PlayState::Running).and(clock_is_running)),
+            )
+            .add_systems(
+                OnEnter(GameState::Playing),
+                (spawn_tilemap, start_scenario)
+                    .chain()
+                    .run_if(not(resource_exists::<PendingLoad>)),
+            )
             .add_systems(Update, (
                 update_fixed_time,
-                update_pop_visuals,
-                handle_speed_input,
+                (mark_dirty_chunks, redraw_dirty_chunks).chain(),
+                handle_speed_input.run_if(in_state(PlayState::Running)),
                 render_pops,
             ).run_if(in_state(GameState::Playing)))
-            .add_systems(FixedUpdate, (
+            .add_systems(OnExit(GameState::Playing), (despawn_city, reset_simulation));
+    }
+}
+
+/// Systems that advance the city simulation by one tick
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SimulationSet;
+
+/// This plugin owns the simulation itself: the clock, pop needs and movement, and the markets.
+/// It has no dependency on rendering, assets or `GameState`, so it can also be driven headless
+/// (see [`crate::HeadlessPlugin`]). Every run of `schedule` advances the simulation by one tick.
+pub struct SimulationPlugin {
+    pub schedule: InternedScheduleLabel,
+}
+
+impl Default for SimulationPlugin {
+    fn default() -> Self {
+        Self {
+            schedule: FixedUpdate.intern(),
+        }
+    }
+}
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SimSeed>()
+            .init_resource::<GameClock>()
+            .init_resource::<JobMarket>()
+            .init_resource::<HousingMarket>()
+            .init_resource::<NavGrid>()
+            .init_resource::<Demographics>()
+            .init_resource::<Shipments>()
+            .init_resource::<TaxPolicy>()
+            .init_resource::<CityTreasury>()
+            .init_resource::<StatsHistory>()
+            .init_resource::<NewCitySettings>()
+            .add_systems(self.schedule, (
                 update_game_clock,
+                update_nav_grid,
                 update_pops,
+                (check_deaths, form_households, have_children, migrate).chain(),
+                review_employment,
+                plan_paths,
                 move_pops,
+                serve_restaurants,
+                produce_goods,
+                deliver_shipments,
+                order_supplies,
+                close_bankrupt_firms,
+                review_wages,
+                collect_rent,
+                balance_budget,
                 manage_markets,
                 assign_jobs_and_housing,
-            ).run_if(in_state(GameState::Playing)));
+                record_stats,
+            ).chain().in_set(SimulationSet))
+            .add_systems(
+                self.schedule,
+                evaluate_scenario
+                    .after(record_stats)
+                    .in_set(SimulationSet)
+                    .run_if(resource_exists::<ActiveScenario>),
+            );
+
+        #[cfg(debug_assertions)]
+        app.add_systems(
+            self.schedule,
+            check_links.after(assign_jobs_and_housing).in_set(SimulationSet),
+        );
     }
 }
 
@@ -48,12 +134,66 @@ pub struct Pop {
     pub(crate) energy: u32,
     pub(crate) job: Option<Job>,
     pub(crate) home: Option<Entity>,
-    position: Vec2,
-    destination: Option<Vec2>,
+    /// Restaurant the pop is eating at or heading to while `PopState::Eating`
+    pub(crate) restaurant: Option<Entity>,
+    pub(crate) position: WorldPos,
+    pub(crate) destination: Option<WorldPos>,
+    /// Tile centers left to walk through on the way to `destination`, the next one last
+    pub(crate) waypoints: Vec<WorldPos>,
+    /// The destination `waypoints` were planned for
+    pub(crate) path_goal: Option<WorldPos>,
+    /// Ticks spent at the workplace during the current shift, paid out when the shift ends
+    pub(crate) shift_ticks: u32,
+    /// Pay received since taxes were last collected
+    pub(crate) earnings: i32,
+    /// Days in a row the pop couldn't pay its rent
+    pub(crate) unpaid_rent_days: u32,
+    /// In years, one passes per day
+    pub(crate) age: u32,
+    pub(crate) sex: Sex,
+    /// The pop this one formed a household with
+    pub(crate) partner: Option<Entity>,
+    /// Ticks in a row spent with hunger at its maximum
+    pub(crate) starving_ticks: u32,
+    /// Ticks in a row spent without any energy
+    pub(crate) exhausted_ticks: u32,
     pub state: PopState,
 }
 
-#[derive(Default, Eq, PartialEq, Copy, Clone, Debug, Hash, Reflect)]
+impl Pop {
+    pub(crate) fn new(position: WorldPos, age: u32, sex: Sex, money: i32) -> Self {
+        Self {
+            money,
+            energy: 100,
+            position,
+            age,
+            sex,
+            ..default()
+        }
+    }
+
+    /// Only adults work, pay rent and form households
+    pub(crate) fn is_adult(&self) -> bool {
+        self.age >= ADULT_AGE
+    }
+
+    /// Whether the pop is on site at its job during a shift. Only that time is paid and productive.
+    pub(crate) fn is_at_work(&self, geometry: &MapGeometry) -> bool {
+        self.state == PopState::Working
+            && self.destination.is_none()
+            && self
+                .job
+                .as_ref()
+                .is_some_and(|job| self.position.distance(job.world_position(geometry)) <= TILE_SIZE)
+    }
+
+    /// Adults look for work until they retire
+    pub(crate) fn is_working_age(&self) -> bool {
+        self.is_adult() && self.age < RETIREMENT_AGE
+    }
+}
+
+#[derive(Default, Eq, PartialEq, Copy, Clone, Debug, Hash, Reflect, Serialize, Deserialize)]
 pub enum PopState {
     #[default]
     Idle,
@@ -64,42 +204,104 @@ pub enum PopState {
 
 #[derive(Component)]
 pub struct Job {
-    workplace: Entity,
-    salary: f32,
-    position: TilePos,
+    pub(crate) workplace: Entity,
+    /// Daily wage for a full shift on site
+    pub(crate) salary: f32,
+    pub(crate) position: TilePos,
+    /// Last day the pop put in time on site, employees who stay away too long are fired
+    pub(crate) last_worked_day: u64,
 }
 
 #[derive(Component)]
 pub struct Workplace {
     pub(crate) capacity: u32,
-    employees: Vec<Entity>,
-    position: TilePos,
+    pub(crate) employees: Vec<Entity>,
+    pub(crate) position: TilePos,
+    /// Daily wage offered to new hires and paid to all employees, see `review_wages`
+    pub(crate) wage: f32,
+    /// Cash the salaries are paid from, the workplace closes once it runs dry
+    pub(crate) treasury: i32,
+    pub(crate) product: Product,
+    /// Units produced but not sold yet
+    pub(crate) inventory: f32,
+    /// Money taken in since taxes were last collected
+    pub(crate) sales: i32,
+    /// Money taken in since the last wage review
+    pub(crate) daily_sales: i32,
 }
 
 #[derive(Component)]
 pub struct Restaurant {
+    /// Number of pops that can eat at the same time
     pub capacity: u32,
     pub position: TilePos,
+    /// Pops currently eating here, never more than `capacity`
+    pub(crate) diners: Vec<Entity>,
+    /// Pops that arrived while all seats were taken, seated first come first served
+    pub(crate) queue: VecDeque<Entity>,
+    /// Money collected from diners and not yet spent on supplies
+    pub(crate) revenue: i32,
+    /// Food bought from farms, every tick a diner eats uses some up
+    pub(crate) food: f32,
+    /// Goods bought from other workplaces, used up alongside the food
+    pub(crate) goods: f32,
+}
+
+impl Restaurant {
+    pub(crate) fn stock(&self, product: Product) -> f32 {
+        match product {
+            Product::Goods => self.goods,
+            Product::Food => self.food,
+        }
+    }
+
+    pub(crate) fn stock_mut(&mut self, product: Product) -> &mut f32 {
+        match product {
+            Product::Goods => &mut self.goods,
+            Product::Food => &mut self.food,
+        }
+    }
+
+    /// Whether there is enough of everything in stock for another tick of eating
+    pub(crate) fn can_serve(&self) -> bool {
+        Product::ALL
+            .into_iter()
+            .all(|product| self.stock(product) >= product.used_per_meal_tick())
+    }
+
+    /// What a diner pays per tick. The emptier the pantry, the higher the price,
+    /// up to `1 + MAX_MEAL_MARKUP` times the base price once the food is gone.
+    pub(crate) fn meal_price(&self) -> i32 {
+        let shortage = (1.0 - self.food / RESTAURANT_STOCK_TARGET).clamp(0.0, 1.0);
+        (MEAL_COST_PER_TICK as f32 * (1.0 + MAX_MEAL_MARKUP * shortage)).round() as i32
+    }
 }
 
 #[derive(Component)]
 pub struct House {
     pub(crate) capacity: u32,
-    residents: Vec<Entity>,
-    position: TilePos,
+    pub(crate) residents: Vec<Entity>,
+    pub(crate) position: TilePos,
+    /// Daily rent each resident pays, see `collect_rent`
+    pub(crate) rent: f32,
+    /// Rent paid since taxes were last collected
+    pub(crate) rent_collected: f32,
 }
 
-use crate::constants::{MAP_SIZE, POP_MOVE_SPEED, TILE_SIZE};
-
-// In your TileBasedEntity trait
-trait TileBasedEntity {
+use crate::constants::{
+    ADULT_AGE, BASE_RENT, BASE_WAGE, CHUNK_SIZE, COMMUTE_COST, EVICTION_DAYS, FIRM_STARTING_CAPITAL,
+    FOOD_PER_MEAL_TICK, HOUSE_CAPACITY, GOODS_PER_MEAL_TICK, JOB_SWITCH_MARGIN, LOCAL_LABOR_RADIUS, MAX_MEAL_MARKUP,
+    MAX_SPEED, MAX_STARTING_RESIDENTS, MAX_RENT, MAX_WAGE, MORNING_HOUR, MEAL_COST_PER_TICK, MIN_RENT, MIN_WAGE, POP_MOVE_SPEED, RENT_ADJUSTMENT_RATE,
+    RESTAURANT_STOCK_TARGET, RETIREMENT_AGE, SALES_WAGE_WEIGHT, TARGET_OCCUPANCY, TILE_SIZE, WAGE_ADJUSTMENT_RATE,
+};
+use crate::stats::median_wage;
+use crate::coords::{MapGeometry, WorldPos};
+
+/// Anything that sits on a single tile of the map
+pub(crate) trait TileBasedEntity {
     fn tile_position(&self) -> TilePos;
-    fn world_position(&self) -> Vec2 {
-        let pos = self.tile_position();
-        Vec2::new(
-            pos.x as f32 * TILE_SIZE + TILE_SIZE / 2.0,
-            pos.y as f32 * TILE_SIZE + TILE_SIZE / 2.0
-        )
+    fn world_position(&self, geometry: &MapGeometry) -> WorldPos {
+        geometry.tile_center(self.tile_position())
     }
 }
 
@@ -136,6 +338,8 @@ pub struct GameClock {
     pub hours_per_day: u64,
     pub speed: u32,
     pub paused: bool,
+    /// Tick at which the clock pauses itself, set by the time controls
+    pub stop_at: Option<u64>,
 }
 
 impl Default for GameClock {
@@ -146,6 +350,7 @@ impl Default for GameClock {
             hours_per_day: 24,
             speed: 1,
             paused: false,
+            stop_at: None,
         }
     }
 }
@@ -154,20 +359,67 @@ impl GameClock {
     pub fn tick(&mut self) {
         if !self.paused {
             self.current_tick += 1;
+            if self.stop_at.is_some_and(|stop_at| self.current_tick >= stop_at) {
+                self.paused = true;
+                self.stop_at = None;
+            }
+        }
+    }
+
+    /// Runs the clock until `tick` and pauses it there. Ticks that already passed are ignored.
+    pub fn run_until(&mut self, tick: u64) {
+        if tick > self.current_tick {
+            self.stop_at = Some(tick);
+            self.paused = false;
+        }
+    }
+
+    /// The first tick of `day`, days start at 1
+    pub fn start_of_day(&self, day: u64) -> u64 {
+        day.saturating_sub(1) * self.ticks_per_day()
+    }
+
+    /// The next tick at `MORNING_HOUR`, today's if it is still before then
+    pub fn next_morning(&self) -> u64 {
+        let morning = self.start_of_day(self.day()) + MORNING_HOUR * self.ticks_per_hour;
+        if morning > self.current_tick {
+            morning
+        } else {
+            morning + self.ticks_per_day()
         }
     }
 
+    pub fn ticks_per_day(&self) -> u64 {
+        self.ticks_per_hour * self.hours_per_day
+    }
+
+    pub fn ticks_per_shift(&self) -> u64 {
+        self.ticks_per_hour * 8
+    }
+
+    /// Whether the current tick is the first one of a day
+    pub fn is_day_start(&self) -> bool {
+        self.current_tick % self.ticks_per_day() == 0
+    }
+
+    /// Whether the current tick is the first one of an hour
+    pub fn is_hour_start(&self) -> bool {
+        self.current_tick % self.ticks_per_hour == 0
+    }
+
     pub fn day(&self) -> u64 {
-        self.current_tick / (self.ticks_per_hour * self.hours_per_day) + 1
+        self.current_tick / self.ticks_per_day() + 1
     }
 
     pub fn hour(&self) -> f64 {
-        (self.current_tick % (self.ticks_per_hour * self.hours_per_day)) as f64
+        (self.current_tick % self.ticks_per_day()) as f64
             / self.ticks_per_hour as f64
     }
 
+    /// Pausing also cancels running until a tick, unpausing runs on until paused again
     pub fn toggle_pause(&mut self) {
         self.paused = !self.paused;
+        self.stop_at = None;
     }
 
     pub fn ticks_per_second(&self) -> f64 {
@@ -175,20 +427,34 @@ impl GameClock {
     }
 }
 
-#[derive(Default)]
-enum Sex {
+#[derive(Default, Eq, PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum Sex {
     Male,
     Female,
     #[default]
     NonBinary,
 }
 
+impl Sex {
+    pub(crate) fn random(rng: &mut SimRng) -> Sex {
+        match rng.gen_range(0..100) {
+            0..=48 => Sex::Male,
+            49..=97 => Sex::Female,
+            _ => Sex::NonBinary,
+        }
+    }
+}
+
 #[derive(Bundle, Default)]
 struct PopBundle {
     pub pop: Pop,
     pub tile_bundle: TileBundle,
 }
 
+fn clock_is_running(game_clock: Res<GameClock>) -> bool {
+    !game_clock.paused
+}
+
 fn update_game_clock(mut game_clock: ResMut<GameClock>) {
     game_clock.tick();
 }
@@ -197,20 +463,20 @@ fn update_fixed_time(
     game_clock: Res<GameClock>,
     mut fixed_time: ResMut<Time<Fixed>>,
 ) {
-    if !game_clock.paused {
-        let tps = game_clock.ticks_per_second();
-        if tps.is_finite() {
-            fixed_time.set_timestep_hz(tps);
-        } else {
-            fixed_time.set_timestep_hz(0.0);
-        }
-    } else {
-        fixed_time.set_timestep_hz(0.0);
+    // A zero rate isn't a valid timestep, while paused the simulation doesn't run anyway
+    let tps = game_clock.ticks_per_second();
+    if !game_clock.paused && tps.is_finite() && tps > 0.0 {
+        fixed_time.set_timestep_hz(tps);
     }
 }
 
+/// Marks the tiles pops can't walk through, see [`crate::pathfinding::NavGrid`]
 #[derive(Component)]
 pub struct Building;
+
+/// A road tile, the cheapest tiles to walk on
+#[derive(Component)]
+pub struct Road;
 // src/tilemap.rs (continued)
 
 use bevy::math::{FloatOrd, Vec2};
@@ -218,82 +484,169 @@ use bevy::math::{FloatOrd, Vec2};
 
 fn move_pops(
     mut pop_query: Query<&mut Pop>,
-    tilemap_query: Query<&TilemapSize>,
+    geometry: Res<MapGeometry>,
 ) {
-    let map_size = tilemap_query.single();
-
     for mut pop in pop_query.iter_mut() {
         if let Some(destination) = pop.destination {
-            let to_destination = destination - pop.position;
-            let distance_to_destination = to_destination.length();
-
-            if distance_to_destination < POP_MOVE_SPEED {
-                // If we're closer than one step, just arrive at the destination
-                pop.position = destination;
-                pop.destination = None;
+            // Follow the planned path, if any, then head straight for the destination itself
+            let target = pop.waypoints.last().copied().unwrap_or(destination);
+            let to_target = target.0 - pop.position.0;
+            let distance_to_target = to_target.length();
+
+            if distance_to_target < POP_MOVE_SPEED {
+                // If we're closer than one step, just arrive at the waypoint or destination
+                pop.position = target;
+                if pop.waypoints.pop().is_none() {
+                    pop.destination = None;
+                }
             } else {
-                // Move in the direction of the destination by POP_MOVE_SPEED
-                let direction = to_destination.normalize();
-                pop.position += direction * POP_MOVE_SPEED;
+                // Move in the direction of the target by POP_MOVE_SPEED
+                let direction = to_target.normalize();
+                pop.position.0 += direction * POP_MOVE_SPEED;
             }
 
             // Clamp position to map boundaries
-            pop.position.x = pop.position.x.clamp(0.0, (map_size.x - 1) as f32 * TILE_SIZE);
-            pop.position.y = pop.position.y.clamp(0.0, (map_size.y - 1) as f32 * TILE_SIZE);
+            pop.position = geometry.clamp(pop.position);
         }
     }
 }
 
+/// Gives new pops a sprite and moves the sprites of pops that moved. Like the tiles, only the pops
+/// in chunks the camera sees are drawn, the others are hidden until the camera gets to them.
 fn render_pops(
     mut commands: Commands,
-    pop_query: Query<(Entity, &Pop)>,
+    new_pop_query: Query<(Entity, &Pop), Added<Pop>>,
+    mut pop_query: Query<(Ref<Pop>, &mut Transform, &mut Visibility)>,
+    camera_query: Query<(Ref<GlobalTransform>, Ref<OrthographicProjection>), With<Camera2d>>,
+    geometry: Option<Res<MapGeometry>>,
     asset_server: Res<AssetServer>,
 ) {
-    let pop_texture = asset_server.load("textures/pop.webp");
-    for (entity, pop) in pop_query.iter() {
-        commands.entity(entity).insert((
-            Transform::IDENTITY
-                .with_scale(Vec3::new(0.1, 0.1, 1.0))
-                .with_translation(Vec3::new(pop.position.x, pop.position.y, 1.0)),
-            Sprite {
-                image: pop_texture.clone(),
-                ..default()
-            },
-        ));
+    if !new_pop_query.is_empty() {
+        let pop_texture = asset_server.load("textures/pop.webp");
+        for (entity, pop) in new_pop_query.iter() {
+            commands.entity(entity).insert((
+                Transform::IDENTITY
+                    .with_scale(Vec3::new(0.1, 0.1, 1.0))
+                    .with_translation(Vec3::new(pop.position.x, pop.position.y, 1.0)),
+                Sprite {
+                    image: pop_texture.clone(),
+                    ..default()
+                },
+            ));
+        }
+    }
+    let Some(geometry) = geometry else {
+        return;
+    };
+    let mut visible_chunks = HashSet::new();
+    let mut view_moved = false;
+    for (camera_transform, projection) in camera_query.iter() {
+        view_moved |= camera_transform.is_changed() || projection.is_changed();
+        let center = camera_transform.translation().truncate();
+        let view = Rect::from_corners(projection.area.min + center, projection.area.max + center);
+        visible_chunks.extend(geometry.chunks_in(view));
+    }
+
+    // Pops change every tick, but only the sprites in view need moving.
+    // Pops that stand still are looked at again once the camera moves.
+    for (pop, mut transform, mut visibility) in pop_query.iter_mut() {
+        if !pop.is_changed() && !view_moved {
+            continue;
+        }
+        let in_view = geometry
+            .tile_at(pop.position)
+            .is_some_and(|tile| visible_chunks.contains(&MapGeometry::chunk_of(tile)));
+        if !in_view {
+            visibility.set_if_neq(Visibility::Hidden);
+            continue;
+        }
+        visibility.set_if_neq(Visibility::Inherited);
+        if transform.translation.truncate() != pop.position.0 {
+            transform.translation = Vec3::new(pop.position.x, pop.position.y, 1.0);
+        }
     }
 }
 
-fn spawn_tilemap(
+/// Generates a new city with the generator or map picked in [`NewCitySettings`].
+/// The tile texture is only loaded when an [`AssetServer`] exists,
+/// so headless runs get a tilemap without any rendering data.
+pub(crate) fn spawn_tilemap(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
+    asset_server: Option<Res<AssetServer>>,
+    seed: Res<SimSeed>,
+    settings: Res<NewCitySettings>,
 ) {
-    let tile_size = TilemapTileSize { x: TILE_SIZE, y: TILE_SIZE };
-    let map_size = MAP_SIZE;
+    let mut rng = SimRng::new(*seed);
+    let plan = settings.plan(&mut rng);
+
+    let map_size = plan.size();
+    let geometry = MapGeometry::new(map_size);
     let tilemap_entity = commands.spawn_empty().id();
     let mut tile_storage = TileStorage::empty(map_size);
-
-    let tile_image: Handle<Image> = asset_server.load("textures/tiles.png");
-
-    let mut rng = rand::thread_rng();
+    // A scenario's population is spread evenly over the houses, as far as they have room
+    let house_count = plan.count(Lot::House);
+    let mut houses_filled = 0;
 
     for x in 0..map_size.x {
         for y in 0..map_size.y {
             let tile_pos = TilePos { x, y };
-            let tile_entity = match rng.gen_range(0..100) {
-                0..=69 => spawn_empty_tile(&mut commands, tile_pos, tilemap_entity), // 70% empty
-                70..=84 => spawn_pop(&mut commands, tile_pos, tilemap_entity),       // 15% pop
-                85..=94 => spawn_house(&mut commands, tile_pos, tilemap_entity),     // 10% house
-                _ => spawn_workplace(&mut commands, tile_pos, tilemap_entity),       // 5% workplace
+            let tile_entity = match plan.lot(tile_pos) {
+                Lot::Empty => spawn_empty_tile(&mut commands, tile_pos, tilemap_entity),
+                Lot::Road => spawn_road(&mut commands, tile_pos, tilemap_entity),
+                Lot::Restaurant => spawn_restaurant(&mut commands, tile_pos, tilemap_entity),
+                Lot::Workplace => spawn_workplace(&mut commands, tile_pos, tilemap_entity),
+                Lot::Farm => spawn_farm(&mut commands, tile_pos, tilemap_entity),
+                Lot::House => {
+                    // Pops start out living in the houses
+                    let resident_count = match settings.starting_population {
+                        Some(population) => {
+                            let share = population / house_count + usize::from(houses_filled < population % house_count);
+                            houses_filled += 1;
+                            share.min(HOUSE_CAPACITY as usize)
+                        }
+                        None => rng.gen_range(0..=MAX_STARTING_RESIDENTS),
+                    };
+                    let residents: Vec<Entity> = (0..resident_count)
+                        .map(|_| commands.spawn_empty().id())
+                        .collect();
+                    let house = spawn_house_with_residents(&mut commands, tile_pos, tilemap_entity, residents.clone());
+                    for resident in residents {
+                        let age = rng.gen_range(ADULT_AGE..RETIREMENT_AGE);
+                        let sex = Sex::random(&mut rng);
+                        let mut pop = Pop::new(geometry.tile_center(tile_pos), age, sex, 100);
+                        pop.home = Some(house);
+                        commands.entity(resident).insert(pop);
+                    }
+                    house
+                }
             };
             tile_storage.set(&tile_pos, tile_entity);
         }
     }
 
-    let grid_size = tile_size.into();
+    insert_tilemap(&mut commands, tilemap_entity, map_size, tile_storage, asset_server);
+    commands.insert_resource(rng);
+}
+
+/// Turns `tilemap_entity` into the city's tilemap once all of its tiles are in `tile_storage`
+pub(crate) fn insert_tilemap(
+    commands: &mut Commands,
+    tilemap_entity: Entity,
+    map_size: TilemapSize,
+    tile_storage: TileStorage,
+    asset_server: Option<Res<AssetServer>>,
+) {
+    let tile_image: Handle<Image> = asset_server
+        .map(|asset_server| asset_server.load("textures/tiles.png"))
+        .unwrap_or_default();
+
+    let tile_size = TilemapTileSize { x: TILE_SIZE, y: TILE_SIZE };
+    let grid_size = MapGeometry::grid_size();
     let map_type = TilemapType::default();
-    let transform = get_tilemap_center_transform(&map_size, &grid_size, &map_type, 0.0);
+    let transform = MapGeometry::transform_for(map_size);
 
-    commands.entity(tilemap_entity).insert(TilemapBundle {
+    commands.insert_resource(MapGeometry::new(map_size));
+    commands.entity(tilemap_entity).insert((Tilemap, TilemapBundle {
         grid_size,
         map_type,
         size: map_size,
@@ -301,11 +654,48 @@ fn spawn_tilemap(
         texture: TilemapTexture::Single(tile_image),
         tile_size,
         transform,
+        render_settings: TilemapRenderSettings {
+            render_chunk_size: UVec2::splat(CHUNK_SIZE),
+            ..default()
+        },
         ..default()
-    });
+    }));
+}
+
+/// The tilemap, its tiles and buildings, and all pops
+pub(crate) type CityEntities = Or<(With<Tilemap>, With<TilePos>, With<Pop>)>;
+
+/// Removes the whole city from the world
+pub(crate) fn despawn_city(
+    mut commands: Commands,
+    city_query: Query<Entity, CityEntities>,
+) {
+    for entity in city_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Forgets the clock, markets, budget and history of the city that was left,
+/// so the next one starts from scratch. The seed and new city settings are kept.
+pub(crate) fn reset_simulation(
+    mut commands: Commands,
+    mut history: ResMut<StatsHistory>,
+    mut dirty: ResMut<DirtyChunks>,
+) {
+    commands.insert_resource(GameClock::default());
+    commands.insert_resource(JobMarket::default());
+    commands.insert_resource(HousingMarket::default());
+    commands.insert_resource(NavGrid::default());
+    commands.insert_resource(Demographics::default());
+    commands.insert_resource(Shipments::default());
+    commands.insert_resource(TaxPolicy::default());
+    commands.insert_resource(CityTreasury::default());
+    commands.remove_resource::<ActiveScenario>();
+    history.clear();
+    dirty.clear();
 }
 
-fn spawn_empty_tile(commands: &mut Commands, tile_pos: TilePos, tilemap_entity: Entity) -> Entity {
+pub(crate) fn spawn_empty_tile(commands: &mut Commands, tile_pos: TilePos, tilemap_entity: Entity) -> Entity {
     commands
         .spawn(TileBundle {
             position: tile_pos,
@@ -316,35 +706,40 @@ fn spawn_empty_tile(commands: &mut Commands, tile_pos: TilePos, tilemap_entity:
         .id()
 }
 
-fn spawn_pop(commands: &mut Commands, tile_pos: TilePos, tilemap_entity: Entity) -> Entity {
-    let world_pos = Vec2::new(
-        tile_pos.x as f32 + 0.5, // Add 0.5 to center within the tile
-        tile_pos.y as f32 + 0.5  // Add 0.5 to center within the tile
-    );
-
+pub(crate) fn spawn_road(commands: &mut Commands, tile_pos: TilePos, tilemap_entity: Entity) -> Entity {
     commands
         .spawn((
-            Pop {
-                money: 100,
-                hunger: 0,
-                energy: 100,
-                job: None,
-                home: None,
-                position: world_pos,
-                destination: None,
-                state: PopState::Idle,
+            Road,
+            TileBundle {
+                position: tile_pos,
+                tilemap_id: TilemapId(tilemap_entity),
+                texture_index: TileTextureIndex(5),
+                ..default()
             },
         ))
         .id()
 }
 
-fn spawn_house(commands: &mut Commands, tile_pos: TilePos, tilemap_entity: Entity) -> Entity {
+pub(crate) fn spawn_house(commands: &mut Commands, tile_pos: TilePos, tilemap_entity: Entity) -> Entity {
+    spawn_house_with_residents(commands, tile_pos, tilemap_entity, Vec::new())
+}
+
+/// Spawns a house that `residents` already live in; their `Pop::home` must point to it
+fn spawn_house_with_residents(
+    commands: &mut Commands,
+    tile_pos: TilePos,
+    tilemap_entity: Entity,
+    residents: Vec<Entity>,
+) -> Entity {
     commands
         .spawn((
+            Building,
             House {
-                capacity: 4,
-                residents: Vec::new(),
+                capacity: HOUSE_CAPACITY,
+                residents,
                 position: tile_pos,
+                rent: BASE_RENT,
+                rent_collected: 0.0,
             },
             TileBundle {
                 position: tile_pos,
@@ -356,18 +751,38 @@ fn spawn_house(commands: &mut Commands, tile_pos: TilePos, tilemap_entity: Entit
         .id()
 }
 
-fn spawn_workplace(commands: &mut Commands, tile_pos: TilePos, tilemap_entity: Entity) -> Entity {
+pub(crate) fn spawn_workplace(commands: &mut Commands, tile_pos: TilePos, tilemap_entity: Entity) -> Entity {
+    spawn_producer(commands, tile_pos, tilemap_entity, Product::Goods)
+}
+
+pub(crate) fn spawn_farm(commands: &mut Commands, tile_pos: TilePos, tilemap_entity: Entity) -> Entity {
+    spawn_producer(commands, tile_pos, tilemap_entity, Product::Food)
+}
+
+fn spawn_producer(
+    commands: &mut Commands,
+    tile_pos: TilePos,
+    tilemap_entity: Entity,
+    product: Product,
+) -> Entity {
     commands
         .spawn((
+            Building,
             Workplace {
                 capacity: 10,
                 employees: Vec::new(),
                 position: tile_pos,
+                wage: BASE_WAGE,
+                treasury: FIRM_STARTING_CAPITAL,
+                product,
+                inventory: 0.0,
+                sales: 0,
+                daily_sales: 0,
             },
             TileBundle {
                 position: tile_pos,
                 tilemap_id: TilemapId(tilemap_entity),
-                texture_index: TileTextureIndex(3), // Assuming 3 is the workplace texture
+                texture_index: TileTextureIndex(product.texture_index()),
                 ..default()
             },
         ))
@@ -375,24 +790,29 @@ fn spawn_workplace(commands: &mut Commands, tile_pos: TilePos, tilemap_entity: E
 }
 
 fn find_nearest_restaurant(
-    pop_position: Vec2,
-    restaurant_query: &Query<&Restaurant>,
-) -> Option<Vec2> {
+    pop_position: WorldPos,
+    restaurant_query: &Query<(Entity, &Restaurant)>,
+    geometry: &MapGeometry,
+) -> Option<(Entity, WorldPos)> {
     restaurant_query
         .iter()
-        .min_by_key(|restaurant| {
-            let restaurant_pos = Vec2::new(restaurant.position.x as f32, restaurant.position.y as f32);
-            FloatOrd(pop_position.distance_squared(restaurant_pos))
-        })
-        .map(|restaurant| Vec2::new(restaurant.position.x as f32, restaurant.position.y as f32))
+        .filter(|(_, restaurant)| restaurant.can_serve())
+        .map(|(entity, restaurant)| (entity, restaurant.world_position(geometry)))
+        .min_by_key(|(_, restaurant_pos)| FloatOrd(pop_position.distance_squared(*restaurant_pos)))
 }
 
-fn spawn_restaurant(commands: &mut Commands, tile_pos: TilePos, tilemap_entity: Entity) -> Entity {
+pub(crate) fn spawn_restaurant(commands: &mut Commands, tile_pos: TilePos, tilemap_entity: Entity) -> Entity {
     commands
         .spawn((
+            Building,
             Restaurant {
                 capacity: 20,
                 position: tile_pos,
+                diners: Vec::new(),
+                queue: VecDeque::new(),
+                revenue: 0,
+                food: RESTAURANT_STOCK_TARGET,
+                goods: RESTAURANT_STOCK_TARGET,
             },
             TileBundle {
                 position: tile_pos,
@@ -408,42 +828,67 @@ fn spawn_restaurant(commands: &mut Commands, tile_pos: TilePos, tilemap_entity:
 // Modify the update_pops function
 fn update_pops(
     game_clock: Res<GameClock>,
-    mut pop_query: Query<&mut Pop>,
+    mut rng: ResMut<SimRng>,
+    mut pop_query: Query<(Entity, &mut Pop)>,
     house_query: Query<&House>,
-    workplace_query: Query<&Workplace>,
-    restaurant_query: Query<&Restaurant>,
-    tilemap_query: Query<&TilemapSize>,
+    mut workplace_query: Query<&mut Workplace>,
+    restaurant_query: Query<(Entity, &Restaurant)>,
+    geometry: Res<MapGeometry>,
 ) {
-    let map_size = tilemap_query.single();
-    for mut pop in pop_query.iter_mut() {
+    // Query order is not stable across runs, so visit pops by entity before drawing random numbers
+    let mut pops: Vec<_> = pop_query.iter_mut().collect();
+    pops.sort_unstable_by_key(|(entity, _)| *entity);
+    for (_, mut pop) in pops {
         // Increase hunger and decrease energy every tick
         pop.hunger = pop.hunger.saturating_add(1);
         pop.energy = pop.energy.saturating_sub(1);
+        let was_working = pop.state == PopState::Working;
 
         // Update pop state and destination based on needs and time of day
         if pop.hunger > 7000 && pop.state != PopState::Eating {
             pop.state = PopState::Eating;
-            pop.destination = find_nearest_restaurant(pop.position, &restaurant_query);
+            let restaurant = find_nearest_restaurant(pop.position, &restaurant_query, &geometry);
+            pop.restaurant = restaurant.map(|(entity, _)| entity);
+            pop.destination = restaurant.map(|(_, position)| position);
         } else if pop.energy < 2000 && pop.state != PopState::Sleeping {
             pop.state = PopState::Sleeping;
-            pop.destination = pop.home.and_then(|home| house_query.get(home).ok().map(|h| h.world_position()));
-        } else if pop.state != PopState::Working && game_clock.hour() >= 9.0 && game_clock.hour() < 17.0 {
+            pop.destination = pop.home.and_then(|home| house_query.get(home).ok().map(|h| h.world_position(&geometry)));
+        } else if pop.state == PopState::Idle && game_clock.hour() >= 9.0 && game_clock.hour() < 17.0 {
+            // Pops finish their meal or their sleep before heading to work
             pop.state = PopState::Working;
-            pop.destination = pop.job.as_ref().map(|job| job.world_position());
+            pop.destination = pop.job.as_ref().map(|job| job.world_position(&geometry));
         } else if pop.state == PopState::Working && (game_clock.hour() < 9.0 || game_clock.hour() >= 17.0) {
             pop.state = PopState::Idle;
             pop.destination = None;
         }
 
+        // Pay for the time spent on site once the shift is over, as far as the workplace can afford it
+        if was_working && pop.state != PopState::Working {
+            let pop = &mut *pop;
+            if let Some(job) = pop.job.as_mut() {
+                let pay = job.salary * pop.shift_ticks as f32 / game_clock.ticks_per_shift() as f32;
+                if pop.shift_ticks > 0 {
+                    job.last_worked_day = game_clock.day();
+                }
+                if let Ok(mut workplace) = workplace_query.get_mut(job.workplace) {
+                    let pay = (pay.round() as i32).min(workplace.treasury.max(0));
+                    workplace.treasury -= pay;
+                    pop.money = pop.money.saturating_add(pay);
+                    pop.earnings = pop.earnings.saturating_add(pay);
+                }
+            }
+            pop.shift_ticks = 0;
+        }
+
         // Handle actions based on state
         match pop.state {
             PopState::Eating => {
-                if pop.hunger > 0 {
-                    pop.hunger = pop.hunger.saturating_sub(20);
-                    pop.money = pop.money.saturating_sub(1); // Cost of food
-                } else {
-                    pop.state = PopState::Idle;
-                    pop.destination = None;
+                // Pops only eat once seated in a restaurant, see `serve_restaurants`.
+                // While every restaurant is out of stock they keep looking for one that isn't.
+                if pop.restaurant.is_none() {
+                    let restaurant = find_nearest_restaurant(pop.position, &restaurant_query, &geometry);
+                    pop.restaurant = restaurant.map(|(entity, _)| entity);
+                    pop.destination = restaurant.map(|(_, position)| position);
                 }
             }
             PopState::Sleeping => {
@@ -456,22 +901,22 @@ fn update_pops(
                 pop.hunger = pop.hunger.saturating_add(1);
             }
             PopState::Working => {
-                if let Some(job) = &pop.job {
-                    pop.money = pop.money.saturating_add((job.salary / (game_clock.ticks_per_hour * 8) as f32) as i32); // Assuming 8-hour workday
+                if pop.job.is_some() {
+                    // Only the time at the workplace counts, commuting is unpaid
+                    if pop.is_at_work(&geometry) {
+                        pop.shift_ticks += 1;
+                    }
                     pop.energy = pop.energy.saturating_sub(1);
                 }
             }
             PopState::Idle => {
-                // Update destination setting to use Vec2
+                // Wander up to two and a half tiles in any direction
                 if pop.destination.is_none() {
                     let random_offset = Vec2::new(
-                        rand::random::<f32>() * 5.0,
-                        rand::random::<f32>() * 5.0
+                        rng.gen::<f32>() * 5.0 - 2.5,
+                        rng.gen::<f32>() * 5.0 - 2.5
                     );
-                    pop.destination = Some(Vec2::new(
-                        (pop.position.x + random_offset.x - 2.0).clamp(0.0, (map_size.x - 1) as f32),
-                        (pop.position.y + random_offset.y - 2.0).clamp(0.0, (map_size.y - 1) as f32),
-                    ));
+                    pop.destination = Some(geometry.clamp(WorldPos(pop.position.0 + random_offset * TILE_SIZE)));
                 }
                 // Idle state consumes energy and increases hunger slightly
                 pop.energy = pop.energy.saturating_sub(1);
@@ -485,87 +930,233 @@ fn update_pops(
     }
 }
 
-use bevy_ecs_tilemap::prelude::*;
-
-fn update_pop_visuals(
-    mut commands: Commands,
-    mut tilemap_query: Query<(Entity, &mut TileStorage)>,
-    pop_query: Query<(Entity, &TilePos), With<Pop>>,
-    house_query: Query<(Entity, &TilePos), With<House>>,
-    workplace_query: Query<(Entity, &TilePos), With<Workplace>>,
+/// Seats hungry pops that reached their restaurant, feeds the seated ones and charges them for it.
+/// Once a restaurant runs out of food or goods its diners have to look elsewhere.
+fn serve_restaurants(
+    mut pop_query: Query<(Entity, &mut Pop)>,
+    mut restaurant_query: Query<(Entity, &mut Restaurant)>,
+    geometry: Res<MapGeometry>,
 ) {
-    if let Ok((tilemap_entity, mut tile_storage)) = tilemap_query.get_single_mut() {
-        // Reset all tiles to empty
-        for x in 0..tile_storage.size.x {
-            for y in 0..tile_storage.size.y {
-                let tile_pos = TilePos { x, y };
-                if let Some(tile_entity) = tile_storage.get(&tile_pos) {
-                    commands.entity(tile_entity).insert(TileTextureIndex(0)); // Empty tile texture
-                }
-            }
-        }
+    // Free the seats and queue spots of pops that went elsewhere
+    for (restaurant_entity, mut restaurant) in restaurant_query.iter_mut() {
+        let eats_here = |diner: &Entity| {
+            pop_query.get(*diner).is_ok_and(|(_, pop)| {
+                pop.state == PopState::Eating && pop.restaurant == Some(restaurant_entity)
+            })
+        };
+        restaurant.diners.retain(eats_here);
+        restaurant.queue.retain(eats_here);
+    }
 
-        // Helper function to safely update tile texture
-        let mut update_tile_texture = |tile_pos: &TilePos, texture_index: u32| {
-            if tile_pos.x < tile_storage.size.x && tile_pos.y < tile_storage.size.y {
-                if let Some(tile_entity) = tile_storage.get(tile_pos) {
-                    commands.entity(tile_entity).insert(TileTextureIndex(texture_index));
-                }
+    // Line up pops that arrived at their restaurant
+    let mut pops: Vec<_> = pop_query.iter_mut().collect();
+    pops.sort_unstable_by_key(|(entity, _)| *entity);
+    for (pop_entity, mut pop) in pops {
+        if pop.state != PopState::Eating {
+            if pop.restaurant.is_some() {
+                pop.restaurant = None;
             }
+            continue;
+        }
+        let Some(restaurant_entity) = pop.restaurant else {
+            continue;
         };
-
-        // Set pop positions
-        for (_, tile_pos) in pop_query.iter() {
-            update_tile_texture(tile_pos, 1);
+        let Ok((_, mut restaurant)) = restaurant_query.get_mut(restaurant_entity) else {
+            pop.restaurant = None;
+            continue;
+        };
+        if restaurant.diners.contains(&pop_entity) || restaurant.queue.contains(&pop_entity) {
+            continue;
+        }
+        let restaurant_position = restaurant.world_position(&geometry);
+        if pop.position.distance(restaurant_position) > TILE_SIZE {
+            // Still on the way, or sent somewhere else in the meantime
+            if pop.destination.is_none() {
+                pop.destination = Some(restaurant_position);
+            }
+            continue;
         }
+        restaurant.queue.push_back(pop_entity);
+    }
 
-        // Set house positions
-        for (_, tile_pos) in house_query.iter() {
-            update_tile_texture(tile_pos, 2);
+    for (_, mut restaurant) in restaurant_query.iter_mut() {
+        let restaurant = &mut *restaurant;
+        while restaurant.diners.len() < restaurant.capacity as usize {
+            let Some(next) = restaurant.queue.pop_front() else {
+                break;
+            };
+            restaurant.diners.push(next);
         }
 
-        // Set workplace positions
-        for (_, tile_pos) in workplace_query.iter() {
-            update_tile_texture(tile_pos, 3);
+        let mut revenue = 0;
+        let price = restaurant.meal_price();
+        let (mut food, mut goods) = (restaurant.food, restaurant.goods);
+        restaurant.diners.retain(|diner| {
+            let Ok((_, mut pop)) = pop_query.get_mut(*diner) else {
+                return false;
+            };
+            if pop.hunger > 0 && food >= FOOD_PER_MEAL_TICK && goods >= GOODS_PER_MEAL_TICK {
+                food -= FOOD_PER_MEAL_TICK;
+                goods -= GOODS_PER_MEAL_TICK;
+                pop.hunger = pop.hunger.saturating_sub(20);
+                pop.money = pop.money.saturating_sub(price);
+                revenue += price;
+                true
+            } else {
+                pop.state = PopState::Idle;
+                pop.destination = None;
+                pop.restaurant = None;
+                false
+            }
+        });
+        restaurant.revenue = restaurant.revenue.saturating_add(revenue);
+        restaurant.food = food;
+        restaurant.goods = goods;
+    }
+}
+
+use bevy_ecs_tilemap::prelude::*;
+
+/// Chunks whose tiles may show the wrong texture
+#[derive(Resource, Default)]
+pub(crate) struct DirtyChunks(HashSet<UVec2>);
+
+impl DirtyChunks {
+    pub(crate) fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+/// Every placed tile dirties its chunk, whether it was built, bulldozed, left by a bankrupt firm
+/// or part of a new city
+fn mark_dirty_chunks(mut dirty: ResMut<DirtyChunks>, tile_query: Query<&TilePos, Added<TilePos>>) {
+    dirty.0.extend(tile_query.iter().map(|tile| MapGeometry::chunk_of(*tile)));
+}
+
+/// Redraws the dirty chunks in view from the buildings on their tiles.
+/// Chunks off screen stay dirty until the camera gets to them.
+fn redraw_dirty_chunks(
+    mut dirty: ResMut<DirtyChunks>,
+    geometry: Option<Res<MapGeometry>>,
+    camera_query: Query<(&GlobalTransform, &OrthographicProjection), With<Camera2d>>,
+    tilemap_query: Query<&TileStorage, With<Tilemap>>,
+    mut tile_query: Query<(&mut TileTextureIndex, Option<&Workplace>, Has<House>, Has<Restaurant>, Has<Road>)>,
+) {
+    if dirty.0.is_empty() {
+        return;
+    }
+    let (Some(geometry), Ok(tile_storage)) = (geometry, tilemap_query.get_single()) else {
+        return;
+    };
+
+    for (camera_transform, projection) in camera_query.iter() {
+        let center = camera_transform.translation().truncate();
+        let view = Rect::from_corners(projection.area.min + center, projection.area.max + center);
+        for chunk in geometry.chunks_in(view) {
+            if !dirty.0.remove(&chunk) {
+                continue;
+            }
+            for tile_pos in geometry.chunk_tiles(chunk) {
+                let Some(tile_entity) = tile_storage.get(&tile_pos) else {
+                    continue;
+                };
+                let Ok((mut texture, workplace, house, restaurant, road)) = tile_query.get_mut(tile_entity) else {
+                    continue;
+                };
+                let index = match (workplace, house, restaurant, road) {
+                    (Some(workplace), ..) => workplace.product.texture_index(),
+                    (_, true, ..) => 2,
+                    (_, _, true, _) => 4,
+                    (.., true) => 5,
+                    _ => 0, // Empty tile texture
+                };
+                // Only touch tiles that are wrong, so the renderer doesn't re-upload the chunk
+                if texture.0 != index {
+                    texture.0 = index;
+                }
+            }
         }
     }
 }
 
 fn assign_jobs_and_housing(
-    mut commands: Commands,
     mut pop_query: Query<(Entity, &mut Pop)>,
+    geometry: Res<MapGeometry>,
+    game_clock: Res<GameClock>,
     mut job_market: ResMut<JobMarket>,
     mut housing_market: ResMut<HousingMarket>,
     mut workplace_query: Query<&mut Workplace>,
     mut house_query: Query<&mut House>,
+    mut nav_grid: ResMut<NavGrid>,
 ) {
+    // Weighing every offer means path searches, so pops without a job or home look on the hour
+    if !game_clock.is_hour_start() {
+        return;
+    }
+    // Employed pops compare their job with the open offers once a day
+    let job_hunting = game_clock.is_day_start();
     for (pop_entity, mut pop) in pop_query.iter_mut() {
-        // Assign job if unemployed
-        if pop.job.is_none() {
-            if let Some((workplace_entity, salary, position)) = job_market.available_jobs.pop() {
-                pop.job = Some(Job {
-                    workplace: workplace_entity,
-                    salary,
-                    position,
-                });
-                // Update workplace
+        if pop.is_working_age() && (pop.job.is_none() || job_hunting) {
+            let commute_from = pop
+                .home
+                .and_then(|home| house_query.get(home).ok())
+                .map(|house| house.position)
+                .or_else(|| geometry.tile_at(pop.position));
+            let distances = commute_from.map(|from| nav_grid.distances_from(from));
+            // A job is worth its wage minus what the commute costs, unreachable jobs are worthless
+            let job_value = |wage: f32, position: TilePos| -> Option<f32> {
+                let cost = distances?.cost_to(position)?;
+                Some(wage - cost as f32 * COMMUTE_COST)
+            };
+            let current = pop.job.as_ref().map(|job| {
+                let value = job_value(job.salary, job.position).unwrap_or(f32::MIN);
+                (job.workplace, value)
+            });
+            let best_offer = job_market
+                .available_jobs
+                .iter()
+                .enumerate()
+                .filter(|(_, (workplace, ..))| Some(*workplace) != current.map(|(current, _)| current))
+                .filter_map(|(index, (_, wage, position))| Some((job_value(*wage, *position)?, index)))
+                .max_by(|(a, _), (b, _)| a.total_cmp(b));
+            let better_offer = best_offer.filter(|(value, _)| match current {
+                Some((_, current_value)) => *value > current_value + JOB_SWITCH_MARGIN,
+                None => true,
+            });
+
+            if let Some((_, index)) = better_offer {
+                let (workplace_entity, ..) = job_market.available_jobs.remove(index);
+                // Quit the old job, shifts are already paid out since nobody works at midnight
+                leave_job(pop_entity, &mut pop, &mut workplace_query);
                 if let Ok(mut workplace) = workplace_query.get_mut(workplace_entity) {
-                    workplace.employees.push(pop_entity);
+                    hire(pop_entity, &mut pop, workplace_entity, &mut workplace, game_clock.day());
                 }
             }
         }
 
-        // Assign home if homeless
+        // Move into the home with the lowest rent plus commute that the pop can afford
         if pop.home.is_none() {
-            if let Some((house_entity, tile_position)) = housing_market.available_houses.pop() {
-                pop.home = Some(house_entity);
-                pop.destination = Some(Vec2::new(
-                    tile_position.x as f32 * TILE_SIZE + TILE_SIZE / 2.0,
-                    tile_position.y as f32 * TILE_SIZE + TILE_SIZE / 2.0
-                ));
-                // Update house
+            // Homes and workplaces are both buildings, so the way to work costs what the way back does
+            let distances = pop.job.as_ref().map(|job| nav_grid.distances_from(job.position));
+            let home_cost = |rent: f32, position: TilePos| -> Option<f32> {
+                let commute = match distances {
+                    Some(distances) => distances.cost_to(position)? as f32 * COMMUTE_COST,
+                    None => 0.0,
+                };
+                Some(rent + commute)
+            };
+            let best_home = housing_market
+                .available_houses
+                .iter()
+                .enumerate()
+                .filter(|(_, (_, rent, _))| pop.money as f32 >= *rent)
+                .filter_map(|(index, (_, rent, position))| Some((home_cost(*rent, *position)?, index)))
+                .min_by(|(a, _), (b, _)| a.total_cmp(b));
+            if let Some((_, index)) = best_home {
+                let (house_entity, _, tile_position) = housing_market.available_houses.remove(index);
                 if let Ok(mut house) = house_query.get_mut(house_entity) {
-                    house.residents.push(pop_entity);
+                    move_in(pop_entity, &mut pop, house_entity, &mut house);
+                    pop.destination = Some(geometry.tile_center(tile_position));
                 }
             }
         }
@@ -575,14 +1166,149 @@ fn assign_jobs_and_housing(
 #[derive(Resource, Default)]
 pub struct JobMarket {
     pub available_jobs: Vec<(Entity, f32, TilePos)>, // (Workplace, Salary, Position)
+    /// Median salary of the employed pops at the latest daily wage review
+    pub median_wage: f32,
+    /// Median salary at the review before that
+    pub previous_median_wage: f32,
+}
+
+impl JobMarket {
+    /// How much the median wage changed over the last day, 0 until there were two reviews with employed pops
+    pub fn wage_trend(&self) -> f32 {
+        if self.previous_median_wage > 0.0 {
+            self.median_wage - self.previous_median_wage
+        } else {
+            0.0
+        }
+    }
 }
 
 #[derive(Resource, Default)]
 pub struct HousingMarket {
-    pub available_houses: Vec<(Entity, TilePos)>, // (House, Position)
+    pub available_houses: Vec<(Entity, f32, TilePos)>, // (House, Rent, Position)
 }
 
-// src/tilemap.rs
+/// Once a day every workplace reviews its wage. It raises it while positions stay vacant or sales
+/// run ahead of the payroll, and cuts it when many pops around it are looking for work or sales
+/// fall short, then pays everyone the new wage.
+/// Workplaces that can't cover another day of salaries cut wages no matter what.
+fn review_wages(
+    game_clock: Res<GameClock>,
+    geometry: Res<MapGeometry>,
+    mut job_market: ResMut<JobMarket>,
+    mut workplace_query: Query<&mut Workplace>,
+    mut pop_query: Query<&mut Pop>,
+) {
+    if !game_clock.is_day_start() {
+        return;
+    }
+
+    // Pops and unemployed pops per tile, counted once for all workplaces
+    let size = geometry.size;
+    let mut labor = vec![(0u32, 0u32); (size.x * size.y) as usize];
+    for pop in pop_query.iter() {
+        if let Some(tile) = geometry.tile_at(pop.position) {
+            let (pops, unemployed) = &mut labor[(tile.y * size.x + tile.x) as usize];
+            *pops += 1;
+            *unemployed += pop.job.is_none() as u32;
+        }
+    }
+
+    let reach = LOCAL_LABOR_RADIUS as u32;
+    for mut workplace in workplace_query.iter_mut() {
+        let center = workplace.position;
+        let (mut nearby, mut unemployed) = (0, 0);
+        for y in center.y.saturating_sub(reach)..=(center.y + reach).min(size.y - 1) {
+            for x in center.x.saturating_sub(reach)..=(center.x + reach).min(size.x - 1) {
+                let (dx, dy) = (x.abs_diff(center.x) as f32, y.abs_diff(center.y) as f32);
+                if dx * dx + dy * dy <= LOCAL_LABOR_RADIUS * LOCAL_LABOR_RADIUS {
+                    let (pops, looking) = labor[(y * size.x + x) as usize];
+                    nearby += pops;
+                    unemployed += looking;
+                }
+            }
+        }
+        let unemployment = if nearby > 0 {
+            unemployed as f32 / nearby as f32
+        } else {
+            0.0
+        };
+        let vacancies = workplace.capacity.saturating_sub(workplace.employees.len() as u32) as f32
+            / workplace.capacity.max(1) as f32;
+
+        let payroll = workplace.wage * workplace.employees.len() as f32;
+        // -1 without sales, 0 when they just cover the payroll, at most 1 for twice that
+        let sales = if payroll > 0.0 {
+            (workplace.daily_sales as f32 / payroll - 1.0).clamp(-1.0, 1.0)
+        } else {
+            0.0
+        };
+        workplace.daily_sales = 0;
+        let adjustment = if (workplace.treasury as f32) < payroll {
+            1.0 - WAGE_ADJUSTMENT_RATE
+        } else {
+            let pressure = vacancies - unemployment + SALES_WAGE_WEIGHT * sales;
+            1.0 + WAGE_ADJUSTMENT_RATE * pressure.clamp(-1.0, 1.0)
+        };
+        let wage = (workplace.wage * adjustment).clamp(MIN_WAGE, MAX_WAGE);
+        workplace.wage = wage;
+        for employee in &workplace.employees {
+            if let Ok(mut pop) = pop_query.get_mut(*employee) {
+                if let Some(job) = pop.job.as_mut() {
+                    job.salary = wage;
+                }
+            }
+        }
+    }
+
+    job_market.previous_median_wage = job_market.median_wage;
+    job_market.median_wage = median_wage(pop_query.iter());
+}
+
+/// Once a day residents pay their rent. Pops that can't pay fall behind and are evicted once they
+/// are `EVICTION_DAYS` days in arrears. Landlords then raise the rent of houses that are fuller
+/// than `TARGET_OCCUPANCY` and lower it for emptier ones.
+fn collect_rent(
+    game_clock: Res<GameClock>,
+    mut house_query: Query<&mut House>,
+    mut pop_query: Query<&mut Pop>,
+) {
+    if !game_clock.is_day_start() {
+        return;
+    }
+
+    for mut house in house_query.iter_mut() {
+        let rent = house.rent.round() as i32;
+        let mut collected = 0;
+        house.residents.retain(|resident| {
+            let Ok(mut pop) = pop_query.get_mut(*resident) else {
+                return false;
+            };
+            if !pop.is_adult() {
+                // Children live with their parents for free
+                return true;
+            }
+            if pop.money >= rent {
+                pop.money -= rent;
+                pop.unpaid_rent_days = 0;
+                collected += rent;
+                return true;
+            }
+            pop.unpaid_rent_days += 1;
+            if pop.unpaid_rent_days < EVICTION_DAYS {
+                return true;
+            }
+            pop.home = None;
+            pop.unpaid_rent_days = 0;
+            false
+        });
+        house.rent_collected += collected as f32;
+
+        let occupancy = house.residents.len() as f32 / house.capacity.max(1) as f32;
+        let adjustment = 1.0 + RENT_ADJUSTMENT_RATE * (occupancy - TARGET_OCCUPANCY);
+        house.rent = (house.rent * adjustment).clamp(MIN_RENT, MAX_RENT);
+    }
+}
 
 fn manage_markets(
     mut job_market: ResMut<JobMarket>,
@@ -594,14 +1320,14 @@ fn manage_markets(
     for (entity, workplace) in workplace_query.iter() {
         let available_positions = workplace.capacity as i32 - workplace.employees.len() as i32;
         if available_positions > 0 {
-            job_market.available_jobs.push((entity, 75.0, workplace.position));
+            job_market.available_jobs.push((entity, workplace.wage, workplace.position));
         }
     }
 
     housing_market.available_houses.clear();
     for (entity, house) in house_query.iter() {
         if house.residents.len() < house.capacity as usize {
-            housing_market.available_houses.push((entity, house.tile_position()));
+            housing_market.available_houses.push((entity, house.rent, house.tile_position()));
         }
     }
 }
@@ -616,8 +1342,8 @@ fn handle_speed_input(
         game_clock.speed = speed.saturating_div(2).max(1);
     }
     if keyboard_input.just_pressed(KeyCode::KeyX) {
-        // Increase speed, with some upper limit (e.g., 10)
-        game_clock.speed = speed.saturating_mul(2).min(512);
+        // Increase speed, up to MAX_SPEED
+        game_clock.speed = speed.saturating_mul(2).min(MAX_SPEED);
     }
     if keyboard_input.just_pressed(KeyCode::Space) {
         game_clock.toggle_pause();
@@ -627,18 +1353,313 @@ fn handle_speed_input(
 #[cfg(test)]
 mod tests {
     use proptest::prelude::*;
+    use bevy::ecs::schedule::ScheduleLabel;
     use bevy::prelude::*;
-    use bevy_ecs_tilemap::map::TilemapSize;
-    use crate::tilemap::{move_pops, Pop};
-    use crate::constants::{TILE_SIZE, MAP_SIZE, POP_MOVE_SPEED};
+    use crate::coords::{MapGeometry, WorldPos};
+    use crate::rng::{SimRng, SimSeed};
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy_ecs_tilemap::prelude::*;
+    use crate::tilemap::{
+        collect_rent, despawn_city, move_pops, reset_simulation, review_wages, serve_restaurants,
+        spawn_tilemap, update_pops, DirtyChunks, GameClock, House, Job, JobMarket, Pop, PopState,
+        Restaurant, SimulationPlugin, Tilemap, Workplace,
+    };
+    use crate::firms::Product;
+    use crate::constants::{
+        ADULT_AGE, BASE_RENT, BASE_WAGE, EVICTION_DAYS, FIRM_STARTING_CAPITAL, TILE_SIZE, DEFAULT_MAP_SIZE,
+        POP_MOVE_SPEED,
+    };
+
+    fn run_city(seed: u64, ticks: u32) -> Vec<(Entity, Vec2, i32, u32, u32, PopState)> {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(SimSeed(seed))
+            .add_plugins(SimulationPlugin { schedule: Update.intern() })
+            .add_systems(Startup, spawn_tilemap);
+
+        for _ in 0..ticks {
+            app.update();
+        }
+
+        let world = app.world_mut();
+        let mut pops: Vec<_> = world
+            .query::<(Entity, &Pop)>()
+            .iter(world)
+            .map(|(entity, pop)| (entity, pop.position.0, pop.money, pop.hunger, pop.energy, pop.state))
+            .collect();
+        pops.sort_unstable_by_key(|(entity, ..)| *entity);
+        pops
+    }
+
+    #[test]
+    fn test_same_seed_reproduces_city() {
+        let first = run_city(42, 600);
+        assert!(!first.is_empty(), "The city should have pops");
+        assert_eq!(first, run_city(42, 600));
+    }
+
+    #[test]
+    fn test_clock_runs_until_the_tick_it_was_asked_to_stop_at() {
+        let mut game_clock = GameClock::default();
+        game_clock.paused = true;
+        game_clock.run_until(game_clock.current_tick + 1);
+        game_clock.tick();
+        game_clock.tick();
+        assert_eq!(game_clock.current_tick, 1);
+        assert!(game_clock.paused);
+
+        // Before 6:00 the next morning is today's, after it tomorrow's
+        assert_eq!(game_clock.next_morning(), 6 * game_clock.ticks_per_hour);
+        game_clock.current_tick = 7 * game_clock.ticks_per_hour;
+        let tomorrow = game_clock.ticks_per_day() + 6 * game_clock.ticks_per_hour;
+        assert_eq!(game_clock.next_morning(), tomorrow);
+        game_clock.run_until(tomorrow);
+        while !game_clock.paused {
+            game_clock.tick();
+        }
+        assert_eq!(game_clock.current_tick, tomorrow);
+        assert_eq!(game_clock.hour(), 6.0);
+
+        game_clock.run_until(game_clock.start_of_day(5));
+        while !game_clock.paused {
+            game_clock.tick();
+        }
+        assert_eq!(game_clock.day(), 5);
+        assert!(game_clock.is_day_start());
+    }
+
+    #[test]
+    fn test_leaving_a_city_starts_the_next_one_fresh() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(SimSeed(42))
+            .init_resource::<DirtyChunks>()
+            .add_plugins(SimulationPlugin { schedule: Update.intern() })
+            .add_systems(Startup, spawn_tilemap);
+        app.update();
+        let world = app.world_mut();
+        let founders = world.query::<&Pop>().iter(world).count();
+        for _ in 0..100 {
+            app.update();
+        }
+
+        let world = app.world_mut();
+        world.run_system_once(despawn_city).unwrap();
+        world.run_system_once(reset_simulation).unwrap();
+        assert_eq!(world.query::<&Pop>().iter(world).count(), 0);
+        assert_eq!(world.query::<&TilePos>().iter(world).count(), 0);
+        assert_eq!(world.resource::<GameClock>().current_tick, 0);
+
+        // The same seed founds the same city again, only once
+        world.run_system_once(spawn_tilemap).unwrap();
+        assert_eq!(world.query::<&Tilemap>().iter(world).count(), 1);
+        assert_eq!(world.query::<&Pop>().iter(world).count(), founders);
+    }
+
+    #[test]
+    fn test_wages_follow_vacancies_and_unemployment() {
+        let mut app = App::new();
+        let geometry = MapGeometry::new(DEFAULT_MAP_SIZE);
+        let mut game_clock = GameClock::default();
+        game_clock.current_tick = game_clock.ticks_per_day();
+        app.insert_resource(game_clock)
+            .insert_resource(geometry)
+            .init_resource::<JobMarket>();
+
+        let world = app.world_mut();
+        let understaffed_tile = TilePos { x: 2, y: 2 };
+        let understaffed = world.spawn(Workplace {
+            capacity: 10,
+            employees: Vec::new(),
+            position: understaffed_tile,
+            wage: BASE_WAGE,
+            treasury: FIRM_STARTING_CAPITAL,
+            product: Product::Goods,
+            inventory: 0.0,
+            sales: 0,
+            daily_sales: 0,
+        }).id();
+        // A full workplace on the far side of the map, surrounded by pops looking for work
+        let full_tile = TilePos { x: 28, y: 28 };
+        let full = world.spawn_empty().id();
+        let employee = world.spawn(Pop {
+            position: geometry.tile_center(full_tile),
+            job: Some(Job { workplace: full, salary: BASE_WAGE, position: full_tile, last_worked_day: 1 }),
+            ..Default::default()
+        }).id();
+        world.entity_mut(full).insert(Workplace {
+            capacity: 1,
+            employees: vec![employee],
+            position: full_tile,
+            wage: BASE_WAGE,
+            treasury: FIRM_STARTING_CAPITAL,
+            product: Product::Goods,
+            inventory: 0.0,
+            sales: 0,
+            daily_sales: 0,
+        });
+        for _ in 0..3 {
+            world.spawn(Pop {
+                position: geometry.tile_center(full_tile),
+                ..Default::default()
+            });
+        }
+
+        world.run_system_once(review_wages).unwrap();
+
+        let world = app.world();
+        assert!(world.get::<Workplace>(understaffed).unwrap().wage > BASE_WAGE);
+        let cut_wage = world.get::<Workplace>(full).unwrap().wage;
+        assert!(cut_wage < BASE_WAGE);
+        assert_eq!(world.get::<Pop>(employee).unwrap().job.as_ref().unwrap().salary, cut_wage);
+        assert_eq!(world.resource::<JobMarket>().median_wage, cut_wage);
+    }
+
+    #[test]
+    fn test_wages_follow_sales() {
+        let mut app = App::new();
+        let geometry = MapGeometry::new(DEFAULT_MAP_SIZE);
+        let mut game_clock = GameClock::default();
+        game_clock.current_tick = game_clock.ticks_per_day();
+        app.insert_resource(game_clock)
+            .insert_resource(geometry)
+            .init_resource::<JobMarket>();
+
+        // Two full workplaces with nobody around looking for work, one sold twice its payroll
+        let world = app.world_mut();
+        let mut full_workplace = |x, daily_sales| {
+            let position = TilePos { x, y: 2 };
+            let workplace = world.spawn_empty().id();
+            let employee = world.spawn(Pop {
+                position: geometry.tile_center(position),
+                job: Some(Job { workplace, salary: BASE_WAGE, position, last_worked_day: 1 }),
+                ..Default::default()
+            }).id();
+            world.entity_mut(workplace).insert(Workplace {
+                capacity: 1,
+                employees: vec![employee],
+                position,
+                wage: BASE_WAGE,
+                treasury: FIRM_STARTING_CAPITAL,
+                product: Product::Goods,
+                inventory: 0.0,
+                sales: daily_sales,
+                daily_sales,
+            });
+            workplace
+        };
+        let selling = full_workplace(2, (2.0 * BASE_WAGE) as i32);
+        let idle = full_workplace(28, 0);
+
+        world.run_system_once(review_wages).unwrap();
+
+        let world = app.world();
+        let selling = world.get::<Workplace>(selling).unwrap();
+        assert!(selling.wage > BASE_WAGE);
+        assert_eq!(selling.daily_sales, 0, "Sales are counted anew for the next review");
+        assert!(world.get::<Workplace>(idle).unwrap().wage < BASE_WAGE);
+    }
+
+    #[test]
+    fn test_rent_is_collected_and_debtors_evicted() {
+        let mut app = App::new();
+        let mut game_clock = GameClock::default();
+        game_clock.current_tick = game_clock.ticks_per_day();
+        app.insert_resource(game_clock);
+
+        let world = app.world_mut();
+        // Children live rent-free, so both are adults
+        let tenant = world.spawn(Pop { money: 100, age: ADULT_AGE, ..Default::default() }).id();
+        let debtor = world.spawn(Pop {
+            money: 0,
+            age: ADULT_AGE,
+            unpaid_rent_days: EVICTION_DAYS - 1,
+            ..Default::default()
+        }).id();
+        let house = world.spawn(House {
+            capacity: 4,
+            residents: vec![tenant, debtor],
+            position: TilePos { x: 0, y: 0 },
+            rent: BASE_RENT,
+            rent_collected: 0.0,
+        }).id();
+        world.entity_mut(tenant).get_mut::<Pop>().unwrap().home = Some(house);
+        world.entity_mut(debtor).get_mut::<Pop>().unwrap().home = Some(house);
+
+        world.run_system_once(collect_rent).unwrap();
+
+        let world = app.world();
+        assert_eq!(world.get::<Pop>(tenant).unwrap().money, 100 - BASE_RENT as i32);
+        assert_eq!(world.get::<Pop>(debtor).unwrap().home, None);
+        let house = world.get::<House>(house).unwrap();
+        assert_eq!(house.residents, vec![tenant]);
+        // A quarter full is below the target occupancy, so the landlord lowers the rent
+        assert!(house.rent < BASE_RENT);
+    }
+
+    #[test]
+    fn test_hungry_pops_eat_before_going_to_work() {
+        let mut app = App::new();
+        let geometry = MapGeometry::new(DEFAULT_MAP_SIZE);
+        let mut game_clock = GameClock::default();
+        game_clock.current_tick = 10 * game_clock.ticks_per_hour;
+        app.insert_resource(game_clock)
+            .insert_resource(geometry)
+            .insert_resource(SimRng::new(SimSeed(42)));
+
+        // A rested, hungry worker standing in a stocked restaurant during work hours
+        let world = app.world_mut();
+        let restaurant_position = TilePos { x: 2, y: 2 };
+        let restaurant = world.spawn(Restaurant {
+            capacity: 1,
+            position: restaurant_position,
+            diners: Vec::new(),
+            queue: Default::default(),
+            revenue: 0,
+            food: 100.0,
+            goods: 100.0,
+        }).id();
+        let workplace_position = TilePos { x: 28, y: 2 };
+        let workplace = world.spawn(Workplace {
+            capacity: 1,
+            employees: Vec::new(),
+            position: workplace_position,
+            wage: BASE_WAGE,
+            treasury: FIRM_STARTING_CAPITAL,
+            product: Product::Goods,
+            inventory: 0.0,
+            sales: 0,
+            daily_sales: 0,
+        }).id();
+        let pop = world.spawn(Pop {
+            money: 100,
+            hunger: 8000,
+            energy: 10000,
+            position: geometry.tile_center(restaurant_position),
+            job: Some(Job { workplace, salary: BASE_WAGE, position: workplace_position, last_worked_day: 1 }),
+            ..Default::default()
+        }).id();
+        world.get_mut::<Workplace>(workplace).unwrap().employees.push(pop);
+
+        for _ in 0..10 {
+            world.run_system_once(update_pops).unwrap();
+            world.run_system_once(serve_restaurants).unwrap();
+        }
+
+        let world = app.world();
+        let eater = world.get::<Pop>(pop).unwrap();
+        assert_eq!(eater.state, PopState::Eating);
+        assert!(eater.hunger < 8000, "The pop should have been fed");
+        assert_eq!(world.get::<Restaurant>(restaurant).unwrap().diners, vec![pop]);
+    }
 
     proptest! {
         #[test]
         fn test_pop_movement(
-            start_x in 0.0f32..(MAP_SIZE.x as f32),
-            start_y in 0.0f32..(MAP_SIZE.y as f32),
-            dest_x in 0.0f32..(MAP_SIZE.x as f32),
-            dest_y in 0.0f32..(MAP_SIZE.y as f32),
+            start_x in 0.0f32..(DEFAULT_MAP_SIZE.x as f32),
+            start_y in 0.0f32..(DEFAULT_MAP_SIZE.y as f32),
+            dest_x in 0.0f32..(DEFAULT_MAP_SIZE.x as f32),
+            dest_y in 0.0f32..(DEFAULT_MAP_SIZE.y as f32),
             num_ticks in 1u32..50
         ) {
             let mut app = App::new();
@@ -648,13 +1669,13 @@ mod tests {
             let start_pos = Vec2::new(start_x, start_y);
             let dest_pos = Vec2::new(dest_x, dest_y);
 
-            // Spawn the TilemapSize
-            app.world_mut().spawn(TilemapSize { x: MAP_SIZE.x, y: MAP_SIZE.y });
+            // Positions are well inside the map, so clamping to its bounds never kicks in
+            app.insert_resource(MapGeometry::new(DEFAULT_MAP_SIZE));
 
             let pop_entity = app.world_mut().spawn((
                 Pop {
-                    position: start_pos,
-                    destination: Some(dest_pos),
+                    position: WorldPos(start_pos),
+                    destination: Some(WorldPos(dest_pos)),
                     ..Default::default()
                 },
             )).id();
@@ -669,10 +1690,10 @@ mod tests {
             let pop = app.world().get::<Pop>(pop_entity).unwrap();
 
             // Calculate distance moved
-            let distance_moved = start_pos.distance(pop.position);
+            let distance_moved = start_pos.distance(pop.position.0);
 
             // Calculate distance to destination
-            let distance_to_dest = pop.position.distance(dest_pos);
+            let distance_to_dest = pop.position.0.distance(dest_pos);
 
             // Define movement speed (this should match the speed in your move_pops function)
             let speed = 0.1 * TILE_SIZE;
@@ -690,10 +1711,10 @@ mod tests {
 
             // If we've had enough ticks to reach the destination, assert that we're there
             if (speed * num_ticks as f32) >= total_distance {
-                prop_assert!((pop.position - dest_pos).length() < 0.001, "Pop should be at the destination");
+                prop_assert!((pop.position.0 - dest_pos).length() < 0.001, "Pop should be at the destination");
                 prop_assert_eq!(pop.destination, None, "Destination should be cleared when reached");
             } else {
-                prop_assert_eq!(pop.destination, Some(dest_pos), "Destination should remain if not reached");
+                prop_assert_eq!(pop.destination, Some(WorldPos(dest_pos)), "Destination should remain if not reached");
             }
         }
     }
@@ -0,0 +1,399 @@
+use std::fmt;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::constants::{
+    CITY_STARTING_FUNDS, DEBT_INTEREST_RATE, INCOME_TAX_RATE, MAX_TAX_INTERVAL_DAYS, MAX_TAX_RATE,
+    MAX_WELFARE, PROPERTY_TAX_RATE, ROAD_UPKEEP, SALES_TAX_RATE, TAX_INTERVAL_DAYS,
+    WELFARE_PER_HOMELESS,
+};
+use crate::tilemap::{GameClock, House, Pop, Road, Workplace};
+use crate::GameState;
+
+pub struct PolicyPlugin;
+
+/// This plugin shows the policy panel: the tax rates and welfare the player can adjust,
+/// and the latest daily budget of the city. The budget itself runs in the simulation.
+impl Plugin for PolicyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Playing), spawn_policy_panel)
+            .add_systems(
+                Update,
+                (click_policy_buttons, update_policy_panel)
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnExit(GameState::Playing), despawn_policy_panel);
+    }
+}
+
+/// Tax rates and spending the city council decided on
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+pub struct TaxPolicy {
+    /// Share of their earnings pops pay
+    pub income_tax: f32,
+    /// Share of the rent a house brought in
+    pub property_tax: f32,
+    /// Share of what workplaces sold
+    pub sales_tax: f32,
+    /// Taxes are collected every this many days
+    pub collection_interval_days: u32,
+    /// Paid daily to every homeless adult
+    pub welfare_per_homeless: i32,
+}
+
+impl Default for TaxPolicy {
+    fn default() -> Self {
+        Self {
+            income_tax: INCOME_TAX_RATE,
+            property_tax: PROPERTY_TAX_RATE,
+            sales_tax: SALES_TAX_RATE,
+            collection_interval_days: TAX_INTERVAL_DAYS,
+            welfare_per_homeless: WELFARE_PER_HOMELESS,
+        }
+    }
+}
+
+impl TaxPolicy {
+    fn is_collection_day(&self, day: u64) -> bool {
+        day % self.collection_interval_days.max(1) as u64 == 0
+    }
+}
+
+/// The city's own money. The balance goes negative when the city is in debt.
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+pub struct CityTreasury {
+    pub balance: i64,
+    /// Budget of the most recent day
+    pub last_budget: Budget,
+}
+
+impl Default for CityTreasury {
+    fn default() -> Self {
+        Self {
+            balance: CITY_STARTING_FUNDS,
+            last_budget: Budget::default(),
+        }
+    }
+}
+
+/// Income and spending of the city over one day
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct Budget {
+    pub day: u64,
+    pub income_tax: i64,
+    pub property_tax: i64,
+    pub sales_tax: i64,
+    pub welfare: i64,
+    pub road_upkeep: i64,
+    pub interest: i64,
+}
+
+impl Budget {
+    pub fn income(&self) -> i64 {
+        self.income_tax + self.property_tax + self.sales_tax
+    }
+
+    pub fn spending(&self) -> i64 {
+        self.welfare + self.road_upkeep + self.interest
+    }
+}
+
+impl fmt::Display for Budget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Budget for day {}: {:+}\n\
+            Taxes: ${} (income ${}, property ${}, sales ${})\n\
+            Spending: ${} (welfare ${}, roads ${}, interest ${})",
+            self.day,
+            self.income() - self.spending(),
+            self.income(),
+            self.income_tax,
+            self.property_tax,
+            self.sales_tax,
+            self.spending(),
+            self.welfare,
+            self.road_upkeep,
+            self.interest
+        )
+    }
+}
+
+impl fmt::Display for CityTreasury {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = if self.balance < 0 { " (in debt)" } else { "" };
+        write!(f, "City Funds: ${}{}\n{}", self.balance, status, self.last_budget)
+    }
+}
+
+/// Once a day the city pays welfare, road upkeep and the interest on its debt. On collection days
+/// it also taxes what pops earned, houses took in as rent and workplaces sold since the last one.
+/// Pops and workplaces pay what they can, the rest is forgiven.
+pub(crate) fn balance_budget(
+    game_clock: Res<GameClock>,
+    policy: Res<TaxPolicy>,
+    mut treasury: ResMut<CityTreasury>,
+    mut pop_query: Query<&mut Pop>,
+    mut house_query: Query<&mut House>,
+    mut workplace_query: Query<&mut Workplace>,
+    road_query: Query<(), With<Road>>,
+) {
+    if !game_clock.is_day_start() {
+        return;
+    }
+
+    let day = game_clock.day();
+    let mut budget = Budget {
+        day,
+        ..default()
+    };
+
+    if policy.is_collection_day(day) {
+        for mut pop in pop_query.iter_mut() {
+            let owed = (pop.earnings as f32 * policy.income_tax).round() as i32;
+            let paid = owed.min(pop.money.max(0));
+            pop.money -= paid;
+            pop.earnings = 0;
+            budget.income_tax += paid as i64;
+        }
+        // Landlords aren't modelled, so the city simply takes its share of the rent
+        for mut house in house_query.iter_mut() {
+            budget.property_tax += (house.rent_collected * policy.property_tax).round() as i64;
+            house.rent_collected = 0.0;
+        }
+        for mut workplace in workplace_query.iter_mut() {
+            let owed = (workplace.sales as f32 * policy.sales_tax).round() as i32;
+            let paid = owed.min(workplace.treasury.max(0));
+            workplace.treasury -= paid;
+            workplace.sales = 0;
+            budget.sales_tax += paid as i64;
+        }
+    }
+
+    for mut pop in pop_query.iter_mut() {
+        if pop.is_adult() && pop.home.is_none() {
+            pop.money = pop.money.saturating_add(policy.welfare_per_homeless);
+            budget.welfare += policy.welfare_per_homeless as i64;
+        }
+    }
+    budget.road_upkeep = road_query.iter().count() as i64 * ROAD_UPKEEP;
+    if treasury.balance < 0 {
+        budget.interest = (-treasury.balance as f64 * DEBT_INTEREST_RATE).ceil() as i64;
+    }
+
+    treasury.balance += budget.income() - budget.spending();
+    treasury.last_budget = budget;
+}
+
+#[derive(Component)]
+struct PolicyPanel;
+
+#[derive(Component)]
+struct BudgetText;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PolicyKnob {
+    IncomeTax,
+    PropertyTax,
+    SalesTax,
+    CollectionInterval,
+    Welfare,
+}
+
+impl PolicyKnob {
+    const ALL: [PolicyKnob; 5] = [
+        PolicyKnob::IncomeTax,
+        PolicyKnob::PropertyTax,
+        PolicyKnob::SalesTax,
+        PolicyKnob::CollectionInterval,
+        PolicyKnob::Welfare,
+    ];
+
+    fn describe(self, policy: &TaxPolicy) -> String {
+        match self {
+            PolicyKnob::IncomeTax => format!("Income tax: {:.0}%", policy.income_tax * 100.0),
+            PolicyKnob::PropertyTax => format!("Property tax: {:.0}%", policy.property_tax * 100.0),
+            PolicyKnob::SalesTax => format!("Sales tax: {:.0}%", policy.sales_tax * 100.0),
+            PolicyKnob::CollectionInterval => {
+                format!("Collect every {} days", policy.collection_interval_days)
+            }
+            PolicyKnob::Welfare => format!("Welfare: ${}/day", policy.welfare_per_homeless),
+        }
+    }
+
+    /// Moves the knob one notch up or down, `direction` is either 1 or -1
+    fn turn(self, policy: &mut TaxPolicy, direction: i32) {
+        let step_rate = |rate: &mut f32| {
+            *rate = (*rate + 0.01 * direction as f32).clamp(0.0, MAX_TAX_RATE);
+        };
+        match self {
+            PolicyKnob::IncomeTax => step_rate(&mut policy.income_tax),
+            PolicyKnob::PropertyTax => step_rate(&mut policy.property_tax),
+            PolicyKnob::SalesTax => step_rate(&mut policy.sales_tax),
+            PolicyKnob::CollectionInterval => {
+                policy.collection_interval_days = policy
+                    .collection_interval_days
+                    .saturating_add_signed(direction)
+                    .clamp(1, MAX_TAX_INTERVAL_DAYS);
+            }
+            PolicyKnob::Welfare => {
+                policy.welfare_per_homeless =
+                    (policy.welfare_per_homeless + 5 * direction).clamp(0, MAX_WELFARE);
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+struct PolicyButton {
+    knob: PolicyKnob,
+    direction: i32,
+}
+
+#[derive(Component)]
+struct PolicyValue(PolicyKnob);
+
+const BUTTON_NORMAL: Color = Color::linear_rgb(0.15, 0.15, 0.15);
+const BUTTON_HOVERED: Color = Color::linear_rgb(0.25, 0.25, 0.25);
+
+fn spawn_policy_panel(mut commands: Commands) {
+    let text_font = TextFont {
+        font_size: 16.0,
+        ..default()
+    };
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(10.0),
+                right: Val::Px(10.0),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(4.0),
+                padding: UiRect::all(Val::Px(8.0)),
+                ..default()
+            },
+            BackgroundColor(Color::linear_rgba(0.0, 0.0, 0.0, 0.6)),
+            PolicyPanel,
+        ))
+        .with_children(|panel| {
+            for knob in PolicyKnob::ALL {
+                panel
+                    .spawn(Node {
+                        flex_direction: FlexDirection::Row,
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(4.0),
+                        ..default()
+                    })
+                    .with_children(|row| {
+                        for (label, direction) in [("-", -1), ("+", 1)] {
+                            row.spawn((
+                                Button,
+                                Node {
+                                    width: Val::Px(24.0),
+                                    height: Val::Px(24.0),
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                BackgroundColor(BUTTON_NORMAL),
+                                PolicyButton { knob, direction },
+                            ))
+                            .with_child((Text::new(label), text_font.clone()));
+                        }
+                        row.spawn((Text::default(), text_font.clone(), PolicyValue(knob)));
+                    });
+            }
+            panel.spawn((Text::default(), text_font.clone(), BudgetText));
+        });
+}
+
+fn despawn_policy_panel(mut commands: Commands, panel_query: Query<Entity, With<PolicyPanel>>) {
+    for entity in panel_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn click_policy_buttons(
+    mut button_query: Query<
+        (&Interaction, &PolicyButton, &mut BackgroundColor),
+        Changed<Interaction>,
+    >,
+    mut policy: ResMut<TaxPolicy>,
+) {
+    for (interaction, button, mut color) in button_query.iter_mut() {
+        if *interaction == Interaction::Pressed {
+            button.knob.turn(&mut policy, button.direction);
+        }
+        *color = if *interaction == Interaction::None {
+            BUTTON_NORMAL.into()
+        } else {
+            BUTTON_HOVERED.into()
+        };
+    }
+}
+
+fn update_policy_panel(
+    policy: Res<TaxPolicy>,
+    treasury: Res<CityTreasury>,
+    mut value_query: Query<(&mut Text, &PolicyValue), Without<BudgetText>>,
+    mut budget_query: Query<&mut Text, With<BudgetText>>,
+) {
+    for (mut text, value) in value_query.iter_mut() {
+        **text = value.0.describe(&policy);
+    }
+    for mut text in budget_query.iter_mut() {
+        **text = treasury.to_string();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::*;
+
+    use crate::constants::{ADULT_AGE, CITY_STARTING_FUNDS};
+    use crate::tilemap::{GameClock, Pop};
+    use crate::treasury::{balance_budget, CityTreasury, TaxPolicy};
+
+    #[test]
+    fn test_taxes_are_collected_on_schedule_and_welfare_paid_daily() {
+        let mut world = World::new();
+        let policy = TaxPolicy {
+            income_tax: 0.1,
+            collection_interval_days: 2,
+            welfare_per_homeless: 5,
+            ..default()
+        };
+        let mut game_clock = GameClock::default();
+        // Day 2 starts
+        game_clock.current_tick = game_clock.ticks_per_day();
+        world.insert_resource(game_clock);
+        world.insert_resource(policy);
+        world.init_resource::<CityTreasury>();
+        let worker = world.spawn(Pop {
+            age: ADULT_AGE,
+            money: 50,
+            earnings: 200,
+            ..default()
+        }).id();
+
+        world.run_system_once(balance_budget).unwrap();
+        // Paid 20 in income tax and got 5 in welfare for being homeless
+        assert_eq!(world.get::<Pop>(worker).unwrap().money, 35);
+        assert_eq!(world.get::<Pop>(worker).unwrap().earnings, 0);
+        let treasury = world.resource::<CityTreasury>();
+        assert_eq!(treasury.last_budget.income_tax, 20);
+        assert_eq!(treasury.balance, CITY_STARTING_FUNDS + 20 - 5);
+
+        // No taxes on day 3, the city goes into debt paying welfare
+        world.resource_mut::<CityTreasury>().balance = 0;
+        world.get_mut::<Pop>(worker).unwrap().earnings = 200;
+        let ticks_per_day = world.resource::<GameClock>().ticks_per_day();
+        world.resource_mut::<GameClock>().current_tick += ticks_per_day;
+        world.run_system_once(balance_budget).unwrap();
+        assert_eq!(world.get::<Pop>(worker).unwrap().money, 40);
+        assert_eq!(world.resource::<CityTreasury>().balance, -5);
+    }
+}
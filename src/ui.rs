@@ -1,12 +1,66 @@
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
-use crate::tilemap::{GameClock, House, Pop, Workplace};
+use crate::constants::MAX_SPEED;
+use crate::lifecycle::Demographics;
+use crate::rng::SimSeed;
+use crate::scenario::ActiveScenario;
+use crate::stats::CityStats;
+use crate::tilemap::{GameClock, House, JobMarket, Pop, Restaurant, Workplace};
+use crate::{GameState, PlayState};
 
 pub struct UiPlugin;
 
+/// This plugin shows the clock and city numbers in the top left corner while playing,
+/// with the time controls below them: pause, speed presets, stepping and running ahead.
+/// They are the only way to change the speed without a keyboard, as on mobile.
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, setup_ui)
-            .add_systems(Update, (update_ui, handle_speed_buttons));
+        app.init_resource::<TargetDay>()
+            .add_systems(OnEnter(GameState::Playing), setup_ui)
+            .add_systems(
+                Update,
+                (update_ui, update_time_controls).run_if(in_state(GameState::Playing)),
+            )
+            // Like the speed keys, the controls are off while the pause menu is open
+            .add_systems(
+                Update,
+                (handle_speed_buttons, click_time_controls).run_if(in_state(PlayState::Running)),
+            )
+            .add_systems(OnExit(GameState::Playing), despawn_ui);
+    }
+}
+
+/// Tells whether the pointer is on the UI, in which case clicks and drags are not meant for the map
+#[derive(SystemParam)]
+pub(crate) struct PointerOverUi<'w, 's> {
+    interactions: Query<'w, 's, &'static Interaction>,
+    nodes: Query<
+        'w,
+        's,
+        (
+            &'static ComputedNode,
+            &'static GlobalTransform,
+            &'static InheritedVisibility,
+            &'static BackgroundColor,
+            Has<Text>,
+        ),
+    >,
+}
+
+impl PointerOverUi<'_, '_> {
+    /// Whether `screen_position`, in logical pixels, is on a visible panel, button or text,
+    /// or a button is still held from a press that started on it
+    pub(crate) fn at(&self, screen_position: Vec2) -> bool {
+        if self.interactions.iter().any(|interaction| *interaction != Interaction::None) {
+            return true;
+        }
+        // Layout is in physical pixels. Transparent nodes only arrange their children.
+        self.nodes.iter().any(|(node, transform, visibility, background, is_text)| {
+            let area = Rect::from_center_size(transform.translation().truncate(), node.size());
+            visibility.get()
+                && (is_text || background.0.alpha() > 0.0)
+                && area.contains(screen_position / node.inverse_scale_factor())
+        })
     }
 }
 
@@ -14,105 +68,243 @@ impl Plugin for UiPlugin {
 struct GameInfoText;
 
 #[derive(Component)]
-struct SpeedButton(f32);
+struct SpeedButton(u32);
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+enum TimeControl {
+    PlayPause,
+    StepTick,
+    NextMorning,
+    EarlierDay,
+    LaterDay,
+    RunUntilDay,
+    Menu,
+}
+
+impl TimeControl {
+    fn label(self) -> &'static str {
+        match self {
+            TimeControl::PlayPause => "Pause",
+            TimeControl::StepTick => "Step",
+            TimeControl::NextMorning => "Morning",
+            TimeControl::EarlierDay => "-",
+            TimeControl::LaterDay => "+",
+            TimeControl::RunUntilDay => "Run",
+            TimeControl::Menu => "Menu",
+        }
+    }
+}
+
+/// Text of the play/pause button, which shows what pressing it does
+#[derive(Component)]
+struct PlayPauseText;
+
+#[derive(Component)]
+struct TargetDayText;
+
+/// Day the "run until day" control runs to, always one still to come
+#[derive(Resource, Default)]
+struct TargetDay(u64);
+
+const BUTTON_NORMAL: Color = Color::linear_rgb(0.15, 0.15, 0.15);
+const BUTTON_HOVERED: Color = Color::linear_rgb(0.25, 0.25, 0.25);
+const BUTTON_SELECTED: Color = Color::linear_rgb(0.2, 0.4, 0.6);
 
 fn setup_ui(mut commands: Commands) {
-    // Spawn the parent node
+    let text_font = TextFont {
+        font_size: 16.0,
+        ..default()
+    };
+    let button_node = |width: f32| Node {
+        width: Val::Px(width),
+        height: Val::Px(28.0),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    let row_node = Node {
+        flex_direction: FlexDirection::Row,
+        align_items: AlignItems::Center,
+        column_gap: Val::Px(4.0),
+        ..default()
+    };
+
+    // Spawn the parent node, the info text has to stay its first child
     commands
         .spawn((
             Node {
                 position_type: PositionType::Absolute,
                 top: Val::Px(10.0),
                 left: Val::Px(10.0),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(4.0),
                 ..default()
             },
             GameInfoText,
         ))
-        .with_child((
-            Text::new("Game Info"),
-            TextFont {
-                font_size: 20.0,
-                ..default()
-            },
-            TextColor(Color::WHITE),
-            TextLayout::default(),
-        ));
+        .with_children(|info| {
+            info.spawn((
+                Text::new("Game Info"),
+                TextFont {
+                    font_size: 20.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+                TextLayout::default(),
+            ));
+            info.spawn(row_node.clone()).with_children(|row| {
+                for control in [TimeControl::PlayPause, TimeControl::StepTick, TimeControl::NextMorning, TimeControl::Menu] {
+                    let mut button = row.spawn((Button, button_node(72.0), BackgroundColor(BUTTON_NORMAL), control));
+                    if control == TimeControl::PlayPause {
+                        button.with_child((Text::new(control.label()), text_font.clone(), PlayPauseText));
+                    } else {
+                        button.with_child((Text::new(control.label()), text_font.clone()));
+                    }
+                }
+            });
+            info.spawn(row_node.clone()).with_children(|row| {
+                for speed in (0..=MAX_SPEED.ilog2()).map(|power| 1 << power) {
+                    row.spawn((Button, button_node(40.0), BackgroundColor(BUTTON_NORMAL), SpeedButton(speed)))
+                        .with_child((Text::new(format!("{speed}x")), text_font.clone()));
+                }
+            });
+            info.spawn(row_node).with_children(|row| {
+                row.spawn((Text::new("Run until"), text_font.clone()));
+                row.spawn((Button, button_node(28.0), BackgroundColor(BUTTON_NORMAL), TimeControl::EarlierDay))
+                    .with_child((Text::new(TimeControl::EarlierDay.label()), text_font.clone()));
+                row.spawn((Text::default(), text_font.clone(), TargetDayText));
+                row.spawn((Button, button_node(28.0), BackgroundColor(BUTTON_NORMAL), TimeControl::LaterDay))
+                    .with_child((Text::new(TimeControl::LaterDay.label()), text_font.clone()));
+                row.spawn((Button, button_node(48.0), BackgroundColor(BUTTON_NORMAL), TimeControl::RunUntilDay))
+                    .with_child((Text::new(TimeControl::RunUntilDay.label()), text_font));
+            });
+        });
+}
+
+fn despawn_ui(mut commands: Commands, info_query: Query<Entity, With<GameInfoText>>) {
+    for entity in info_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
 }
 
 fn update_ui(
     text_query: Query<&Children, With<GameInfoText>>,
     mut text_span_query: Query<&mut Text>,
     game_clock: Res<GameClock>,
+    seed: Res<SimSeed>,
+    job_market: Res<JobMarket>,
+    demographics: Res<Demographics>,
+    scenario: Option<Res<ActiveScenario>>,
     pop_query: Query<&Pop>,
     house_query: Query<&House>,
     workplace_query: Query<&Workplace>,
+    restaurant_query: Query<&Restaurant>,
 ) {
     if let Ok(children) = text_query.get_single() {
         // Get the first child which should be our text entity
         if let Some(&text_entity) = children.first() {
             if let Ok(mut text) = text_span_query.get_mut(text_entity) {
-                let pop_count = pop_query.iter().count();
-                let total_money: i32 = pop_query.iter().map(|pop| pop.money).sum();
-                let average_money = if pop_count > 0 { total_money as f32 / pop_count as f32 } else { 0.0 };
-
-                let employed_count = pop_query.iter().filter(|pop| pop.job.is_some()).count();
-                let homeless_count = pop_query.iter().filter(|pop| pop.home.is_none()).count();
-
-                let total_hunger: u32 = pop_query.iter().map(|pop| pop.hunger).sum();
-                let average_hunger = if pop_count > 0 { total_hunger as f32 / pop_count as f32 } else { 0.0 };
-
-                let total_energy: u32 = pop_query.iter().map(|pop| pop.energy).sum();
-                let average_energy = if pop_count > 0 { total_energy as f32 / pop_count as f32 } else { 0.0 };
-
-                let house_count = house_query.iter().count();
-                let total_house_capacity: u32 = house_query.iter().map(|house| house.capacity).sum();
-
-                let workplace_count = workplace_query.iter().count();
-                let total_job_capacity: u32 = workplace_query.iter().map(|workplace| workplace.capacity).sum();
+                let mut stats = CityStats::collect(
+                    &pop_query,
+                    &house_query,
+                    &workplace_query,
+                    &restaurant_query,
+                );
+                stats.wage_trend = job_market.wage_trend();
 
                 **text = format!(
-                    "Day: {}, Time: {:02}:{:02}\nSpeed: {}x({} ticks/sec)\n\n\
-                    Population: {}\n\
-                    Employed: {} ({:.1}%)\n\
-                    Homeless: {} ({:.1}%)\n\
-                    Average Money: ${:.2}\n\
-                    Average Hunger: {:.1}/10000\n\
-                    Average Energy: {:.1}/10000\n\n\
-                    Houses: {} (Capacity: {})\n\
-                    Workplaces: {} (Capacity: {})",
+                    "Day: {}, Time: {:02}:{:02}\nSpeed: {}x({} ticks/sec)\nSeed: {}\n\n{}\n{}",
                     game_clock.day(),
                     game_clock.hour(),
                     (game_clock.hour().fract() * 60.0) as u32,
                     game_clock.speed,
                     game_clock.ticks_per_second(),
-                    pop_count,
-                    employed_count,
-                    if pop_count > 0 { (employed_count as f32 / pop_count as f32) * 100.0 } else { 0.0 },
-                    homeless_count,
-                    if pop_count > 0 { (homeless_count as f32 / pop_count as f32) * 100.0 } else { 0.0 },
-                    average_money,
-                    average_hunger,
-                    average_energy,
-                    house_count,
-                    total_house_capacity,
-                    workplace_count,
-                    total_job_capacity
+                    seed.0,
+                    stats,
+                    *demographics
                 );
+                if let Some(scenario) = &scenario {
+                    text.push_str(&format!("\n\n{}", **scenario));
+                }
             }
         }
     }
 }
 
+/// Sets the speed of the pressed preset and highlights the active one,
+/// which may also have been picked with the Z and X keys
 fn handle_speed_buttons(
     mut interaction_query: Query<
-        (&Interaction, &SpeedButton),
-        (Changed<Interaction>, With<Button>),
+        (&Interaction, &SpeedButton, &mut BackgroundColor),
+        With<Button>,
     >,
     mut game_clock: ResMut<GameClock>,
 ) {
-    for (interaction, speed_button) in interaction_query.iter_mut() {
+    for (interaction, speed_button, _) in interaction_query.iter() {
         if *interaction == Interaction::Pressed {
-            game_clock.speed = speed_button.0 as u32;
+            game_clock.speed = speed_button.0;
         }
     }
+    for (interaction, speed_button, mut color) in interaction_query.iter_mut() {
+        *color = if game_clock.speed == speed_button.0 {
+            BUTTON_SELECTED.into()
+        } else if *interaction == Interaction::None {
+            BUTTON_NORMAL.into()
+        } else {
+            BUTTON_HOVERED.into()
+        };
+    }
+}
+
+fn click_time_controls(
+    mut button_query: Query<(&Interaction, &TimeControl, &mut BackgroundColor), Changed<Interaction>>,
+    mut game_clock: ResMut<GameClock>,
+    mut target_day: ResMut<TargetDay>,
+    mut next_play_state: ResMut<NextState<PlayState>>,
+) {
+    for (interaction, control, mut color) in button_query.iter_mut() {
+        if *interaction == Interaction::Pressed {
+            match control {
+                TimeControl::PlayPause => game_clock.toggle_pause(),
+                TimeControl::StepTick => {
+                    let next_tick = game_clock.current_tick + 1;
+                    game_clock.run_until(next_tick);
+                }
+                TimeControl::NextMorning => {
+                    let morning = game_clock.next_morning();
+                    game_clock.run_until(morning);
+                }
+                TimeControl::EarlierDay => target_day.0 = target_day.0.saturating_sub(1),
+                TimeControl::LaterDay => target_day.0 += 1,
+                TimeControl::RunUntilDay => {
+                    let start = game_clock.start_of_day(target_day.0);
+                    game_clock.run_until(start);
+                }
+                TimeControl::Menu => next_play_state.set(PlayState::Paused),
+            }
+        }
+        *color = if *interaction == Interaction::None {
+            BUTTON_NORMAL.into()
+        } else {
+            BUTTON_HOVERED.into()
+        };
+    }
+}
+
+fn update_time_controls(
+    game_clock: Res<GameClock>,
+    mut target_day: ResMut<TargetDay>,
+    mut play_pause_query: Query<&mut Text, (With<PlayPauseText>, Without<TargetDayText>)>,
+    mut target_day_query: Query<&mut Text, With<TargetDayText>>,
+) {
+    let tomorrow = game_clock.day() + 1;
+    if target_day.0 < tomorrow {
+        target_day.0 = tomorrow;
+    }
+    for mut text in play_pause_query.iter_mut() {
+        **text = if game_clock.paused { "Play" } else { TimeControl::PlayPause.label() }.to_string();
+    }
+    for mut text in target_day_query.iter_mut() {
+        **text = format!("Day {}", target_day.0);
+    }
 }
\ No newline at end of file